@@ -0,0 +1,63 @@
+#ifndef DICEROLL_H
+#define DICEROLL_H
+
+/*
+ * C bindings for diceroll, built into libdiceroll_ffi by the diceroll-ffi
+ * crate:
+ *
+ *     cargo build --release -p diceroll-ffi
+ */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* A parsed dice expression. */
+typedef struct DiceExpr DiceExpr;
+
+/* A die rolled, and whether it counts towards the total. */
+typedef struct DicerollDie {
+    int64_t value;
+    bool kept;
+} DicerollDie;
+
+/* The outcome of diceroll_roll, owned by the caller until it's passed to
+ * diceroll_free_result. */
+typedef struct DicerollResult {
+    char *expr;
+    int64_t total;
+    int64_t modifier;
+    DicerollDie *dice;
+    size_t dice_len;
+} DicerollResult;
+
+/* Parses a dice expression, returning NULL if it's invalid. If error isn't
+ * NULL, it's set to the reason the expression is invalid, to free with
+ * diceroll_free_string, or to NULL if it's valid. */
+DiceExpr *diceroll_parse(const char *expr, char **error);
+
+/* Rolls a parsed expression once, ignoring any repeat count and taking any
+ * variables as zero. Returns NULL if expr is NULL, or if any of its
+ * variables can't be zero, such as a divisor. */
+DicerollResult *diceroll_roll(const DiceExpr *expr);
+
+/* Frees a result returned by diceroll_roll. Does nothing if it's NULL. */
+void diceroll_free_result(DicerollResult *result);
+
+/* Frees an expression returned by diceroll_parse. Does nothing if it's
+ * NULL. */
+void diceroll_free_expr(DiceExpr *expr);
+
+/* Frees an error message set by diceroll_parse. Does nothing if it's
+ * NULL. */
+void diceroll_free_string(char *s);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
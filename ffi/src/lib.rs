@@ -0,0 +1,206 @@
+use diceroll::DiceExpr;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+/// A die rolled, and whether it counts towards the total.
+#[repr(C)]
+pub struct DicerollDie {
+    pub value: i64,
+    pub kept: bool,
+}
+
+/// The outcome of `diceroll_roll`, owned by the caller until it's passed to
+/// `diceroll_free_result`.
+#[repr(C)]
+pub struct DicerollResult {
+    pub expr: *mut c_char,
+    pub total: i64,
+    pub modifier: i64,
+    pub dice: *mut DicerollDie,
+    pub dice_len: usize,
+}
+
+/// Parses a dice expression, returning null if it's invalid. If `error`
+/// isn't null, it's set to the reason the expression is invalid, to free
+/// with `diceroll_free_string`, or to null if it's valid.
+///
+/// # Safety
+///
+/// `expr` must be a valid, nul-terminated string, and `error` must be null
+/// or valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn diceroll_parse(
+    expr: *const c_char,
+    error: *mut *mut c_char,
+) -> *mut DiceExpr {
+    let parsed = match expr.is_null() {
+        true => Err(String::from("expression is null")),
+        false => CStr::from_ptr(expr)
+            .to_str()
+            .map_err(|e| e.to_string())
+            .and_then(|s| DiceExpr::try_from(s).map_err(|e| e.to_string())),
+    };
+
+    let (parsed, message) = match parsed {
+        Ok(parsed) => (Box::into_raw(Box::new(parsed)), ptr::null_mut()),
+        Err(e) => (ptr::null_mut(), into_c_string(e)),
+    };
+    match error.is_null() {
+        true => diceroll_free_string(message),
+        false => *error = message,
+    }
+
+    parsed
+}
+
+/// Rolls a parsed expression once, ignoring any repeat count and taking
+/// any variables as zero. Returns null if `expr` is null, or if any of its
+/// variables can't be zero, such as a divisor.
+///
+/// # Safety
+///
+/// `expr` must be null or returned by `diceroll_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn diceroll_roll(expr: *const DiceExpr) -> *mut DicerollResult {
+    let Some(expr) = expr.as_ref() else {
+        return ptr::null_mut();
+    };
+    let vars = expr
+        .variables()
+        .into_iter()
+        .map(|name| (name.to_string(), 0))
+        .collect();
+    let Ok(result) = expr.roll_with_vars(&vars) else {
+        return ptr::null_mut();
+    };
+
+    let dice: Box<[DicerollDie]> = result
+        .dice
+        .iter()
+        .flat_map(|d| &d.rolls)
+        .map(|r| DicerollDie {
+            value: r.value,
+            kept: r.kept,
+        })
+        .collect();
+    let dice_len = dice.len();
+
+    Box::into_raw(Box::new(DicerollResult {
+        expr: into_c_string(result.expr),
+        total: result.total,
+        modifier: result.modifier,
+        dice: Box::into_raw(dice) as *mut DicerollDie,
+        dice_len,
+    }))
+}
+
+/// Frees a result returned by `diceroll_roll`. Does nothing if `result` is
+/// null.
+///
+/// # Safety
+///
+/// `result` must be null or returned by `diceroll_roll` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn diceroll_free_result(result: *mut DicerollResult) {
+    if result.is_null() {
+        return;
+    }
+
+    let result = Box::from_raw(result);
+    diceroll_free_string(result.expr);
+    drop(Box::from_raw(ptr::slice_from_raw_parts_mut(
+        result.dice,
+        result.dice_len,
+    )));
+}
+
+/// Frees an expression returned by `diceroll_parse`. Does nothing if
+/// `expr` is null.
+///
+/// # Safety
+///
+/// `expr` must be null or returned by `diceroll_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn diceroll_free_expr(expr: *mut DiceExpr) {
+    if !expr.is_null() {
+        drop(Box::from_raw(expr));
+    }
+}
+
+/// Frees an error message set by `diceroll_parse`. Does nothing if `s` is
+/// null.
+///
+/// # Safety
+///
+/// `s` must be null or set by `diceroll_parse` and not yet freed.
+#[no_mangle]
+pub unsafe extern "C" fn diceroll_free_string(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+/// Hands a string to C, dropping anything after an interior nul.
+fn into_c_string(s: String) -> *mut c_char {
+    let mut bytes = s.into_bytes();
+    if let Some(nul) = bytes.iter().position(|&b| b == 0) {
+        bytes.truncate(nul);
+    }
+    CString::new(bytes).unwrap().into_raw()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_roll_free() {
+        unsafe {
+            let mut error = ptr::null_mut();
+            let expr = diceroll_parse(c"4d6kh3+2".as_ptr(), &mut error);
+            assert!(!expr.is_null());
+            assert!(error.is_null());
+
+            let result = diceroll_roll(expr);
+            let dice = std::slice::from_raw_parts((*result).dice, (*result).dice_len);
+            assert_eq!(4, dice.len());
+            assert_eq!(3, dice.iter().filter(|d| d.kept).count());
+            assert_eq!(
+                (*result).total,
+                dice.iter().filter(|d| d.kept).map(|d| d.value).sum::<i64>() + 2
+            );
+            assert_eq!(2, (*result).modifier);
+
+            diceroll_free_result(result);
+            diceroll_free_expr(expr);
+        }
+    }
+
+    #[test]
+    fn parse_error() {
+        unsafe {
+            let mut error = ptr::null_mut();
+            assert!(diceroll_parse(c"2d6+".as_ptr(), &mut error).is_null());
+            assert!(!CStr::from_ptr(error).to_bytes().is_empty());
+            diceroll_free_string(error);
+
+            assert!(diceroll_parse(ptr::null(), ptr::null_mut()).is_null());
+            assert!(diceroll_roll(ptr::null()).is_null())
+        }
+    }
+
+    #[test]
+    fn roll_variables() {
+        unsafe {
+            let expr = diceroll_parse(c"1d6+$x".as_ptr(), ptr::null_mut());
+            let result = diceroll_roll(expr);
+            assert!((1..=6).contains(&(*result).total));
+            diceroll_free_result(result);
+            diceroll_free_expr(expr);
+
+            let expr = diceroll_parse(c"1d6/$x".as_ptr(), ptr::null_mut());
+            assert!(diceroll_roll(expr).is_null());
+            diceroll_free_expr(expr)
+        }
+    }
+}
@@ -0,0 +1,267 @@
+//! The `dice!` macro, re-exported by diceroll with its `macros` feature.
+//! Since diceroll depends on this crate to re-export it, this crate can't
+//! depend on diceroll to parse with, and builds diceroll's parser into
+//! itself instead.
+
+extern crate alloc;
+
+#[allow(dead_code)]
+#[path = "../../src/ast.rs"]
+mod ast;
+#[allow(dead_code)]
+#[path = "../../src/compare.rs"]
+mod compare;
+#[allow(dead_code)]
+#[path = "../../src/dialect.rs"]
+mod dialect;
+#[allow(dead_code)]
+#[path = "../../src/distribution.rs"]
+mod distribution;
+#[allow(dead_code, unused_imports)]
+#[path = "../../src/expr.rs"]
+mod expr;
+#[allow(dead_code)]
+#[path = "../../src/parser.rs"]
+mod parser;
+#[allow(dead_code)]
+#[path = "../../src/ratio.rs"]
+mod ratio;
+#[allow(dead_code)]
+#[path = "../../src/roller.rs"]
+mod roller;
+#[allow(dead_code)]
+#[path = "../../src/token.rs"]
+mod token;
+
+use expr::{Ast, Comparison, Dice, DiceExpr, Drop, Function, Keep, Reroll, Sides, Sort};
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, LitStr};
+
+/// Parses a dice expression when it's compiled, so that an invalid one is a
+/// build error rather than a runtime one, and expands to the `DiceExpr`
+/// built from its parsed parts, without parsing it again when it runs.
+///
+/// ```
+/// use diceroll_macros::dice;
+/// use diceroll::DiceExpr;
+///
+/// let stats = dice!("4d6-L");
+/// assert_eq!(DiceExpr::try_from("4d6-L").unwrap(), stats);
+/// assert!((3..=18).contains(&stats.roll().unwrap().total));
+/// ```
+///
+/// ```compile_fail
+/// use diceroll_macros::dice;
+///
+/// let typo = dice!("4d6-");
+/// ```
+///
+/// ```compile_fail
+/// use diceroll_macros::dice;
+///
+/// let never_positive = dice!("d20-100");
+/// ```
+#[proc_macro]
+pub fn dice(input: TokenStream) -> TokenStream {
+    let expr = parse_macro_input!(input as LitStr);
+
+    match DiceExpr::try_from(expr.value().as_str()) {
+        Ok(parsed) => expr_tokens(&parsed).into(),
+        Err(e) => syn::Error::new(expr.span(), e).to_compile_error().into(),
+    }
+}
+
+fn expr_tokens(expr: &DiceExpr) -> TokenStream2 {
+    let repeat = expr.repeat();
+    let signed = expr.is_signed();
+    let ast = ast_tokens(expr.ast());
+    let dc = option_tokens(expr.dc(), |dc| quote!(#dc));
+
+    quote! {
+        ::diceroll::__private::unchecked_expr(::diceroll::__private::ExprParts {
+            repeat: #repeat,
+            signed: #signed,
+            ast: #ast,
+            dc: #dc,
+        })
+    }
+}
+
+fn ast_tokens(ast: &Ast) -> TokenStream2 {
+    let pair = |lhs: &Ast, rhs: &Ast| {
+        let lhs = ast_tokens(lhs);
+        let rhs = ast_tokens(rhs);
+        quote!(::core::convert::From::from(#lhs), ::core::convert::From::from(#rhs))
+    };
+
+    match ast {
+        Ast::Dice(dice) => {
+            let dice = dice_tokens(dice);
+            quote!(::diceroll::expr::Ast::Dice(::core::convert::From::from(#dice)))
+        }
+        Ast::Constant(n) => quote!(::diceroll::expr::Ast::Constant(#n)),
+        Ast::Var(name) => quote!(::diceroll::expr::Ast::Var(::core::convert::From::from(#name))),
+        Ast::Add(lhs, rhs) => {
+            let operands = pair(lhs, rhs);
+            quote!(::diceroll::expr::Ast::Add(#operands))
+        }
+        Ast::Sub(lhs, rhs) => {
+            let operands = pair(lhs, rhs);
+            quote!(::diceroll::expr::Ast::Sub(#operands))
+        }
+        Ast::Mul(lhs, rhs) => {
+            let operands = pair(lhs, rhs);
+            quote!(::diceroll::expr::Ast::Mul(#operands))
+        }
+        Ast::Div(lhs, rhs) => {
+            let operands = pair(lhs, rhs);
+            quote!(::diceroll::expr::Ast::Div(#operands))
+        }
+        Ast::Call(function, arg) => {
+            let function = function_tokens(function);
+            let arg = ast_tokens(arg);
+            quote!(::diceroll::expr::Ast::Call(#function, ::core::convert::From::from(#arg)))
+        }
+        Ast::Roll(count, sides) => {
+            let operands = pair(count, sides);
+            quote!(::diceroll::expr::Ast::Roll(#operands))
+        }
+        Ast::Group(exprs, keep, drop) => {
+            let exprs = exprs.iter().map(ast_tokens);
+            let keep = keep_tokens(keep);
+            let drop = drop_tokens(drop);
+            quote! {
+                ::diceroll::expr::Ast::Group(
+                    ::core::convert::From::from([#(#exprs),*]),
+                    #keep,
+                    #drop,
+                )
+            }
+        }
+    }
+}
+
+fn dice_tokens(dice: &Dice) -> TokenStream2 {
+    let count = dice.count();
+    let sides = sides_tokens(dice.sides());
+    let explode = option_tokens(dice.explode(), comparison_tokens);
+    let open_ended = dice.open_ended();
+    let reroll = option_tokens(dice.reroll(), reroll_tokens);
+    let min = option_tokens(dice.min(), |n| quote!(#n));
+    let max = option_tokens(dice.max(), |n| quote!(#n));
+    let keep = keep_tokens(dice.keep());
+    let drop = drop_tokens(dice.drop());
+    let success = option_tokens(dice.success(), comparison_tokens);
+    let double = option_tokens(dice.double(), comparison_tokens);
+    let failure = option_tokens(dice.failure(), comparison_tokens);
+    let critical_success = option_tokens(dice.critical_success(), comparison_tokens);
+    let critical_failure = option_tokens(dice.critical_failure(), comparison_tokens);
+    let sets = dice.sets();
+    let sort = option_tokens(dice.sort(), sort_tokens);
+    let each = option_tokens(dice.each(), |n| quote!(#n));
+    let label = option_tokens(
+        dice.label(),
+        |label| quote!(::core::convert::From::from(#label)),
+    );
+
+    quote! {
+        ::diceroll::__private::unchecked_dice(::diceroll::__private::DiceParts {
+            count: #count,
+            sides: #sides,
+            explode: #explode,
+            open_ended: #open_ended,
+            reroll: #reroll,
+            min: #min,
+            max: #max,
+            keep: #keep,
+            drop: #drop,
+            success: #success,
+            double: #double,
+            failure: #failure,
+            critical_success: #critical_success,
+            critical_failure: #critical_failure,
+            sets: #sets,
+            sort: #sort,
+            each: #each,
+            label: #label,
+        })
+    }
+}
+
+fn sides_tokens(sides: &Sides) -> TokenStream2 {
+    match sides {
+        Sides::Number(n) => quote!(::diceroll::expr::Sides::Number(#n)),
+        Sides::Fate => quote!(::diceroll::expr::Sides::Fate),
+        Sides::Coin => quote!(::diceroll::expr::Sides::Coin),
+        Sides::Percentile => quote!(::diceroll::expr::Sides::Percentile),
+        Sides::Positional { faces, digits } => quote! {
+            ::diceroll::expr::Sides::Positional { faces: #faces, digits: #digits }
+        },
+        Sides::Faces(faces) => quote! {
+            ::diceroll::expr::Sides::Faces(::core::convert::From::from([#(#faces),*]))
+        },
+    }
+}
+
+fn comparison_tokens(comparison: &Comparison) -> TokenStream2 {
+    match comparison {
+        Comparison::Eq(values) => quote! {
+            ::diceroll::expr::Comparison::Eq(::core::convert::From::from([#(#values),*]))
+        },
+        Comparison::Lt(n) => quote!(::diceroll::expr::Comparison::Lt(#n)),
+        Comparison::Le(n) => quote!(::diceroll::expr::Comparison::Le(#n)),
+        Comparison::Gt(n) => quote!(::diceroll::expr::Comparison::Gt(#n)),
+        Comparison::Ge(n) => quote!(::diceroll::expr::Comparison::Ge(#n)),
+    }
+}
+
+fn reroll_tokens(reroll: &Reroll) -> TokenStream2 {
+    let condition = comparison_tokens(reroll.condition());
+    let once = reroll.once();
+    quote!(::diceroll::__private::unchecked_reroll(#condition, #once))
+}
+
+fn keep_tokens(keep: &Keep) -> TokenStream2 {
+    match keep {
+        Keep::High(n) => quote!(::diceroll::expr::Keep::High(#n)),
+        Keep::Low(n) => quote!(::diceroll::expr::Keep::Low(#n)),
+        Keep::Middle(n) => quote!(::diceroll::expr::Keep::Middle(#n)),
+        Keep::None => quote!(::diceroll::expr::Keep::None),
+    }
+}
+
+fn drop_tokens(drop: &Drop) -> TokenStream2 {
+    match drop {
+        Drop::High(n) => quote!(::diceroll::expr::Drop::High(#n)),
+        Drop::Low(n) => quote!(::diceroll::expr::Drop::Low(#n)),
+        Drop::None => quote!(::diceroll::expr::Drop::None),
+    }
+}
+
+fn sort_tokens(sort: &Sort) -> TokenStream2 {
+    match sort {
+        Sort::Ascending => quote!(::diceroll::expr::Sort::Ascending),
+        Sort::Descending => quote!(::diceroll::expr::Sort::Descending),
+    }
+}
+
+fn function_tokens(function: &Function) -> TokenStream2 {
+    match function {
+        Function::Floor => quote!(::diceroll::expr::Function::Floor),
+        Function::Ceil => quote!(::diceroll::expr::Function::Ceil),
+        Function::Round => quote!(::diceroll::expr::Function::Round),
+        Function::Abs => quote!(::diceroll::expr::Function::Abs),
+    }
+}
+
+fn option_tokens<T, F: Fn(T) -> TokenStream2>(value: Option<T>, tokens: F) -> TokenStream2 {
+    match value {
+        Some(value) => {
+            let value = tokens(value);
+            quote!(::core::option::Option::Some(#value))
+        }
+        None => quote!(::core::option::Option::None),
+    }
+}
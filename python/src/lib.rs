@@ -0,0 +1,187 @@
+use ::diceroll::{DiceExpr, RollResult};
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use std::collections::{BTreeMap, HashMap};
+
+/// A dice expression, parsed from its text as in `DiceExpr("4d6kh3")`.
+#[pyclass(name = "DiceExpr", frozen, eq, hash)]
+#[derive(PartialEq, Eq, Hash)]
+pub struct PyDiceExpr(DiceExpr);
+
+#[pymethods]
+impl PyDiceExpr {
+    #[new]
+    fn new(expr: &str) -> PyResult<Self> {
+        DiceExpr::try_from(expr)
+            .map(PyDiceExpr)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Rolls the expression once, ignoring any repeat count, with its
+    /// variables taken from `vars`, or zero if they're missing. Raises
+    /// `ValueError` if their values can't be rolled, such as a divisor of
+    /// zero.
+    #[pyo3(signature = (vars = None))]
+    fn roll(&self, vars: Option<HashMap<String, i64>>) -> PyResult<PyRollResult> {
+        let mut vars = vars.unwrap_or_default();
+        for name in self.0.variables() {
+            vars.entry(name.to_string()).or_insert(0);
+        }
+
+        self.0
+            .roll_with_vars(&vars)
+            .map(PyRollResult)
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    /// Rolls the expression `n` times, with its variables taken as zero.
+    /// Raises `ValueError` if they can't be, such as a divisor.
+    fn roll_n(&self, n: usize) -> PyResult<Vec<PyRollResult>> {
+        self.0
+            .roll_n(n)
+            .map(|results| results.into_iter().map(PyRollResult).collect())
+            .map_err(|e| PyValueError::new_err(e.to_string()))
+    }
+
+    #[getter]
+    fn min_value(&self) -> i64 {
+        self.0.min_value()
+    }
+
+    #[getter]
+    fn max_value(&self) -> i64 {
+        self.0.max_value()
+    }
+
+    fn mean(&self) -> Option<f64> {
+        self.0.mean()
+    }
+
+    fn variance(&self) -> Option<f64> {
+        self.0.variance()
+    }
+
+    /// The exact chance of rolling each total, as a dict from totals to
+    /// chances, or `None` if it's unknown.
+    fn distribution(&self) -> Option<BTreeMap<i64, f64>> {
+        self.0.distribution().map(|d| d.iter().collect())
+    }
+
+    fn prob_at_least(&self, n: i64) -> Option<f64> {
+        self.0.prob_at_least(n)
+    }
+
+    fn prob_at_most(&self, n: i64) -> Option<f64> {
+        self.0.prob_at_most(n)
+    }
+
+    fn __str__(&self) -> String {
+        self.0.to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("DiceExpr({:?})", self.0.to_string())
+    }
+}
+
+/// The outcome of rolling a `DiceExpr`.
+#[pyclass(name = "RollResult", frozen, eq)]
+#[derive(PartialEq, Eq)]
+pub struct PyRollResult(RollResult);
+
+#[pymethods]
+impl PyRollResult {
+    #[getter]
+    fn expr(&self) -> &str {
+        &self.0.expr
+    }
+
+    #[getter]
+    fn total(&self) -> i64 {
+        self.0.total
+    }
+
+    #[getter]
+    fn modifier(&self) -> i64 {
+        self.0.modifier
+    }
+
+    /// The value of every die rolled, including any that don't count
+    /// towards the total.
+    #[getter]
+    fn rolls(&self) -> Vec<i64> {
+        self.0
+            .dice
+            .iter()
+            .flat_map(|d| d.rolls.iter().map(|r| r.value))
+            .collect()
+    }
+
+    /// The value of each die counted towards the total.
+    #[getter]
+    fn kept(&self) -> Vec<i64> {
+        self.0
+            .dice
+            .iter()
+            .flat_map(|d| d.rolls.iter().filter(|r| r.kept).map(|r| r.value))
+            .collect()
+    }
+
+    fn __int__(&self) -> i64 {
+        self.0.total
+    }
+
+    fn __repr__(&self) -> String {
+        format!("RollResult({:?}, total={})", self.0.expr, self.0.total)
+    }
+}
+
+/// The `diceroll` Python module, built as an extension with `maturin build`
+/// from this crate's directory.
+#[pymodule]
+fn diceroll(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyDiceExpr>()?;
+    m.add_class::<PyRollResult>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new() {
+        assert!(PyDiceExpr::new("2d6+").is_err());
+        assert_eq!(
+            "DiceExpr(\"4d6kh3\")",
+            PyDiceExpr::new("4d6kh3").unwrap().__repr__()
+        )
+    }
+
+    #[test]
+    fn distribution() {
+        let expr = PyDiceExpr::new("2d6").unwrap();
+        let distribution = expr.distribution().unwrap();
+
+        assert_eq!(11, distribution.len());
+        assert!((distribution[&7] - 6.0 / 36.0).abs() < 1e-12);
+        assert_eq!((2, 12), (expr.min_value(), expr.max_value()))
+    }
+
+    #[test]
+    fn roll() {
+        let result = PyDiceExpr::new("4d6kh3+1").unwrap().roll(None).unwrap();
+
+        assert_eq!(4, result.rolls().len());
+        assert_eq!(result.total(), result.kept().iter().sum::<i64>() + 1)
+    }
+
+    #[test]
+    fn roll_variables() {
+        let expr = PyDiceExpr::new("1d6/$x").unwrap();
+        let vars = HashMap::from([(String::from("x"), 1)]);
+
+        assert!((1..=6).contains(&expr.roll(Some(vars)).unwrap().total()));
+        assert!(expr.roll(None).is_err())
+    }
+}
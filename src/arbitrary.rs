@@ -0,0 +1,98 @@
+use crate::expr::DiceExpr;
+use proptest::prelude::*;
+
+/// Dice terms such as `4d6kh3` or `2dF`, of up to 20 dice of the common
+/// sizes, Fate dice, or percentile dice, keeping or dropping some of them or
+/// not.
+pub fn dice_term() -> impl Strategy<Value = String> {
+    let sides = prop_oneof![
+        prop::sample::select(vec![2u32, 4, 6, 8, 10, 12, 20, 100]).prop_map(|s| s.to_string()),
+        Just(String::from("F")),
+        Just(String::from("%")),
+    ];
+
+    (1u32..=20, sides)
+        .prop_flat_map(|(count, sides)| (Just(count), Just(sides), 0..4, 1..=count))
+        .prop_map(|(count, sides, selection, n)| match selection {
+            1 => format!("{}d{}kh{}", count, sides, n),
+            2 => format!("{}d{}kl{}", count, sides, n),
+            3 if n < count => format!("{}d{}dl{}", count, sides, n),
+            _ => format!("{}d{}", count, sides),
+        })
+}
+
+/// Expressions adding and subtracting one to four dice terms and a whole
+/// constant, such as `4d6kh3+1d4-2`.
+pub fn dice_expr_string() -> impl Strategy<Value = String> {
+    let term = (any::<bool>(), dice_term());
+    (
+        dice_term(),
+        prop::collection::vec(term, 0..4),
+        -100i64..=100,
+    )
+        .prop_map(|(first, rest, constant)| {
+            let mut expr = first;
+            for (add, term) in rest {
+                expr.push(if add { '+' } else { '-' });
+                expr.push_str(&term);
+            }
+
+            match constant {
+                0 => expr,
+                n => format!("{}{:+}", expr, n),
+            }
+        })
+}
+
+/// Expressions parsed from `dice_expr_string`, skipping any that aren't
+/// valid, such as those whose total can never be above zero.
+pub fn dice_expr() -> impl Strategy<Value = DiceExpr> {
+    dice_expr_string().prop_filter_map("invalid expression", |s| {
+        DiceExpr::try_from(s.as_str()).ok()
+    })
+}
+
+impl Arbitrary for DiceExpr {
+    type Parameters = ();
+    type Strategy = BoxedStrategy<DiceExpr>;
+
+    fn arbitrary_with(_: Self::Parameters) -> Self::Strategy {
+        dice_expr().boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    proptest! {
+        #[test]
+        fn display_round_trip(expr: DiceExpr) {
+            let displayed = expr.to_string();
+            prop_assert_eq!(Ok(expr), DiceExpr::try_from(displayed.as_str()))
+        }
+
+        #[test]
+        fn roll_within_bounds(expr: DiceExpr) {
+            let total = expr.roll().unwrap().total;
+            prop_assert!((expr.min_value()..=expr.max_value()).contains(&total))
+        }
+
+        #[test]
+        fn simplify_keeps_bounds(expr: DiceExpr) {
+            let simplified = expr.clone().simplify();
+            prop_assert_eq!(
+                (expr.min_value(), expr.max_value()),
+                (simplified.min_value(), simplified.max_value())
+            )
+        }
+
+        #[test]
+        fn mean_within_bounds(expr: DiceExpr) {
+            if let Some(mean) = expr.mean() {
+                prop_assert!(expr.min_value() as f64 <= mean + 1e-9);
+                prop_assert!(mean <= expr.max_value() as f64 + 1e-9)
+            }
+        }
+    }
+}
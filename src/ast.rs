@@ -0,0 +1,724 @@
+use crate::distribution::DiceDistribution;
+use crate::expr::{kept_ranks, Dice, DiceExprError, DiceRoll, Drop, Keep, RollBuffer, Sides, Vars};
+use crate::ratio::Ratio;
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::Reverse;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+/// A function applied to the value of a sub-expression after it's rolled,
+/// such as the `ceil` in `ceil(1d100/10)` or the `abs` in `abs(1d6-1d6)`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Function {
+    Floor,
+    Ceil,
+    Round,
+    Abs,
+}
+
+impl Function {
+    fn apply(&self, value: Ratio) -> Ratio {
+        match self {
+            Function::Floor => Ratio::from(value.floor()),
+            Function::Ceil => Ratio::from(value.ceil()),
+            Function::Round => Ratio::from(value.round()),
+            Function::Abs => value.abs(),
+        }
+    }
+
+    /// The lowest and highest values the function can return for values
+    /// in `range`.
+    fn range(&self, range: RangeInclusive<i64>) -> RangeInclusive<i64> {
+        match self {
+            Function::Abs if range.contains(&0) => {
+                0..=range
+                    .start()
+                    .saturating_abs()
+                    .max(range.end().saturating_abs())
+            }
+            Function::Abs if *range.end() < 0 => {
+                range.end().saturating_abs()..=range.start().saturating_abs()
+            }
+            _ => range,
+        }
+    }
+}
+
+impl Display for Function {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Function::Floor => write!(f, "floor"),
+            Function::Ceil => write!(f, "ceil"),
+            Function::Round => write!(f, "round"),
+            Function::Abs => write!(f, "abs"),
+        }
+    }
+}
+
+/// A node of a parsed `DiceExpr`, as returned by `DiceExpr::ast` for
+/// inspecting an expression without parsing its string form.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Ast {
+    Dice(Box<Dice>),
+    Constant(i64),
+    /// A named value supplied when rolling, such as `$str`.
+    Var(String),
+    Add(Box<Ast>, Box<Ast>),
+    Sub(Box<Ast>, Box<Ast>),
+    Mul(Box<Ast>, Box<Ast>),
+    Div(Box<Ast>, Box<Ast>),
+    Call(Function, Box<Ast>),
+    /// A dice term whose count or number of sides is rolled first, such as
+    /// `(1d4)d6` or `1d(1d20)`.
+    Roll(Box<Ast>, Box<Ast>),
+    /// Several sub-expressions rolled together, with any keep or drop
+    /// modifier applied to their totals, such as `{2d6,1d8,1d10}kh1`.
+    Group(Vec<Ast>, Keep, Drop),
+}
+
+impl Ast {
+    /// The lowest and highest totals the expression can roll.
+    pub(crate) fn range(&self) -> RangeInclusive<i64> {
+        let bounds = |a: &Ast, b: &Ast| {
+            let (a, b) = (a.range(), b.range());
+            [
+                (*a.start(), *b.start()),
+                (*a.start(), *b.end()),
+                (*a.end(), *b.start()),
+                (*a.end(), *b.end()),
+            ]
+        };
+
+        match self {
+            Ast::Dice(d) => d.range(),
+            Ast::Constant(n) => *n..=*n,
+            Ast::Var(_) => i64::MIN..=i64::MAX,
+            Ast::Add(a, b) => {
+                let (a, b) = (a.range(), b.range());
+                a.start().saturating_add(*b.start())..=a.end().saturating_add(*b.end())
+            }
+            Ast::Sub(a, b) => {
+                let (a, b) = (a.range(), b.range());
+                a.start().saturating_sub(*b.end())..=a.end().saturating_sub(*b.start())
+            }
+            Ast::Mul(a, b) => {
+                let products = bounds(a, b).map(|(a, b)| a.saturating_mul(b));
+                *products.iter().min().unwrap()..=*products.iter().max().unwrap()
+            }
+            Ast::Div(a, b) => {
+                let quotients = bounds(a, b).map(|(a, b)| Ratio::new(a, b));
+                let floor = quotients.iter().flatten().map(|q| q.floor()).min();
+                let ceil = quotients.iter().flatten().map(|q| q.ceil()).max();
+
+                match (floor, ceil) {
+                    (Some(floor), Some(ceil)) => floor..=ceil,
+                    _ => i64::MIN..=i64::MAX,
+                }
+            }
+            Ast::Call(f, a) => f.range(a.range()),
+            Ast::Roll(count, sides) => {
+                let (count, sides) = (count.range(), sides.range());
+                *count.start()..=count.end().saturating_mul(*sides.end())
+            }
+            Ast::Group(terms, keep, drop) => {
+                let mut starts: Vec<i64> = terms.iter().map(|t| *t.range().start()).collect();
+                let mut ends: Vec<i64> = terms.iter().map(|t| *t.range().end()).collect();
+                starts.sort();
+                ends.sort();
+
+                // Whichever terms are kept, their total can't fall outside
+                // the sum of the same number of the lowest or highest bounds.
+                let kept = kept_ranks(keep, drop, terms.len()).len();
+                let sum =
+                    |bounds: &[i64]| bounds.iter().fold(0, |sum: i64, b| sum.saturating_add(*b));
+
+                sum(&starts[..kept])..=sum(&ends[ends.len() - kept..])
+            }
+        }
+    }
+
+    /// The lowest and highest values the expression can come to with its
+    /// variables taken from `vars`, or zero if they're missing, or `None` if
+    /// any part of it can come to a value too large to be held in a total.
+    /// Dice are taken as not exploding, like `Dice::checked_range`.
+    pub(crate) fn checked_range(&self, vars: &Vars) -> Option<RangeInclusive<i64>> {
+        let ranges = |a: &Ast, b: &Ast| Some((a.checked_range(vars)?, b.checked_range(vars)?));
+        let bounds = |a: &RangeInclusive<i64>, b: &RangeInclusive<i64>| {
+            [
+                (*a.start(), *b.start()),
+                (*a.start(), *b.end()),
+                (*a.end(), *b.start()),
+                (*a.end(), *b.end()),
+            ]
+        };
+
+        match self {
+            Ast::Dice(d) => d.checked_range(),
+            Ast::Constant(n) => Some(*n..=*n),
+            Ast::Var(name) => {
+                let n = vars.get(name).copied().unwrap_or(0);
+                Some(n..=n)
+            }
+            Ast::Add(a, b) => {
+                let (a, b) = ranges(a, b)?;
+                Some(a.start().checked_add(*b.start())?..=a.end().checked_add(*b.end())?)
+            }
+            Ast::Sub(a, b) => {
+                let (a, b) = ranges(a, b)?;
+                Some(a.start().checked_sub(*b.end())?..=a.end().checked_sub(*b.start())?)
+            }
+            Ast::Mul(a, b) => {
+                let (a, b) = ranges(a, b)?;
+                let products = bounds(&a, &b)
+                    .iter()
+                    .map(|(a, b)| a.checked_mul(*b))
+                    .collect::<Option<Vec<_>>>()?;
+                Some(*products.iter().min()?..=*products.iter().max()?)
+            }
+            // The divisor's range excludes zero, so no quotient is further
+            // from zero than the dividend.
+            Ast::Div(a, b) => {
+                let (a, _) = ranges(a, b)?;
+                let furthest = a.start().checked_abs()?.max(a.end().checked_abs()?);
+                Some(-furthest..=furthest)
+            }
+            Ast::Call(f, a) => Some(f.range(a.checked_range(vars)?)),
+            Ast::Roll(count, sides) => {
+                let (count, sides) = ranges(count, sides)?;
+                Some(*count.start()..=count.end().checked_mul(*sides.end())?)
+            }
+            Ast::Group(terms, keep, drop) => {
+                let ranges = terms
+                    .iter()
+                    .map(|t| t.checked_range(vars))
+                    .collect::<Option<Vec<_>>>()?;
+                let mut starts: Vec<i64> = ranges.iter().map(|r| *r.start()).collect();
+                let mut ends: Vec<i64> = ranges.iter().map(|r| *r.end()).collect();
+                starts.sort();
+                ends.sort();
+
+                let kept = kept_ranks(keep, drop, terms.len()).len();
+                let sum =
+                    |bounds: &[i64]| bounds.iter().try_fold(0, |sum: i64, b| sum.checked_add(*b));
+
+                Some(sum(&starts[..kept])?..=sum(&ends[ends.len() - kept..])?)
+            }
+        }
+    }
+
+    /// Whether the expression can be rolled with its variables taken from
+    /// `vars`, or zero if they're missing: that no divisor can be zero, and
+    /// that no dice whose count or sides are rolled can have too few or too
+    /// many. Parsing checks these for the parts without variables.
+    pub(crate) fn rollable_with(&self, vars: &Vars) -> bool {
+        match self {
+            Ast::Dice(_) | Ast::Constant(_) | Ast::Var(_) => true,
+            Ast::Add(a, b) | Ast::Sub(a, b) | Ast::Mul(a, b) => {
+                a.rollable_with(vars) && b.rollable_with(vars)
+            }
+            Ast::Div(a, b) => {
+                a.rollable_with(vars)
+                    && b.rollable_with(vars)
+                    && b.checked_range(vars).is_some_and(|b| !b.contains(&0))
+            }
+            Ast::Call(_, a) => a.rollable_with(vars),
+            Ast::Roll(count, sides) => {
+                count.rollable_with(vars)
+                    && sides.rollable_with(vars)
+                    && match (count.checked_range(vars), sides.checked_range(vars)) {
+                        (Some(count), Some(sides)) => rollable(&count, &sides),
+                        _ => false,
+                    }
+            }
+            Ast::Group(terms, ..) => terms.iter().all(|t| t.rollable_with(vars)),
+        }
+    }
+
+    /// Whether the expression's total is meant to be able to go below zero,
+    /// as it can when it subtracts dice rather than just a constant.
+    pub(crate) fn signed(&self) -> bool {
+        match self {
+            Ast::Dice(d) => d.signed(),
+            Ast::Constant(_) | Ast::Var(_) | Ast::Roll(..) => false,
+            Ast::Add(a, b) | Ast::Mul(a, b) | Ast::Div(a, b) => a.signed() || b.signed(),
+            Ast::Sub(a, b) => a.signed() || b.signed() || !b.is_constant(),
+            Ast::Call(Function::Abs, _) => false,
+            Ast::Call(_, a) => a.signed(),
+            Ast::Group(terms, ..) => terms.iter().any(|t| t.signed()),
+        }
+    }
+
+    /// Checks what parsing does of a whole expression once it's read: that
+    /// it can total above zero, unless it's `signed` or meant to go below
+    /// zero, and that its total can't overflow. Errors name it as `expr`.
+    pub(crate) fn validate(&self, expr: &dyn Display, signed: bool) -> Result<(), DiceExprError> {
+        if !(signed || self.signed() || *self.range().end() > 0) {
+            Err(DiceExprError::from(expr.to_string()))
+        } else if self.checked_range(&Vars::new()).is_none() {
+            Err(DiceExprError::Overflow(expr.to_string()))
+        } else {
+            Ok(())
+        }
+    }
+
+    fn is_constant(&self) -> bool {
+        match self {
+            Ast::Dice(_) | Ast::Var(_) | Ast::Roll(..) => false,
+            Ast::Constant(_) => true,
+            Ast::Add(a, b) | Ast::Sub(a, b) | Ast::Mul(a, b) | Ast::Div(a, b) => {
+                a.is_constant() && b.is_constant()
+            }
+            Ast::Call(_, a) => a.is_constant(),
+            Ast::Group(terms, ..) => terms.iter().all(|t| t.is_constant()),
+        }
+    }
+
+    /// The names of the variables used in the expression.
+    pub(crate) fn variables(&self) -> Vec<&str> {
+        match self {
+            Ast::Dice(_) | Ast::Constant(_) => vec![],
+            Ast::Var(name) => vec![name],
+            Ast::Add(a, b) | Ast::Sub(a, b) | Ast::Mul(a, b) | Ast::Div(a, b) | Ast::Roll(a, b) => {
+                [a.variables(), b.variables()].concat()
+            }
+            Ast::Call(_, a) => a.variables(),
+            Ast::Group(terms, ..) => terms.iter().flat_map(|t| t.variables()).collect(),
+        }
+    }
+
+    /// Moves each dice term one step along the dice chain, to the next
+    /// larger die if `up` or the next smaller one otherwise.
+    pub(crate) fn step(&mut self, up: bool) {
+        match self {
+            Ast::Dice(d) => d.step(up),
+            Ast::Constant(_) | Ast::Var(_) => {}
+            Ast::Add(a, b) | Ast::Sub(a, b) | Ast::Mul(a, b) | Ast::Div(a, b) | Ast::Roll(a, b) => {
+                a.step(up);
+                b.step(up);
+            }
+            Ast::Call(_, a) => a.step(up),
+            Ast::Group(terms, ..) => terms.iter_mut().for_each(|t| t.step(up)),
+        }
+    }
+
+    /// The dice terms, constants, and variables making up the expression.
+    pub(crate) fn terms(&self) -> Vec<&Ast> {
+        match self {
+            Ast::Dice(_) | Ast::Constant(_) | Ast::Var(_) => vec![self],
+            Ast::Add(a, b) | Ast::Sub(a, b) | Ast::Mul(a, b) | Ast::Div(a, b) => {
+                [a.terms(), b.terms()].concat()
+            }
+            Ast::Roll(a, b) => [vec![self], a.terms(), b.terms()].concat(),
+            Ast::Call(_, a) => a.terms(),
+            Ast::Group(terms, ..) => terms.iter().flat_map(|t| t.terms()).collect(),
+        }
+    }
+
+    /// Rolls each dice term in order, recording their outcomes in `dice`,
+    /// and returns the expression's exact value, with any variable missing
+    /// from `vars` taken as zero.
+    pub(crate) fn eval<F: FnMut(u32) -> u32>(
+        &self,
+        roll_die: &mut F,
+        vars: &Vars,
+        dice: &mut Vec<DiceRoll>,
+    ) -> Ratio {
+        match self {
+            Ast::Dice(d) => {
+                let roll = d.roll_with(roll_die);
+                let total = roll.total;
+                dice.push(roll);
+                Ratio::from(total)
+            }
+            Ast::Constant(n) => Ratio::from(*n),
+            Ast::Var(name) => Ratio::from(vars.get(name).copied().unwrap_or(0)),
+            Ast::Add(a, b) => a.eval(roll_die, vars, dice) + b.eval(roll_die, vars, dice),
+            Ast::Sub(a, b) => a.eval(roll_die, vars, dice) - b.eval(roll_die, vars, dice),
+            Ast::Mul(a, b) => a.eval(roll_die, vars, dice) * b.eval(roll_die, vars, dice),
+            Ast::Div(a, b) => a
+                .eval(roll_die, vars, dice)
+                .checked_div(b.eval(roll_die, vars, dice))
+                .expect("divisor range excludes zero"),
+            Ast::Call(f, a) => f.apply(a.eval(roll_die, vars, dice)),
+            Ast::Roll(count, sides) => {
+                // Both ranges are checked to fit when parsing, or for those
+                // with variables, when rolling with them.
+                let count = count.eval(roll_die, vars, dice).floor() as u32;
+                let sides = sides.eval(roll_die, vars, dice).floor() as u32;
+
+                Ast::Dice(Box::new(Dice::new(count, Sides::Number(sides))))
+                    .eval(roll_die, vars, dice)
+            }
+            Ast::Group(terms, keep, drop) => {
+                let mut totals: Vec<Ratio> =
+                    terms.iter().map(|t| t.eval(roll_die, vars, dice)).collect();
+                totals.sort();
+
+                let kept = kept_ranks(keep, drop, totals.len());
+                totals[kept].iter().fold(Ratio::from(0), |sum, &t| sum + t)
+            }
+        }
+    }
+
+    /// Evaluates the expression like `eval`, with its variables taken as
+    /// zero, rolling its dice into `buffer` rather than recording each
+    /// term's outcome.
+    pub(crate) fn eval_into<F: FnMut(u32) -> u32>(
+        &self,
+        roll_die: &mut F,
+        buffer: &mut RollBuffer,
+    ) -> Ratio {
+        match self {
+            Ast::Dice(d) => {
+                Ratio::from(d.roll_into(roll_die, &mut buffer.rolls, &mut buffer.order))
+            }
+            Ast::Constant(n) => Ratio::from(*n),
+            Ast::Var(_) => Ratio::from(0),
+            Ast::Add(a, b) => a.eval_into(roll_die, buffer) + b.eval_into(roll_die, buffer),
+            Ast::Sub(a, b) => a.eval_into(roll_die, buffer) - b.eval_into(roll_die, buffer),
+            Ast::Mul(a, b) => a.eval_into(roll_die, buffer) * b.eval_into(roll_die, buffer),
+            Ast::Div(a, b) => a
+                .eval_into(roll_die, buffer)
+                .checked_div(b.eval_into(roll_die, buffer))
+                .expect("divisor range excludes zero"),
+            Ast::Call(f, a) => f.apply(a.eval_into(roll_die, buffer)),
+            Ast::Roll(count, sides) => {
+                let count = count.eval_into(roll_die, buffer).floor() as u32;
+                let sides = sides.eval_into(roll_die, buffer).floor() as u32;
+
+                let dice = Dice::new(count, Sides::Number(sides));
+                Ratio::from(dice.roll_into(roll_die, &mut buffer.rolls, &mut buffer.order))
+            }
+            Ast::Group(terms, keep, drop) => {
+                // Nested groups push their totals after this one's, and take
+                // them off again before it carries on.
+                let start = buffer.totals.len();
+                for term in terms {
+                    let total = term.eval_into(roll_die, buffer);
+                    buffer.totals.push(total);
+                }
+
+                let totals = &mut buffer.totals[start..];
+                totals.sort();
+
+                let kept = kept_ranks(keep, drop, totals.len());
+                let sum = totals[kept].iter().fold(Ratio::from(0), |sum, &t| sum + t);
+                buffer.totals.truncate(start);
+                sum
+            }
+        }
+    }
+
+    /// The whole constants added to or subtracted from the rest of the
+    /// expression, such as the 3 of `2d6+4-1`.
+    pub(crate) fn modifier(&self) -> i64 {
+        match self {
+            Ast::Add(a, b) => a.modifier().saturating_add(b.modifier()),
+            Ast::Sub(a, b) => a.modifier().saturating_sub(b.modifier()),
+            term => term.constant_value().unwrap_or(0),
+        }
+    }
+
+    /// The mean and, if it's known, the variance of the expression's exact
+    /// value. Dice terms are independent, so these add up across sums and
+    /// multiply out across products. Expressions with variables, division,
+    /// functions, or dice whose count or sides are rolled have neither.
+    pub(crate) fn moments(&self) -> Option<(f64, Option<f64>)> {
+        match self {
+            Ast::Dice(d) => d.moments(),
+            Ast::Constant(n) => Some((*n as f64, Some(0.0))),
+            Ast::Add(a, b) | Ast::Sub(a, b) => {
+                let ((ma, va), (mb, vb)) = (a.moments()?, b.moments()?);
+                let mean = match self {
+                    Ast::Add(..) => ma + mb,
+                    _ => ma - mb,
+                };
+                Some((mean, va.zip(vb).map(|(va, vb)| va + vb)))
+            }
+            Ast::Mul(a, b) => {
+                let ((ma, va), (mb, vb)) = (a.moments()?, b.moments()?);
+                let variance = va
+                    .zip(vb)
+                    .map(|(va, vb)| (va + ma * ma) * (vb + mb * mb) - ma * ma * mb * mb);
+                Some((ma * mb, variance))
+            }
+            Ast::Var(_) | Ast::Div(..) | Ast::Call(..) | Ast::Roll(..) | Ast::Group(..) => None,
+        }
+    }
+
+    /// The exact chance of each value the expression can come to. Dice terms
+    /// whose count or sides are rolled mix the chances of each they could
+    /// be. Expressions with variables, division, exploding dice, or groups
+    /// keeping or dropping some of their terms have none.
+    pub(crate) fn distribution(&self) -> Option<DiceDistribution> {
+        let combine = |a: &Ast, b: &Ast, f: fn(i64, i64) -> i64| {
+            Some(a.distribution()?.combine(&b.distribution()?, f))
+        };
+
+        match self {
+            Ast::Dice(d) => d.distribution(),
+            Ast::Constant(n) => Some(DiceDistribution::constant(*n)),
+            Ast::Add(a, b) => combine(a, b, i64::saturating_add),
+            Ast::Sub(a, b) => combine(a, b, i64::saturating_sub),
+            Ast::Mul(a, b) => combine(a, b, i64::saturating_mul),
+            Ast::Call(f, a) => Some(a.distribution()?.map(|v| f.apply(Ratio::from(v)).floor())),
+            Ast::Roll(count, sides) => {
+                let (count, sides) = (count.distribution()?, sides.distribution()?);
+                let mut parts = Vec::new();
+
+                for (c, p) in count.iter() {
+                    for (s, q) in sides.iter() {
+                        let dice = Dice::new(c as u32, Sides::Number(s as u32));
+                        parts.push((p * q, dice.distribution()?));
+                    }
+                }
+
+                Some(DiceDistribution::mix(parts))
+            }
+            Ast::Group(terms, keep, drop)
+                if kept_ranks(keep, drop, terms.len()).len() == terms.len() =>
+            {
+                terms
+                    .iter()
+                    .try_fold(DiceDistribution::constant(0), |sum, t| {
+                        Some(sum.combine(&t.distribution()?, i64::saturating_add))
+                    })
+            }
+            Ast::Var(_) | Ast::Div(..) | Ast::Group(..) => None,
+        }
+    }
+
+    /// The whole number the expression always comes to, if it has no dice
+    /// or variables.
+    fn constant_value(&self) -> Option<i64> {
+        let range = self.range();
+        match self.is_constant() && range.start() == range.end() {
+            true => Some(*range.start()),
+            false => None,
+        }
+    }
+
+    /// Rewrites the expression into a canonical form, in which each sum
+    /// lists its added terms before its subtracted ones, each from the
+    /// largest dice down, with its whole constants folded into one at the
+    /// end, as in `d8+d4-d6+3` for `2+1d4-1d6+1d8+1`.
+    pub(crate) fn normalize(self) -> Ast {
+        let mut added = Vec::new();
+        let mut subtracted = Vec::new();
+        self.split_sum(false, &mut added, &mut subtracted);
+
+        let mut constant = 0;
+        let mut fold = |terms: Vec<Ast>, sign: i64| {
+            let (constants, mut terms): (Vec<Ast>, Vec<Ast>) = terms
+                .into_iter()
+                .partition(|t| t.constant_value().is_some());
+            constant += sign
+                * constants
+                    .iter()
+                    .filter_map(|t| t.constant_value())
+                    .sum::<i64>();
+            terms.sort_by_cached_key(|t| t.sort_key());
+            terms
+        };
+        let added = fold(added, 1);
+        let subtracted = fold(subtracted, -1);
+
+        let mut added = added.into_iter();
+        let mut ast = match added.next() {
+            Some(first) => first,
+            None => {
+                let first = constant.max(0);
+                constant -= first;
+                Ast::Constant(first)
+            }
+        };
+
+        for term in added {
+            ast = Ast::Add(Box::new(ast), Box::new(term));
+        }
+        for term in subtracted {
+            ast = Ast::Sub(Box::new(ast), Box::new(term));
+        }
+
+        match constant {
+            0 => ast,
+            n if n < 0 => Ast::Sub(Box::new(ast), Box::new(Ast::Constant(-n))),
+            n => Ast::Add(Box::new(ast), Box::new(Ast::Constant(n))),
+        }
+    }
+
+    /// Replaces each dice term that always rolls the same total, such as
+    /// `1d1` or `3d[2]`, with that total, unless its total is negative or
+    /// the term can explode, is labelled, or can roll a critical. Dice whose
+    /// count and sides come to constants become plain dice terms.
+    pub(crate) fn collapse(self) -> Ast {
+        let collapse = |a: Box<Ast>| Box::new(a.collapse());
+
+        match self {
+            Ast::Dice(d)
+                if d.explode.is_none()
+                    && d.label.is_none()
+                    && d.critical_success.is_none()
+                    && d.critical_failure.is_none() =>
+            {
+                match d.range() {
+                    range if range.start() == range.end() && *range.start() >= 0 => {
+                        Ast::Constant(*range.start())
+                    }
+                    _ => Ast::Dice(d),
+                }
+            }
+            Ast::Add(a, b) => Ast::Add(collapse(a), collapse(b)),
+            Ast::Sub(a, b) => Ast::Sub(collapse(a), collapse(b)),
+            Ast::Mul(a, b) => Ast::Mul(collapse(a), collapse(b)),
+            Ast::Div(a, b) => Ast::Div(collapse(a), collapse(b)),
+            Ast::Call(f, a) => Ast::Call(f, collapse(a)),
+            Ast::Roll(count, sides) => {
+                let (count, sides) = (collapse(count), collapse(sides));
+
+                match (count.constant_value(), sides.constant_value()) {
+                    (Some(c), Some(s)) if c > 0 && s > 0 => {
+                        Ast::Dice(Box::new(Dice::new(c as u32, Sides::Number(s as u32)))).collapse()
+                    }
+                    _ => Ast::Roll(count, sides),
+                }
+            }
+            Ast::Group(terms, keep, drop) => Ast::Group(
+                terms.into_iter().map(|t| t.collapse()).collect(),
+                keep,
+                drop,
+            ),
+            term => term,
+        }
+    }
+
+    /// Collects the normalized terms of a sum, split by whether they're
+    /// added or subtracted.
+    fn split_sum(self, negated: bool, added: &mut Vec<Ast>, subtracted: &mut Vec<Ast>) {
+        match self {
+            Ast::Add(a, b) => {
+                a.split_sum(negated, added, subtracted);
+                b.split_sum(negated, added, subtracted);
+            }
+            Ast::Sub(a, b) => {
+                a.split_sum(negated, added, subtracted);
+                b.split_sum(!negated, added, subtracted);
+            }
+            term => match negated {
+                false => added.push(term.normalize_term()),
+                true => subtracted.push(term.normalize_term()),
+            },
+        }
+    }
+
+    /// Normalizes the operands of a term that isn't a sum, and replaces it
+    /// with its value if it's a constant that can be written as one.
+    fn normalize_term(self) -> Ast {
+        let term = match self {
+            Ast::Mul(a, b) => Ast::Mul(Box::new(a.normalize()), Box::new(b.normalize())),
+            Ast::Div(a, b) => Ast::Div(Box::new(a.normalize()), Box::new(b.normalize())),
+            Ast::Call(f, a) => Ast::Call(f, Box::new(a.normalize())),
+            Ast::Roll(count, sides) => {
+                Ast::Roll(Box::new(count.normalize()), Box::new(sides.normalize()))
+            }
+            Ast::Group(terms, keep, drop) => Ast::Group(
+                terms.into_iter().map(|t| t.normalize()).collect(),
+                keep,
+                drop,
+            ),
+            term => term,
+        };
+
+        match term.constant_value() {
+            Some(n) if n >= 0 => Ast::Constant(n),
+            _ => term,
+        }
+    }
+
+    /// Orders the terms of a normalized sum: dice terms first, from the
+    /// largest dice down, then any other terms.
+    fn sort_key(&self) -> (bool, Reverse<i64>, String) {
+        match self {
+            Ast::Dice(d) => (false, Reverse(*d.sides.range().end()), self.to_string()),
+            _ => (true, Reverse(0), self.to_string()),
+        }
+    }
+}
+
+impl Ast {
+    /// How tightly the node binds its operands, for deciding where
+    /// parentheses are needed when displaying it.
+    fn precedence(&self) -> u8 {
+        match self {
+            Ast::Add(..) | Ast::Sub(..) => 1,
+            Ast::Mul(..) | Ast::Div(..) => 2,
+            Ast::Dice(_)
+            | Ast::Constant(_)
+            | Ast::Var(_)
+            | Ast::Call(..)
+            | Ast::Roll(..)
+            | Ast::Group(..) => 3,
+        }
+    }
+
+    /// Displays the operands of a binary operator, grouping either side if
+    /// it wouldn't otherwise parse back into the same tree.
+    fn fmt_binary(&self, f: &mut Formatter, op: char, a: &Ast, b: &Ast) -> fmt::Result {
+        match a.precedence() < self.precedence() {
+            true => write!(f, "({})", a)?,
+            false => write!(f, "{}", a)?,
+        }
+
+        match b.precedence() <= self.precedence() {
+            true => write!(f, "{}({})", op, b),
+            false => write!(f, "{}{}", op, b),
+        }
+    }
+}
+
+impl Display for Ast {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Ast::Dice(d) => write!(f, "{}", d),
+            Ast::Constant(n) => write!(f, "{}", n),
+            Ast::Var(name) => write!(f, "${}", name),
+            Ast::Add(a, b) => self.fmt_binary(f, '+', a, b),
+            Ast::Sub(a, b) => self.fmt_binary(f, '-', a, b),
+            Ast::Mul(a, b) => self.fmt_binary(f, '*', a, b),
+            Ast::Div(a, b) => self.fmt_binary(f, '/', a, b),
+            Ast::Call(func, a) => write!(f, "{}({})", func, a),
+            Ast::Roll(count, sides) => {
+                match count.as_ref() {
+                    Ast::Constant(1) => {}
+                    Ast::Constant(n) => write!(f, "{}", n)?,
+                    count => write!(f, "({})", count)?,
+                }
+
+                match sides.as_ref() {
+                    Ast::Constant(n) => write!(f, "d{}", n),
+                    sides => write!(f, "d({})", sides),
+                }
+            }
+            Ast::Group(terms, keep, drop) => {
+                let terms: Vec<String> = terms.iter().map(|t| t.to_string()).collect();
+                write!(f, "{{{}}}{}", terms.join(","), keep)?;
+
+                match drop {
+                    Drop::High(n) => write!(f, "dh{}", n),
+                    Drop::Low(n) => write!(f, "dl{}", n),
+                    Drop::None => Ok(()),
+                }
+            }
+        }
+    }
+}
+
+/// Whether dice can be rolled with any count in `count` and any number of
+/// sides in `sides`.
+pub(crate) fn rollable(count: &RangeInclusive<i64>, sides: &RangeInclusive<i64>) -> bool {
+    let max = u32::MAX as i64;
+    *count.start() >= 0 && *count.end() <= max && *sides.start() >= 1 && *sides.end() <= max
+}
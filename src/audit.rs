@@ -0,0 +1,190 @@
+use crate::expr::{DiceExpr, DiceExprError, RollResult};
+use crate::fair::FairRoller;
+use crate::roller::Roller;
+use crate::seeded::SeededRoller;
+use std::collections::HashMap;
+
+/// Where a logged roll's dice came from, with whatever is needed to roll
+/// them again.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+#[non_exhaustive]
+pub enum RollSource {
+    /// A roller whose state can't be recorded, such as the thread's
+    /// generator.
+    Unrecorded,
+    /// A `SeededRoller` in the given state, which `SeededRoller::new` rolls
+    /// the same dice from.
+    Seeded(u64),
+    /// A `FairRoller` committed to a server seed, rolling from the given
+    /// client seed and counter.
+    Fair {
+        commitment: String,
+        client_seed: String,
+        counter: u64,
+    },
+}
+
+/// A roll recorded in a `RollLog`.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct LogEntry {
+    pub source: RollSource,
+    pub result: RollResult,
+}
+
+/// An audit trail of rolls, each with its expression, results and
+/// timestamp, and where its dice came from. Serializes as a list of
+/// entries.
+#[derive(Debug, PartialEq, Eq, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct RollLog {
+    entries: Vec<LogEntry>,
+}
+
+impl RollLog {
+    pub fn new() -> Self {
+        RollLog::default()
+    }
+
+    /// Rolls the expression as many times as its repeat count with
+    /// `roller`, logging each result.
+    pub fn roll<R: Roller + ?Sized>(
+        &mut self,
+        expr: &DiceExpr,
+        vars: &HashMap<String, i64>,
+        roller: &mut R,
+    ) -> Result<&[LogEntry], DiceExprError> {
+        self.roll_from(expr, vars, roller, |_| RollSource::Unrecorded)
+    }
+
+    /// Rolls as `roll` does, logging the state of the seeded roller before
+    /// each result.
+    pub fn roll_seeded(
+        &mut self,
+        expr: &DiceExpr,
+        vars: &HashMap<String, i64>,
+        roller: &mut SeededRoller,
+    ) -> Result<&[LogEntry], DiceExprError> {
+        self.roll_from(expr, vars, roller, SeededRoller::source)
+    }
+
+    /// Rolls as `roll` does, logging the commitment, client seed and
+    /// counter of the fair roller before each result.
+    pub fn roll_fair(
+        &mut self,
+        expr: &DiceExpr,
+        vars: &HashMap<String, i64>,
+        roller: &mut FairRoller,
+    ) -> Result<&[LogEntry], DiceExprError> {
+        self.roll_from(expr, vars, roller, FairRoller::source)
+    }
+
+    fn roll_from<R: Roller + ?Sized, F: Fn(&R) -> RollSource>(
+        &mut self,
+        expr: &DiceExpr,
+        vars: &HashMap<String, i64>,
+        roller: &mut R,
+        source: F,
+    ) -> Result<&[LogEntry], DiceExprError> {
+        expr.check_vars(vars)?;
+        let start = self.entries.len();
+
+        for _ in 0..expr.repeat() {
+            let source = source(roller);
+            let result = expr.roll_vars_with(vars, |sides| roller.roll_die(sides));
+            self.entries.push(LogEntry { source, result });
+        }
+
+        Ok(&self.entries[start..])
+    }
+
+    /// Records a roll made elsewhere.
+    pub fn record(&mut self, source: RollSource, result: RollResult) {
+        self.entries.push(LogEntry { source, result });
+    }
+
+    pub fn entries(&self) -> &[LogEntry] {
+        &self.entries
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roll_seeded() {
+        let expr = DiceExpr::try_from("3x(4d6kh3)").unwrap();
+        let mut log = RollLog::new();
+        log.roll_seeded(&expr, &HashMap::new(), &mut SeededRoller::new(42))
+            .unwrap();
+
+        assert_eq!(3, log.len());
+        for entry in log.entries() {
+            match entry.source {
+                RollSource::Seeded(state) => {
+                    assert_eq!(
+                        Ok(entry.result.clone()),
+                        SeededRoller::new(state).roll(&expr)
+                    )
+                }
+                _ => panic!("expected a seeded source"),
+            }
+        }
+    }
+
+    #[test]
+    fn roll_fair() {
+        let expr = DiceExpr::try_from("2x(1d20)").unwrap();
+        let mut roller = FairRoller::with_server_seed([3; 32]);
+        roller.set_client_seed("player");
+        let mut log = RollLog::new();
+        log.roll_fair(&expr, &HashMap::new(), &mut roller).unwrap();
+
+        assert_eq!(
+            RollSource::Fair {
+                commitment: FairRoller::with_server_seed([3; 32]).commitment(),
+                client_seed: String::from("player"),
+                counter: 0,
+            },
+            log.entries()[0].source
+        );
+        assert_ne!(log.entries()[0].source, log.entries()[1].source)
+    }
+
+    #[test]
+    fn roll_missing_variable() {
+        let expr = DiceExpr::try_from("1d20+$str").unwrap();
+        let mut log = RollLog::new();
+
+        assert!(log
+            .roll(&expr, &HashMap::new(), &mut rand::thread_rng())
+            .is_err());
+        assert!(log.is_empty())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let expr = DiceExpr::try_from("1d6").unwrap();
+        let mut log = RollLog::new();
+        log.roll_seeded(&expr, &HashMap::new(), &mut SeededRoller::new(1))
+            .unwrap();
+        let json = serde_json::to_value(&log).unwrap();
+
+        assert_eq!(serde_json::json!({ "seeded": 1 }), json[0]["source"]);
+        assert_eq!("d6", json[0]["result"]["expression"]);
+        assert!(json[0]["result"]["timestamp"].is_u64())
+    }
+}
@@ -1,10 +1,46 @@
 use clap::{arg, command, ArgAction};
-use diceroll::expr::DiceExpr;
-use std::convert::TryFrom;
+use diceroll::cortex::CortexPool;
+use diceroll::entropy::SecureRoller;
+use diceroll::expr::{Dialect, RollTotal};
+use diceroll::fair::{self, FairRoller};
+use diceroll::narrative::NarrativePool;
+use diceroll::roller::Roller;
+use diceroll::DiceExpr;
+use rand::thread_rng;
+use std::collections::HashMap;
+use std::io::{self, IsTerminal, Write};
 
 fn main() {
     let matches = roll().get_matches();
+    if let Some(("fair", matches)) = matches.subcommand() {
+        return roll_fair(matches);
+    }
+
     let verbose = matches.get_flag("verbose");
+    let total = matches.get_flag("total");
+    let signed = matches.get_flag("signed");
+    let skill = matches.get_one::<i64>("skill").copied();
+    let odds = matches.get_one::<i64>("odds").copied();
+    let terminal = io::stdout().is_terminal();
+    let mut roller: Box<dyn Roller> = match matches.get_flag("secure") {
+        true => Box::new(SecureRoller::new()),
+        false => Box::new(thread_rng()),
+    };
+    let vars: HashMap<String, i64> = matches
+        .get_many::<(String, i64)>("var")
+        .unwrap_or_default()
+        .cloned()
+        .collect();
+    let system = matches.get_one::<String>("system").map(|s| s.as_str());
+    let dialect = match system {
+        Some("wod") => Dialect::WorldOfDarkness {
+            difficulty: *matches.get_one::<u32>("difficulty").unwrap(),
+        },
+        Some("roll20") => Dialect::Roll20,
+        Some("foundry") => Dialect::Foundry,
+        Some("anydice") => Dialect::AnyDice,
+        _ => Dialect::Standard,
+    };
 
     for expr in matches
         .get_many::<String>("EXPR")
@@ -12,31 +48,233 @@ fn main() {
         .map(|v| v.as_str())
         .collect::<Vec<_>>()
     {
-        let dice = match DiceExpr::try_from(expr) {
-            Ok(d) => d,
-            Err(e) => {
+        if system == Some("cortex") {
+            match CortexPool::try_from(expr) {
+                Ok(pool) => {
+                    let roll = pool.roll_with_roller(&mut *roller);
+                    println!(
+                        "{}: {} (effect d{}{})",
+                        pool,
+                        roll.total,
+                        roll.effect,
+                        match (roll.is_botch(), roll.hitches) {
+                            (true, _) => String::from(", botch"),
+                            (false, 0) => String::new(),
+                            (false, 1) => String::from(", 1 hitch"),
+                            (false, n) => format!(", {} hitches", n),
+                        }
+                    );
+
+                    if verbose {
+                        let rolls: Vec<String> = roll
+                            .rolls
+                            .iter()
+                            .map(|(sides, value)| format!("d{}: {}", sides, value))
+                            .collect();
+                        println!("[{}]", rolls.join(", "));
+                        println!();
+                    }
+                }
+                Err(e) => println!("{}", e),
+            }
+            continue;
+        }
+
+        let exprs = match (
+            DiceExpr::parse_all(expr, dialect),
+            NarrativePool::try_from(expr),
+        ) {
+            (Ok(exprs), _) => exprs,
+            (Err(_), Ok(pool)) => {
+                let roll = pool.roll_with_roller(&mut *roller);
+                println!("{}: {}", pool, roll.net);
+
+                if verbose {
+                    let faces: Vec<String> = roll
+                        .faces
+                        .iter()
+                        .map(|(die, symbols)| format!("{}: {}", die, symbols))
+                        .collect();
+                    println!("[{}]", faces.join(", "));
+                    println!();
+                }
+                continue;
+            }
+            (Err(e), Err(_)) => {
                 println!("{}", e);
                 continue;
             }
         };
 
-        let (roll, rolls) = dice.roll();
-        println!("{}: {}", dice, roll);
+        for dice in exprs {
+            let dice = dice.signed(signed);
+            let vars = match dialect {
+                Dialect::Foundry => strip_data_paths(&dice, &vars),
+                _ => vars.clone(),
+            };
+
+            let results = match dice.roll_repeated_with_roller(&vars, &mut *roller) {
+                Ok(results) => results,
+                Err(e) => {
+                    println!("{}", e);
+                    continue;
+                }
+            };
+            for (i, result) in results.iter().enumerate() {
+                let critical = match skill.map(|skill| result.skill_check(skill)) {
+                    Some(check) => format!(
+                        " ({}{} by {})",
+                        if check.critical { "critical " } else { "" },
+                        if check.success { "success" } else { "failure" },
+                        check.margin.abs()
+                    ),
+                    None => String::from(
+                        match (result.is_critical_success(), result.is_critical_failure()) {
+                            (true, false) => " (critical success)",
+                            (false, true) => " (critical failure)",
+                            (true, true) => " (critical success and failure)",
+                            (false, false) => "",
+                        },
+                    ),
+                };
+
+                let labels: Vec<String> = result
+                    .label_totals()
+                    .iter()
+                    .map(|(label, total)| format!("{}: {}", label, total))
+                    .collect();
+                let labels = match labels.is_empty() {
+                    true => String::new(),
+                    false => format!(" [{}]", labels.join(", ")),
+                };
 
-        if verbose {
-            let sum: u16 = rolls.iter().sum();
-            println!("Rolls: {:?} = {}\n", rolls, sum);
+                let outcome = match &result.dc {
+                    Some(dc) => format!(
+                        "{} ({} vs DC {})",
+                        if dc.success { "Success" } else { "Failure" },
+                        result.total,
+                        dc.dc
+                    ),
+                    None => result.total.to_string(),
+                };
+
+                match results.len() {
+                    1 => println!("{}: {}{}{}", dice, outcome, labels, critical),
+                    _ => println!("{} #{}: {}{}{}", dice, i + 1, outcome, labels, critical),
+                }
+
+                if verbose {
+                    for dice in &result.dice {
+                        let dropped = dice.dropped();
+                        let rolls: Vec<String> = dice
+                            .rolls
+                            .iter()
+                            .enumerate()
+                            .map(|(i, r)| match terminal && dropped.contains(&i) {
+                                // Strike through dropped dice on a terminal, in
+                                // place of the tildes around them.
+                                true => {
+                                    format!("\x1b[9m{}\x1b[0m", r.to_string().trim_matches('~'))
+                                }
+                                false => r.to_string(),
+                            })
+                            .collect();
+                        println!("{}: [{}] = {}", dice.expr, rolls.join(", "), dice.total);
+
+                        if !dice.sets.is_empty() {
+                            let sets: Vec<String> =
+                                dice.sets.iter().map(|s| s.to_string()).collect();
+                            println!("sets: {}", sets.join(", "));
+                        }
+                    }
+                    if result.modifier != 0 {
+                        println!("modifier: {:+}", result.modifier);
+                    }
+                    println!();
+                }
+            }
+
+            if total && results.len() > 1 {
+                let sum: RollTotal = results.iter().sum();
+                println!("{} total: {}", dice, sum.total);
+            }
+
+            if let Some(n) = odds {
+                match dice.prob_at_least(n) {
+                    Some(p) => println!("{} odds of {}+: {:.2}%", dice, n, p * 100.0),
+                    None => println!("{} odds of {}+: unknown", dice, n),
+                }
+            }
         }
     }
 }
 
+/// Commits to a server seed, takes the player's client seed from
+/// `--client-seed` or else from stdin, rolls, and then reveals the server
+/// seed.
+fn roll_fair(matches: &clap::ArgMatches) {
+    let mut roller = match matches.get_one::<[u8; 32]>("server-seed") {
+        Some(seed) => FairRoller::with_server_seed(*seed),
+        None => FairRoller::new(),
+    };
+    println!("commitment: {}", roller.commitment());
+
+    let client_seed = match matches.get_one::<String>("client-seed") {
+        Some(seed) => seed.clone(),
+        None => {
+            print!("client seed: ");
+            io::stdout().flush().ok();
+            let mut line = String::new();
+            io::stdin().read_line(&mut line).ok();
+            line.trim().to_string()
+        }
+    };
+    roller.set_client_seed(&client_seed);
+
+    for expr in matches
+        .get_many::<String>("EXPR")
+        .unwrap_or_default()
+        .map(|v| v.as_str())
+    {
+        match DiceExpr::parse_all(expr, Dialect::Standard) {
+            Ok(exprs) => {
+                for dice in exprs {
+                    match roller.roll(&dice) {
+                        Ok(result) => println!("{:#}", result),
+                        Err(e) => println!("{}", e),
+                    }
+                }
+            }
+            Err(e) => println!("{}", e),
+        }
+    }
+
+    println!("server seed: {}", fair::hex(&roller.reveal()));
+}
+
 fn roll() -> clap::Command {
     command!("diceroll")
         .version("1.0")
         .author("Jesse B. Hannah <jesse@jbhannah.net>")
         .about("A command-line dice roller")
+        .args_conflicts_with_subcommands(true)
+        .subcommand_negates_reqs(true)
+        .subcommand(
+            clap::Command::new("fair")
+                .about("Rolls provably fairly, committing to a server seed before taking a client seed")
+                .arg(
+                    arg!([EXPR] "Dice expression(s) to roll, also separable by semicolons")
+                        .action(ArgAction::Append)
+                        .required(true),
+                )
+                .arg(arg!(--"client-seed" <SEED> "The player's seed, instead of reading it from stdin"))
+                .arg(
+                    arg!(--"server-seed" <HEX> "Replays rolls from a revealed server seed")
+                        .value_parser(parse_seed),
+                ),
+        )
         .arg(
-            arg!([EXPR] "Dice expression(s) to roll")
+            arg!([EXPR] "Dice expression(s) to roll, also separable by semicolons")
                 .action(ArgAction::Append)
                 .required(true),
         )
@@ -46,6 +284,68 @@ fn roll() -> clap::Command {
                 .long("verbose")
                 .action(ArgAction::SetTrue),
         )
+        .arg(
+            arg!([total] "Displays the sum of a repeated roll")
+                .short('t')
+                .long("total")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(arg!(--signed "Allows totals below zero").action(ArgAction::SetTrue))
+        .arg(
+            arg!(--secure "Rolls with the operating system's cryptographically secure generator")
+                .action(ArgAction::SetTrue),
+        )
+        .arg(
+            arg!(--var <VAR> "Sets a variable used in expressions, as name=value")
+                .value_parser(parse_var)
+                .action(ArgAction::Append),
+        )
+        .arg(
+            arg!(--skill <N> "Checks each roll against a skill, succeeding at or under it")
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            arg!(--odds <N> "Displays the exact chance of rolling N or more")
+                .value_parser(clap::value_parser!(i64)),
+        )
+        .arg(
+            arg!(--system <SYSTEM> "Rolls by the conventions of a game system or VTT")
+                .visible_alias("dialect")
+                .value_parser(["wod", "roll20", "foundry", "anydice", "cortex"]),
+        )
+        .arg(
+            arg!(--difficulty <N> "Difficulty of World of Darkness pools")
+                .value_parser(clap::value_parser!(u32).range(2..=10))
+                .default_value("6"),
+        )
+}
+
+fn parse_var(s: &str) -> Result<(String, i64), String> {
+    match s.split_once('=') {
+        Some((name, value)) => match value.parse() {
+            Ok(value) => Ok((name.trim_start_matches(['$', '@']).to_string(), value)),
+            Err(e) => Err(e.to_string()),
+        },
+        None => Err(String::from("expected name=value")),
+    }
+}
+
+/// Takes any of Foundry's data paths in `dice` missing from `vars` as zero,
+/// warning that they are, as Foundry itself does.
+fn strip_data_paths(dice: &DiceExpr, vars: &HashMap<String, i64>) -> HashMap<String, i64> {
+    let mut vars = vars.clone();
+    for name in dice.variables() {
+        if !vars.contains_key(name) {
+            eprintln!("warning: @{} isn't set, taking it as 0", name);
+            vars.insert(name.to_string(), 0);
+        }
+    }
+
+    vars
+}
+
+fn parse_seed(s: &str) -> Result<[u8; 32], String> {
+    fair::parse_seed(s).ok_or_else(|| String::from("expected 64 hex digits"))
 }
 
 #[test]
@@ -0,0 +1,200 @@
+use crate::expr::{DiceExpr, DiceExprError};
+use std::collections::{BTreeMap, HashMap};
+use std::num::NonZeroUsize;
+
+/// How often a `DiceCache` has found expressions already parsed.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+}
+
+impl CacheStats {
+    /// The share of lookups that were hits, from 0 to 1, or `None` if
+    /// there haven't been any.
+    pub fn hit_rate(&self) -> Option<f64> {
+        match self.hits + self.misses {
+            0 => None,
+            n => Some(self.hits as f64 / n as f64),
+        }
+    }
+}
+
+/// Parsed expressions kept by their text, for services rolling the same
+/// macros over and over to skip parsing them again. Expressions are kept
+/// once by how they display, so `2d6 + 3` and `2d6+3` share an entry, and
+/// each way they've been written without any leading or trailing
+/// whitespace finds it without parsing again. Once the cache is full, the
+/// least recently used is dropped to make room for the next.
+#[derive(Debug, Clone)]
+pub struct DiceCache {
+    capacity: NonZeroUsize,
+    entries: HashMap<String, Entry>,
+    spellings: HashMap<String, String>,
+    recency: BTreeMap<u64, String>,
+    clock: u64,
+    stats: CacheStats,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    dice: DiceExpr,
+    used: u64,
+    spellings: Vec<String>,
+}
+
+impl DiceCache {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        DiceCache {
+            capacity,
+            entries: HashMap::new(),
+            spellings: HashMap::new(),
+            recency: BTreeMap::new(),
+            clock: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    /// The parsed expression, parsing and keeping it if it isn't kept
+    /// already. Expressions that fail to parse aren't kept. A new way of
+    /// writing an expression that's kept counts as a miss, since it's
+    /// parsed to find that it is.
+    pub fn get(&mut self, expr: &str) -> Result<&DiceExpr, DiceExprError> {
+        let spelling = expr.trim();
+        self.clock += 1;
+
+        let key = match self.spellings.get(spelling) {
+            Some(key) => {
+                self.stats.hits += 1;
+                key.clone()
+            }
+            None => {
+                self.stats.misses += 1;
+                let parsed = DiceExpr::try_from(spelling)?;
+                let key = parsed.to_string();
+
+                if !self.entries.contains_key(&key) {
+                    if self.entries.len() >= self.capacity.get() {
+                        self.evict();
+                    }
+                    self.recency.insert(self.clock, key.clone());
+                    self.entries.insert(
+                        key.clone(),
+                        Entry {
+                            dice: parsed,
+                            used: self.clock,
+                            spellings: Vec::new(),
+                        },
+                    );
+                }
+                self.entries
+                    .get_mut(&key)
+                    .unwrap()
+                    .spellings
+                    .push(spelling.to_string());
+                self.spellings.insert(spelling.to_string(), key.clone());
+                key
+            }
+        };
+
+        let entry = self.entries.get_mut(&key).unwrap();
+        self.recency.remove(&entry.used);
+        self.recency.insert(self.clock, key);
+        entry.used = self.clock;
+        Ok(&entry.dice)
+    }
+
+    /// Drops the least recently used expression and every way it's been
+    /// written.
+    fn evict(&mut self) {
+        if let Some((_, key)) = self.recency.pop_first() {
+            if let Some(entry) = self.entries.remove(&key) {
+                for spelling in entry.spellings {
+                    self.spellings.remove(&spelling);
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        self.stats
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn capacity(&self) -> NonZeroUsize {
+        self.capacity
+    }
+
+    /// Drops every expression, keeping the stats.
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.spellings.clear();
+        self.recency.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cache(capacity: usize) -> DiceCache {
+        DiceCache::new(NonZeroUsize::new(capacity).unwrap())
+    }
+
+    #[test]
+    fn get() {
+        let mut cache = cache(4);
+
+        assert_eq!(
+            &DiceExpr::try_from("4d6kh3").unwrap(),
+            cache.get("4d6kh3").unwrap()
+        );
+        assert!(cache.get(" 4d6kh3 ").is_ok());
+        assert!(cache.get("2d6+").is_err());
+        assert_eq!(1, cache.len());
+        assert_eq!(CacheStats { hits: 1, misses: 2 }, cache.stats());
+        assert_eq!(Some(1.0 / 3.0), cache.stats().hit_rate())
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let mut cache = cache(2);
+        cache.get("1d4").unwrap();
+        cache.get("1d6").unwrap();
+        cache.get("1d4").unwrap();
+        cache.get("1d8").unwrap();
+
+        assert_eq!(2, cache.len());
+        cache.get("1d4").unwrap();
+        cache.get("1d6").unwrap();
+        assert_eq!(CacheStats { hits: 2, misses: 4 }, cache.stats())
+    }
+
+    #[test]
+    fn spellings_share_entry() {
+        let mut cache = cache(2);
+        cache.get("2d6 + 3").unwrap();
+        cache.get("2d6+3").unwrap();
+        cache.get("1d4").unwrap();
+        cache.get("2D6+3").unwrap();
+        assert_eq!(2, cache.len());
+        assert_eq!(CacheStats { hits: 0, misses: 4 }, cache.stats());
+
+        // Using 2d6+3 by any spelling keeps it over 1d4, whose own
+        // spellings go along with it.
+        cache.get(" 2d6 + 3").unwrap();
+        cache.get("1d8").unwrap();
+        assert_eq!(2, cache.len());
+        assert_eq!(4, cache.spellings.len());
+        assert!(!cache.spellings.contains_key("1d4"));
+        cache.get("2d6+3").unwrap();
+        assert_eq!(CacheStats { hits: 2, misses: 5 }, cache.stats())
+    }
+}
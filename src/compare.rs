@@ -0,0 +1,165 @@
+use crate::expr::DiceExprError;
+use alloc::string::ToString;
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::fmt::{self, Display, Formatter};
+use core::ops::RangeInclusive;
+
+/// A condition that individual die results are tested against, such as the
+/// `>8` in `d10!>8` or the `=[5,6]` in `d6!=[5,6]`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Comparison {
+    Eq(Vec<i64>),
+    Lt(i64),
+    Le(i64),
+    Gt(i64),
+    Ge(i64),
+}
+
+impl Comparison {
+    pub fn matches(&self, value: i64) -> bool {
+        match self {
+            Comparison::Eq(values) => values.contains(&value),
+            Comparison::Lt(n) => value < *n,
+            Comparison::Le(n) => value <= *n,
+            Comparison::Gt(n) => value > *n,
+            Comparison::Ge(n) => value >= *n,
+        }
+    }
+
+    /// Whether every one of the given die results matches, which would make
+    /// a repeating modifier such as an explosion loop forever.
+    pub fn matches_all<I: IntoIterator<Item = i64>>(&self, values: I) -> bool {
+        values.into_iter().all(|v| self.matches(v))
+    }
+
+    /// Whether every value in `range` matches other than any of `except`
+    /// between its ends, like `matches_all`. Only the ends need to be tried
+    /// against an inequality, and a list fails within as many values as it
+    /// and `except` hold, so a range of billions is decided at once.
+    pub fn matches_range(&self, range: RangeInclusive<i64>, except: &[i64]) -> bool {
+        let (start, end) = range.clone().into_inner();
+        match self {
+            Comparison::Eq(values) => range
+                .filter(|v| *v == start || *v == end || !except.contains(v))
+                .all(|v| values.contains(&v)),
+            _ => self.matches(start) && self.matches(end),
+        }
+    }
+}
+
+impl TryFrom<&str> for Comparison {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let err = || Self::Error::Comparison(s.to_string());
+
+        let (op, value) = match s.find(|c: char| !"<>=".contains(c)) {
+            Some(i) => s.split_at(i),
+            None => return Err(err()),
+        };
+
+        if let Some(list) = value.strip_prefix('[').and_then(|v| v.strip_suffix(']')) {
+            return match op {
+                "" | "=" => Ok(Comparison::Eq(
+                    list.split(',')
+                        .map(|v| v.trim().parse())
+                        .collect::<Result<_, _>>()?,
+                )),
+                _ => Err(err()),
+            };
+        }
+
+        let n: i64 = value.parse()?;
+
+        match op {
+            "" | "=" => Ok(Comparison::Eq(vec![n])),
+            "<" => Ok(Comparison::Lt(n)),
+            "<=" => Ok(Comparison::Le(n)),
+            ">" => Ok(Comparison::Gt(n)),
+            ">=" => Ok(Comparison::Ge(n)),
+            _ => Err(err()),
+        }
+    }
+}
+
+/// Displays the comparison with its operator, or with the alternate flag
+/// (`{:#}`) as a bare number when it's equal to a single value.
+impl Display for Comparison {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Comparison::Eq(values) if values.len() == 1 && f.alternate() => {
+                write!(f, "{}", values[0])
+            }
+            Comparison::Eq(values) if values.len() == 1 => write!(f, "={}", values[0]),
+            Comparison::Eq(values) => write!(
+                f,
+                "=[{}]",
+                values
+                    .iter()
+                    .map(|v| v.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            ),
+            Comparison::Lt(n) => write!(f, "<{}", n),
+            Comparison::Le(n) => write!(f, "<={}", n),
+            Comparison::Gt(n) => write!(f, ">{}", n),
+            Comparison::Ge(n) => write!(f, ">={}", n),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use alloc::string::String;
+
+    #[test]
+    fn try_from_str_bare() {
+        assert_eq!(Ok(Comparison::Eq(vec![1])), Comparison::try_from("1"))
+    }
+
+    #[test]
+    fn try_from_str_ge() {
+        assert_eq!(Ok(Comparison::Ge(8)), Comparison::try_from(">=8"))
+    }
+
+    #[test]
+    fn try_from_str_list() {
+        assert_eq!(
+            Ok(Comparison::Eq(vec![9, 10])),
+            Comparison::try_from("=[9,10]")
+        )
+    }
+
+    #[test]
+    fn try_from_str_invalid() {
+        assert_eq!(
+            Err(DiceExprError::Comparison(String::from("<[1,2]"))),
+            Comparison::try_from("<[1,2]")
+        )
+    }
+
+    #[test]
+    fn display_alternate() {
+        assert_eq!("=1", Comparison::Eq(vec![1]).to_string());
+        assert_eq!("1", format!("{:#}", Comparison::Eq(vec![1])));
+        assert_eq!("<3", format!("{:#}", Comparison::Lt(3)))
+    }
+
+    #[test]
+    fn matches_all() {
+        assert!(Comparison::Gt(0).matches_all(1..=6));
+        assert!(!Comparison::Gt(1).matches_all(1..=6))
+    }
+
+    #[test]
+    fn matches_range() {
+        assert!(Comparison::Lt(4_000_000_000).matches_range(1..=3_999_999_999, &[]));
+        assert!(!Comparison::Lt(3_999_999_999).matches_range(1..=4_000_000_000, &[]));
+        assert!(Comparison::Eq(vec![1, 2, 4]).matches_range(1..=4, &[3]));
+        assert!(!Comparison::Eq(vec![1, 2]).matches_range(1..=4_000_000_000, &[]))
+    }
+}
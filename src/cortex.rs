@@ -0,0 +1,155 @@
+use crate::expr::DiceExprError;
+use crate::roller::Roller;
+use lazy_static::lazy_static;
+use rand::thread_rng;
+use regex::Regex;
+use std::cmp::Reverse;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+
+/// The die sizes of a Cortex Prime pool, from d4 up to d12.
+const SIZES: [u32; 5] = [4, 6, 8, 10, 12];
+
+/// A Cortex Prime dice pool of mixed sizes, written as dice separated by
+/// spaces or plus signs, such as `d8 d10 d6` or `2d8+d6`.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CortexPool {
+    dice: Vec<u32>,
+}
+
+/// The outcome of rolling a `CortexPool`. Dice that rolled a 1 are hitches,
+/// and can't be kept. Of the rest, the two highest rolls are kept for the
+/// total, and the largest of the dice left over is the effect die, or a d4
+/// if there are none.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct CortexRoll {
+    /// The sides and value of each die rolled, in the order they're written.
+    pub rolls: Vec<(u32, u32)>,
+    pub total: u32,
+    pub effect: u32,
+    pub hitches: u32,
+}
+
+impl CortexRoll {
+    /// Whether every die rolled a 1.
+    pub fn is_botch(&self) -> bool {
+        self.hitches as usize == self.rolls.len()
+    }
+}
+
+impl TryFrom<&str> for CortexPool {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^\s*\d*d\d+(?:\s*[+\s]\s*\d*d\d+)*\s*$").unwrap();
+            static ref DIE: Regex = Regex::new(r"(\d*)d(\d+)").unwrap();
+        }
+
+        if !RE.is_match(s) {
+            return Err(Self::Error::Expr(s.to_string()));
+        }
+
+        let mut dice = Vec::new();
+        for caps in DIE.captures_iter(s) {
+            let count: usize = match &caps[1] {
+                "" => 1,
+                n => n.parse()?,
+            };
+            let sides: u32 = caps[2].parse()?;
+
+            if count == 0 || !SIZES.contains(&sides) {
+                return Err(Self::Error::Expr(s.to_string()));
+            }
+
+            dice.extend(std::iter::repeat_n(sides, count));
+        }
+
+        Ok(CortexPool { dice })
+    }
+}
+
+impl Display for CortexPool {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let dice: Vec<String> = self.dice.iter().map(|d| format!("d{}", d)).collect();
+        write!(f, "{}", dice.join(" "))
+    }
+}
+
+impl CortexPool {
+    pub fn roll(&self) -> CortexRoll {
+        self.roll_with_roller(&mut thread_rng())
+    }
+
+    /// Rolls the pool like `roll`, with each die rolled by `roller`.
+    pub fn roll_with_roller<R: Roller + ?Sized>(&self, roller: &mut R) -> CortexRoll {
+        self.roll_with(|sides| roller.roll_die(sides))
+    }
+
+    fn roll_with<F: FnMut(u32) -> u32>(&self, mut roll_die: F) -> CortexRoll {
+        let rolls: Vec<(u32, u32)> = self.dice.iter().map(|&d| (d, roll_die(d))).collect();
+
+        let mut kept: Vec<&(u32, u32)> = rolls.iter().filter(|(_, value)| *value > 1).collect();
+        kept.sort_by_key(|&&(sides, value)| (Reverse(value), sides));
+
+        let total = kept.iter().take(2).map(|(_, value)| value).sum();
+        let effect = kept
+            .iter()
+            .skip(2)
+            .map(|(sides, _)| *sides)
+            .max()
+            .unwrap_or(4);
+
+        CortexRoll {
+            total,
+            effect,
+            hitches: (rolls.len() - kept.len()) as u32,
+            rolls,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_from_str() {
+        for (expr, display) in [("d8 d10 d6", "d8 d10 d6"), ("2d8+d12", "d8 d8 d12")] {
+            assert_eq!(display, CortexPool::try_from(expr).unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn try_from_str_invalid() {
+        for expr in ["", "d8 d7", "0d6", "d8-d6", "2A"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                CortexPool::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_keeps_two() {
+        let pool = CortexPool::try_from("d8 d10 d6 d12").unwrap();
+        let mut values = vec![7, 1, 5, 3].into_iter();
+        let roll = pool.roll_with(|_| values.next().unwrap());
+
+        assert_eq!(12, roll.total);
+        assert_eq!(12, roll.effect);
+        assert_eq!(1, roll.hitches);
+        assert!(!roll.is_botch())
+    }
+
+    #[test]
+    fn roll_botch() {
+        let pool = CortexPool::try_from("d8 d6").unwrap();
+        let roll = pool.roll_with(|_| 1);
+
+        assert_eq!(0, roll.total);
+        assert_eq!(4, roll.effect);
+        assert_eq!(2, roll.hitches);
+        assert!(roll.is_botch())
+    }
+}
@@ -0,0 +1,156 @@
+use crate::compare::Comparison;
+use crate::expr::{Dice, Sides};
+use alloc::vec;
+
+/// A set of game-system conventions for reading dice expressions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub enum Dialect {
+    #[default]
+    Standard,
+    /// World of Darkness pools, where a d10 pool with no success condition
+    /// such as `7d10` counts successes against the difficulty, with 10s
+    /// rolled again and 1s cancelling successes, as in `7d10!>=6f1`.
+    WorldOfDarkness { difficulty: u32 },
+    /// Roll20 macros as pasted from its chat: a leading `/roll` or `/r`, or
+    /// an inline roll wrapped in `[[ ]]`, with character attributes such as
+    /// `@{strength_mod}` read as variables and labels allowed on any term,
+    /// as in `1d20+5[STR]`.
+    Roll20,
+    /// AnyDice `output` statements, such as `output 3d6 + 2 named "STR"`,
+    /// with dice of custom faces written `d{1,2,3}` and comments between
+    /// backslashes. Other AnyDice constructs, such as functions, sequences
+    /// and variables, fail naming the construct.
+    AnyDice,
+    /// Foundry VTT roll formulas, with modifiers in any order: `x` to
+    /// explode, `r` to reroll once and `rr` repeatedly, `k`, `kh`, `kl`,
+    /// `dh` and `dl` to keep or drop, `cs` and `cf` to count successes and
+    /// cancel them with failures, and `min` and `max`. Data paths such as
+    /// `@abilities.str.mod` are read as variables, which the `roll` binary
+    /// strips, taking them as zero with a warning, when they aren't set.
+    /// Flavor text is allowed on any term, as in `1d20+5[bonus]`.
+    Foundry,
+}
+
+impl Dialect {
+    /// Applies the dialect's conventions to a parsed dice term.
+    pub(crate) fn apply(&self, dice: Dice) -> Dice {
+        match self {
+            Dialect::WorldOfDarkness { difficulty }
+                if dice.sides == Sides::Number(10) && dice.success.is_none() =>
+            {
+                Dice {
+                    explode: dice.explode.or(Some(Comparison::Eq(vec![10]))),
+                    success: Some(Comparison::Ge(*difficulty as i64)),
+                    failure: Some(Comparison::Eq(vec![1])),
+                    ..dice
+                }
+            }
+            _ => dice,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::{DiceExpr, DiceExprError};
+    use alloc::string::{String, ToString};
+    use alloc::vec::Vec;
+
+    #[test]
+    fn parse_world_of_darkness() {
+        let dialect = Dialect::WorldOfDarkness { difficulty: 7 };
+
+        for (expr, canonical) in [
+            ("7d10", "7d10!>=7f1"),
+            ("7d10+1d6", "7d10!>=7f1+d6"),
+            ("5d10>=8", "5d10>=8"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::parse(expr, dialect).unwrap().to_string()
+            )
+        }
+    }
+
+    #[test]
+    fn parse_world_of_darkness_difficulty() {
+        let expr = "7d10";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            DiceExpr::parse(expr, Dialect::WorldOfDarkness { difficulty: 11 })
+        )
+    }
+
+    #[test]
+    fn parse_roll20() {
+        for (expr, canonical) in [
+            ("/roll 1d20+5[STR] + @{prof}", "d20+5+$prof"),
+            ("/r 2d6[fire damage]", "2d6[fire damage]"),
+            ("[[2d20kh1cs>19 + 3 [bonus]]]", "2d20kh1cs>19+3"),
+            ("[[ {4d6+3d8, 2d10}kh1 ]]", "{4d6+3d8,2d10}kh1"),
+            ("8d6!", "8d6!"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::parse(expr, Dialect::Roll20).unwrap().to_string()
+            )
+        }
+    }
+
+    #[test]
+    fn parse_foundry() {
+        for (expr, canonical) in [
+            ("4d6kh3", "4d6kh3"),
+            ("2d20k + @abilities.str.mod", "2d20kh1+$abilities.str.mod"),
+            ("4d6rr1x", "4d6!r1"),
+            ("1d20r1 + 5[bonus]", "d20ro1+5"),
+            ("4d6dl1min2", "4d6min2-L"),
+            ("10d10cs>=8cf1", "10d10>=8f1"),
+            ("2d6[fire]", "2d6[fire]"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::parse(expr, Dialect::Foundry).unwrap().to_string()
+            )
+        }
+
+        assert!(DiceExpr::parse("1d20+@abilities.str.mod", Dialect::Standard).is_err())
+    }
+
+    #[test]
+    fn parse_anydice() {
+        for (expr, canonical) in [
+            ("output 3d6 + 2", "3d6+2"),
+            (
+                "output d{1,2,3} \\ custom die \\ named \"Custom\"",
+                "d[1,2,3]",
+            ),
+            ("output 4d6 - d4 named \"STR\"", "4d6-d4"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::parse(expr, Dialect::AnyDice).unwrap().to_string()
+            )
+        }
+
+        let exprs = DiceExpr::parse_all("output 3d6\noutput 2d6+6", Dialect::AnyDice).unwrap();
+        assert_eq!(
+            vec!["3d6", "2d6+6"],
+            exprs.iter().map(|e| e.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn parse_world_of_darkness_forever() {
+        let dialect = Dialect::WorldOfDarkness { difficulty: 6 };
+        for expr in ["7d10r<10", "7d10min10"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                DiceExpr::parse(expr, dialect)
+            )
+        }
+        assert!(DiceExpr::parse("7d10r<10", Dialect::Standard).is_ok())
+    }
+}
@@ -1,30 +1,195 @@
-use mockall::automock;
+use crate::distribution::DiceDistribution;
+use crate::roller::Roller;
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
 
-#[derive(PartialEq, Debug)]
-pub struct Die {
-    sides: u16,
+/// A die whose faces can be anything, such as symbols, colors, or the
+/// entries of a table, each as likely to be rolled as the others. Each is
+/// rolled as a numeric die of the same size, with the number picking the
+/// face.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Die<T> {
+    faces: Vec<T>,
 }
 
-#[automock]
-impl Die {
-    pub fn new(sides: u16) -> Self {
-        Die { sides }
+impl<T> Die<T> {
+    /// A die with the given faces, or `None` if there aren't any, or more
+    /// than `u32::MAX` of them.
+    pub fn new(faces: Vec<T>) -> Option<Self> {
+        match faces.is_empty() || u32::try_from(faces.len()).is_err() {
+            true => None,
+            false => Some(Die { faces }),
+        }
     }
 
-    pub fn roll<R: rand::Rng + 'static>(&self, rng: &mut R) -> u16 {
-        rng.gen_range(1..=self.sides)
+    pub fn faces(&self) -> &[T] {
+        &self.faces
+    }
+
+    pub fn sides(&self) -> u32 {
+        self.faces.len() as u32
+    }
+
+    #[cfg(feature = "std")]
+    pub fn roll(&self) -> &T {
+        self.roll_with_roller(&mut thread_rng())
+    }
+
+    pub fn roll_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> &T {
+        self.roll_with_roller(rng)
+    }
+
+    pub fn roll_with_roller<R: Roller + ?Sized>(&self, roller: &mut R) -> &T {
+        &self.faces[roller.roll_die(self.sides()) as usize - 1]
+    }
+}
+
+impl<T: PartialEq> Die<T> {
+    /// The chance of rolling `face`, which is greater for a face that's on
+    /// the die more than once.
+    pub fn chance(&self, face: &T) -> f64 {
+        let count = self.faces.iter().filter(|f| *f == face).count();
+        count as f64 / self.faces.len() as f64
+    }
+}
+
+impl Die<i64> {
+    /// A die numbered from 1 up to its sides, or `None` if it has none.
+    pub fn numbered(sides: u32) -> Option<Self> {
+        Die::new((1..=sides as i64).collect())
+    }
+
+    /// The exact chance of rolling each face.
+    pub fn distribution(&self) -> DiceDistribution {
+        let p = 1.0 / self.faces.len() as f64;
+        DiceDistribution::new(self.faces.iter().map(|&face| (face, p)))
     }
 }
 
+/// A die of `SIDES` sides, numbered from 1 up, whose size is checked when
+/// it's compiled rather than when it's constructed, and which holds
+/// nothing at all. A `ConstDie<0>` fails to compile.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct ConstDie<const SIDES: u16>(());
+
+impl<const SIDES: u16> ConstDie<SIDES> {
+    const SIDES_NONZERO: () = assert!(SIDES > 0, "a die must have at least one side");
+
+    pub const fn new() -> Self {
+        let () = Self::SIDES_NONZERO;
+        ConstDie(())
+    }
+
+    pub const fn sides(&self) -> u16 {
+        SIDES
+    }
+
+    #[cfg(feature = "std")]
+    pub fn roll(&self) -> u16 {
+        self.roll_with_roller(&mut thread_rng())
+    }
+
+    pub fn roll_with_roller<R: Roller + ?Sized>(&self, roller: &mut R) -> u16 {
+        roller.roll_die(SIDES.into()) as u16
+    }
+
+    /// The exact chance of rolling each face.
+    pub fn distribution(&self) -> DiceDistribution {
+        let p = 1.0 / f64::from(SIDES);
+        DiceDistribution::new((1..=SIDES.into()).map(|face| (face, p)))
+    }
+}
+
+impl<const SIDES: u16> Default for ConstDie<SIDES> {
+    fn default() -> Self {
+        ConstDie::new()
+    }
+}
+
+pub type D4 = ConstDie<4>;
+pub type D6 = ConstDie<6>;
+pub type D8 = ConstDie<8>;
+pub type D10 = ConstDie<10>;
+pub type D12 = ConstDie<12>;
+pub type D20 = ConstDie<20>;
+pub type D100 = ConstDie<100>;
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use rand::rngs::mock::StepRng;
+    use crate::seeded::SeededRoller;
+    use alloc::vec;
+
+    #[derive(Debug, PartialEq, Eq, Clone, Copy)]
+    enum Color {
+        Red,
+        Green,
+        Blue,
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(None, Die::<Color>::new(vec![]));
+        assert_eq!(None, Die::numbered(0));
+        assert_eq!(6, Die::numbered(6).unwrap().sides())
+    }
+
+    #[test]
+    fn roll_with_roller() {
+        let die = Die::new(vec![Color::Red, Color::Green, Color::Blue]).unwrap();
+        let mut roller = SeededRoller::new(3);
+
+        for _ in 0..20 {
+            assert!(die.faces().contains(die.roll_with_roller(&mut roller)))
+        }
+        #[cfg(feature = "std")]
+        assert!(die.faces().contains(die.roll()))
+    }
 
     #[test]
-    fn test_roll() {
-        let mut rng = StepRng::new(1, 0);
-        let die = Die::new(4);
-        assert_eq!(die.roll(&mut rng), 1);
+    fn const_die() {
+        const D: D20 = D20::new();
+        let mut roller = SeededRoller::new(11);
+
+        assert_eq!(0, core::mem::size_of::<D20>());
+        assert_eq!(20, D.sides());
+        for _ in 0..20 {
+            assert!((1..=20).contains(&D.roll_with_roller(&mut roller)))
+        }
+        #[cfg(feature = "std")]
+        assert!((1..=6).contains(&D6::new().roll()))
+    }
+
+    #[test]
+    fn distribution() {
+        let die = Die::new(vec![1, 2, 2, 4]).unwrap();
+        let distribution = die.distribution();
+
+        assert_eq!(0.5, distribution.pmf(2));
+        assert_eq!(0.0, distribution.pmf(3));
+        assert_eq!(2.25, distribution.mean());
+        assert_eq!(
+            Die::numbered(20).unwrap().distribution(),
+            D20::new().distribution()
+        );
+        assert_eq!(0.05, D20::new().distribution().pmf(20))
+    }
+
+    #[test]
+    fn chance() {
+        let die = Die::new(vec![Color::Red, Color::Red, Color::Blue]).unwrap();
+
+        assert_eq!(2.0 / 3.0, die.chance(&Color::Red));
+        assert_eq!(0.0, die.chance(&Color::Green))
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn roll_strings() {
+        let die = Die::new(vec!["hit", "miss"]).unwrap();
+
+        assert!(["hit", "miss"].contains(die.roll()))
     }
 }
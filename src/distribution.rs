@@ -0,0 +1,194 @@
+use alloc::collections::BTreeMap;
+
+/// The exact chance of rolling each total of an expression, as found by
+/// `DiceExpr::distribution`.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DiceDistribution {
+    probabilities: BTreeMap<i64, f64>,
+}
+
+impl DiceDistribution {
+    /// The distribution of a total that's always `n`.
+    pub(crate) fn constant(n: i64) -> Self {
+        DiceDistribution {
+            probabilities: BTreeMap::from([(n, 1.0)]),
+        }
+    }
+
+    /// The distribution of totals with the given chances, adding up the
+    /// chances of any total that's given more than once.
+    pub(crate) fn new<I: IntoIterator<Item = (i64, f64)>>(probabilities: I) -> Self {
+        let mut merged = BTreeMap::new();
+        for (total, p) in probabilities {
+            *merged.entry(total).or_insert(0.0) += p;
+        }
+
+        DiceDistribution {
+            probabilities: merged,
+        }
+    }
+
+    /// The distribution of `f` applied to independent totals of this
+    /// distribution and `other`, such as their sum.
+    pub(crate) fn combine<F: Fn(i64, i64) -> i64>(&self, other: &Self, f: F) -> Self {
+        let f = &f;
+        DiceDistribution::new(self.probabilities.iter().flat_map(|(&a, &p)| {
+            other
+                .probabilities
+                .iter()
+                .map(move |(&b, &q)| (f(a, b), p * q))
+        }))
+    }
+
+    /// The distribution of `f` applied to each total.
+    pub(crate) fn map<F: Fn(i64) -> i64>(&self, f: F) -> Self {
+        DiceDistribution::new(self.probabilities.iter().map(|(&total, &p)| (f(total), p)))
+    }
+
+    /// The distribution of rolling one of several distributions, picked
+    /// with the chance given alongside each.
+    pub(crate) fn mix<I: IntoIterator<Item = (f64, DiceDistribution)>>(parts: I) -> Self {
+        DiceDistribution::new(parts.into_iter().flat_map(|(weight, part)| {
+            part.probabilities
+                .into_iter()
+                .map(move |(total, p)| (total, weight * p))
+        }))
+    }
+
+    /// The chance of rolling exactly `total`.
+    pub fn pmf(&self, total: i64) -> f64 {
+        self.probabilities.get(&total).copied().unwrap_or(0.0)
+    }
+
+    /// The chance of rolling `total` or less.
+    pub fn cdf(&self, total: i64) -> f64 {
+        self.probabilities.range(..=total).map(|(_, p)| p).sum()
+    }
+
+    /// The lowest total rolled at least `p` of the time or less, as a
+    /// fraction from 0 to 1, such as the median for 0.5 or the least of the
+    /// top 10% of totals for 0.9. Chances that add up to `p` of all of
+    /// them but for the rounding of adding them up count as reaching it,
+    /// and a `p` of 1 is always the highest total, however unlikely.
+    pub fn quantile(&self, p: f64) -> i64 {
+        if p >= 1.0 {
+            return self.max();
+        }
+
+        let mass: f64 = self.probabilities.values().sum();
+        let mut cumulative = 0.0;
+        for (i, (total, q)) in self.iter().enumerate() {
+            cumulative += q;
+            // Each chance added can round the sum by up to an epsilon of it.
+            if cumulative >= (p - (i + 1) as f64 * f64::EPSILON) * mass {
+                return total;
+            }
+        }
+        self.max()
+    }
+
+    /// The chance of rolling `total` or less, as a fraction from 0 to 1,
+    /// placing it among the totals that can be rolled.
+    pub fn percentile_of(&self, total: i64) -> f64 {
+        self.cdf(total).min(1.0)
+    }
+
+    /// Each total that can be rolled, from the lowest up, with its chance.
+    pub fn iter(&self) -> impl Iterator<Item = (i64, f64)> + '_ {
+        self.probabilities.iter().map(|(&total, &p)| (total, p))
+    }
+
+    pub fn min(&self) -> i64 {
+        *self.probabilities.keys().next().unwrap()
+    }
+
+    pub fn max(&self) -> i64 {
+        *self.probabilities.keys().next_back().unwrap()
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.iter().map(|(total, p)| total as f64 * p).sum()
+    }
+
+    pub fn variance(&self) -> f64 {
+        let mean = self.mean();
+        self.iter()
+            .map(|(total, p)| {
+                let deviation = total as f64 - mean;
+                deviation * deviation * p
+            })
+            .sum()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    fn die(sides: i64) -> DiceDistribution {
+        DiceDistribution::new((1..=sides).map(|v| (v, 1.0 / sides as f64)))
+    }
+
+    #[test]
+    fn combine() {
+        let sum = die(6).combine(&die(6), |a, b| a + b);
+
+        assert_eq!((2, 12), (sum.min(), sum.max()));
+        assert!((sum.pmf(7) - 6.0 / 36.0).abs() < 1e-12);
+        assert!((sum.cdf(4) - 6.0 / 36.0).abs() < 1e-12);
+        assert_eq!(0.0, sum.pmf(13))
+    }
+
+    #[test]
+    fn map() {
+        let clamped = die(4).map(|v| (v - 3).max(0));
+
+        assert_eq!(
+            vec![(0, 0.75), (1, 0.25)],
+            clamped.iter().collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn mix() {
+        let mixed = DiceDistribution::mix([(0.5, die(2)), (0.5, DiceDistribution::constant(2))]);
+
+        assert_eq!(vec![(1, 0.25), (2, 0.75)], mixed.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn quantile() {
+        let sum = die(6).combine(&die(6), |a, b| a + b);
+
+        assert_eq!(7, sum.quantile(0.5));
+        assert_eq!(10, sum.quantile(0.9));
+        assert_eq!((2, 12), (sum.quantile(0.0), sum.quantile(1.0)));
+        assert_eq!(10, die(20).quantile(0.5))
+    }
+
+    #[test]
+    fn quantile_tail() {
+        let sum = (1..200).fold(die(6), |sum, _| sum.combine(&die(6), |a, b| a + b));
+
+        assert_eq!(1200, sum.quantile(1.0));
+        assert!(sum.quantile(1.0 - 1e-12) < 1200);
+        assert_eq!(200, sum.quantile(0.0))
+    }
+
+    #[test]
+    fn percentile_of() {
+        let sum = die(6).combine(&die(6), |a, b| a + b);
+
+        assert!((sum.percentile_of(7) - 21.0 / 36.0).abs() < 1e-12);
+        assert_eq!(0.0, sum.percentile_of(1));
+        assert_eq!(1.0, sum.percentile_of(12))
+    }
+
+    #[test]
+    fn moments() {
+        assert_eq!(3.5, die(6).mean());
+        assert!((die(6).variance() - 35.0 / 12.0).abs() < 1e-12)
+    }
+}
@@ -0,0 +1,244 @@
+use crate::entropy::{EntropyRoller, EntropySource};
+use crate::fair;
+use rand::{RngCore, SeedableRng};
+use rand_chacha::ChaCha20Rng;
+use serde::{Deserialize, Deserializer};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+
+/// The League of Entropy's main drand chain, which publishes a beacon every
+/// 30 seconds.
+pub const DEFAULT_URL: &str = "https://api.drand.sh";
+
+/// A round of randomness published by a drand chain. Its 32 bytes seed the
+/// ChaCha20 generator of its `roller`, so that anyone can check a roll made
+/// with it by fetching the same round and rolling again.
+///
+/// Deserializing a beacon checks that its randomness is the SHA-256 hash of
+/// its signature, as drand derives it, but not the BLS signature itself
+/// against the chain's public key. Check that with a drand client to trust
+/// that the chain published the round.
+#[derive(Debug, PartialEq, Eq, Clone, Hash, Deserialize)]
+#[serde(try_from = "PublishedBeacon")]
+pub struct Beacon {
+    pub round: u64,
+    pub randomness: [u8; 32],
+    pub signature: String,
+}
+
+/// A beacon as a chain publishes it, before its randomness is checked.
+#[derive(Deserialize)]
+struct PublishedBeacon {
+    round: u64,
+    #[serde(deserialize_with = "randomness")]
+    randomness: [u8; 32],
+    signature: String,
+}
+
+impl TryFrom<PublishedBeacon> for Beacon {
+    type Error = String;
+
+    fn try_from(beacon: PublishedBeacon) -> Result<Self, Self::Error> {
+        let signature =
+            parse_hex(&beacon.signature).ok_or_else(|| String::from("expected a hex signature"))?;
+        if Sha256::digest(signature)[..] != beacon.randomness {
+            return Err(String::from("randomness isn't the hash of the signature"));
+        }
+
+        Ok(Beacon {
+            round: beacon.round,
+            randomness: beacon.randomness,
+            signature: beacon.signature,
+        })
+    }
+}
+
+impl Beacon {
+    /// A roller drawing from the round's randomness, which rolls the same
+    /// dice for everyone.
+    pub fn roller(&self) -> EntropyRoller<ChaCha20Rng> {
+        EntropyRoller::new(ChaCha20Rng::from_seed(self.randomness))
+    }
+}
+
+impl EntropySource for ChaCha20Rng {
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+}
+
+/// Reads a beacon's randomness from the 64 hex digits it's published as.
+fn randomness<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+    let s = String::deserialize(deserializer)?;
+    fair::parse_seed(&s).ok_or_else(|| serde::de::Error::custom("expected 64 hex digits"))
+}
+
+/// Reads bytes of any length from the pairs of hex digits they're shown as.
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) || !s.is_ascii() {
+        return None;
+    }
+
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&s[i..i + 2], 16).ok())
+        .collect()
+}
+
+/// The ways fetching a beacon can fail: the request itself, including the
+/// chain answering with an error or something other than a beacon, or the
+/// chain answering with a different round than the one asked for.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum DrandError {
+    Request(reqwest::Error),
+    Round(u64),
+}
+
+impl Error for DrandError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::Request(e) => Some(e),
+            Self::Round(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for DrandError {
+    fn from(e: reqwest::Error) -> Self {
+        Self::Request(e)
+    }
+}
+
+impl Display for DrandError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Self::Request(_) => write!(f, "Couldn't fetch a drand beacon"),
+            Self::Round(round) => write!(f, "Fetched the wrong drand round for {}", round),
+        }
+    }
+}
+
+/// Fetches beacons from a drand chain, keeping each round it's fetched,
+/// since a round's randomness never changes once it's published.
+#[derive(Debug, Clone)]
+pub struct DrandClient {
+    http: reqwest::Client,
+    url: String,
+    cache: HashMap<u64, Beacon>,
+}
+
+impl DrandClient {
+    pub fn new() -> Self {
+        DrandClient::with_url(DEFAULT_URL)
+    }
+
+    /// A client for the chain served at `url`, such as
+    /// `https://api.drand.sh/<chain hash>` for a chain other than the main
+    /// one.
+    pub fn with_url(url: &str) -> Self {
+        DrandClient {
+            http: reqwest::Client::new(),
+            url: url.trim_end_matches('/').to_string(),
+            cache: HashMap::new(),
+        }
+    }
+
+    /// Fetches the most recently published round.
+    pub async fn latest(&mut self) -> Result<Beacon, DrandError> {
+        let beacon = self.fetch("latest").await?;
+        self.cache.insert(beacon.round, beacon.clone());
+        Ok(beacon)
+    }
+
+    /// Fetches the given round, unless it's been fetched already.
+    pub async fn round(&mut self, round: u64) -> Result<Beacon, DrandError> {
+        if let Some(beacon) = self.cache.get(&round) {
+            return Ok(beacon.clone());
+        }
+
+        let beacon = self.fetch(&round.to_string()).await?;
+        if beacon.round != round {
+            return Err(DrandError::Round(round));
+        }
+
+        self.cache.insert(round, beacon.clone());
+        Ok(beacon)
+    }
+
+    async fn fetch(&self, round: &str) -> Result<Beacon, DrandError> {
+        Ok(self
+            .http
+            .get(format!("{}/public/{}", self.url, round))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?)
+    }
+}
+
+impl Default for DrandClient {
+    fn default() -> Self {
+        DrandClient::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::DiceExpr;
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    const BEACON: &str = r#"{
+        "round": 1000,
+        "randomness": "fe290beca10872ef2fb164d2aa4442de4566183ec51c56ff3cd603d930e54fdd",
+        "signature": "b44679b9a59af2ec876b1a6b1ad52ea9b1615fc3982b19576350f93447cb1125e342b73a8dd2bacbe47e4b6b63ed5e39"
+    }"#;
+
+    fn beacon() -> Beacon {
+        serde_json::from_str(BEACON).unwrap()
+    }
+
+    #[test]
+    fn deserialize() {
+        let beacon = beacon();
+
+        assert_eq!(1000, beacon.round);
+        assert_eq!([0xfe, 0x29, 0x0b], beacon.randomness[..3]);
+        assert!(serde_json::from_str::<Beacon>(&BEACON.replace("fe29", "zz29")).is_err())
+    }
+
+    #[test]
+    fn deserialize_unhashed() {
+        assert!(serde_json::from_str::<Beacon>(&BEACON.replace("fe29", "fe28")).is_err());
+        assert!(serde_json::from_str::<Beacon>(&BEACON.replace("b446", "b447")).is_err());
+        assert!(serde_json::from_str::<Beacon>(&BEACON.replace("b446", "zz46")).is_err())
+    }
+
+    #[test]
+    fn roller_reproducible() {
+        let expr = DiceExpr::try_from("4d6kh3+1d20").unwrap();
+
+        assert_eq!(
+            expr.roll_with_roller(&mut beacon().roller()),
+            expr.roll_with_roller(&mut beacon().roller())
+        )
+    }
+
+    #[test]
+    fn round_cached() {
+        let mut client = DrandClient::new();
+        client.cache.insert(1000, beacon());
+
+        let mut round = pin!(client.round(1000));
+        match round.as_mut().poll(&mut Context::from_waker(Waker::noop())) {
+            Poll::Ready(result) => assert_eq!(beacon(), result.unwrap()),
+            Poll::Pending => panic!("fetched a cached round"),
+        }
+    }
+}
@@ -0,0 +1,181 @@
+#[cfg(feature = "std")]
+use crate::expr::{DiceExpr, DiceExprError, RollResult};
+use crate::roller::Roller;
+use crate::seeded::SeededRoller;
+#[cfg(feature = "std")]
+use rand::rngs::{OsRng, ThreadRng};
+#[cfg(feature = "std")]
+use rand::{thread_rng, RngCore, TryRngCore};
+
+/// A source of uniformly random 64-bit numbers for an `EntropyRoller` to
+/// roll dice from.
+pub trait EntropySource {
+    fn next_u64(&mut self) -> u64;
+}
+
+/// The thread's generator, as used by `DiceExpr::roll`.
+#[cfg(feature = "std")]
+impl EntropySource for ThreadRng {
+    fn next_u64(&mut self) -> u64 {
+        RngCore::next_u64(self)
+    }
+}
+
+/// The operating system's generator, which is slower than the thread's but
+/// suitable for cryptography. Panics if the operating system can't provide
+/// any numbers.
+#[cfg(feature = "std")]
+impl EntropySource for OsRng {
+    fn next_u64(&mut self) -> u64 {
+        self.try_next_u64()
+            .expect("the operating system's generator failed")
+    }
+}
+
+/// Rolls a die of `sides` sides from numbers drawn from `source`, taking
+/// the next number modulo its sides, plus one. Numbers from the top of the
+/// range that doesn't divide evenly by the sides are drawn again, so that
+/// every face is as likely as the others.
+pub(crate) fn uniform<S: EntropySource + ?Sized>(source: &mut S, sides: u32) -> u32 {
+    let sides = sides as u64;
+    let zone = u64::MAX - u64::MAX % sides;
+
+    loop {
+        let n = source.next_u64();
+        if n < zone {
+            return (n % sides) as u32 + 1;
+        }
+    }
+}
+
+/// Rolls dice from whichever entropy source it's constructed with, such as
+/// the thread's generator, a seeded one for replaying rolls, or the
+/// operating system's.
+#[derive(Debug, Clone)]
+pub struct EntropyRoller<S> {
+    source: S,
+}
+
+impl<S: EntropySource> EntropyRoller<S> {
+    pub fn new(source: S) -> Self {
+        EntropyRoller { source }
+    }
+}
+
+#[cfg(feature = "std")]
+impl EntropyRoller<ThreadRng> {
+    pub fn thread() -> Self {
+        EntropyRoller::new(thread_rng())
+    }
+}
+
+impl EntropyRoller<SeededRoller> {
+    /// Rolls the same dice as `SeededRoller::new(seed)`.
+    pub fn seeded(seed: u64) -> Self {
+        EntropyRoller::new(SeededRoller::new(seed))
+    }
+}
+
+#[cfg(feature = "std")]
+impl EntropyRoller<OsRng> {
+    pub fn os() -> Self {
+        EntropyRoller::new(OsRng)
+    }
+}
+
+impl<S: EntropySource> Roller for EntropyRoller<S> {
+    fn roll_die(&mut self, sides: u32) -> u32 {
+        uniform(&mut self.source, sides)
+    }
+}
+
+/// Rolls dice from the operating system's cryptographically secure
+/// generator, for rolls that mustn't be predictable even to someone who has
+/// seen earlier ones, as in tournaments or wherever money is at stake.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct SecureRoller {
+    roller: EntropyRoller<OsRng>,
+}
+
+#[cfg(feature = "std")]
+impl SecureRoller {
+    pub fn new() -> Self {
+        SecureRoller {
+            roller: EntropyRoller::os(),
+        }
+    }
+
+    /// Rolls the expression once, ignoring any repeat count and taking any
+    /// variables as zero.
+    pub fn roll(&mut self, expr: &DiceExpr) -> Result<RollResult, DiceExprError> {
+        expr.roll_with_roller(self)
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for SecureRoller {
+    fn default() -> Self {
+        SecureRoller::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Roller for SecureRoller {
+    fn roll_die(&mut self, sides: u32) -> u32 {
+        self.roller.roll_die(sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::DiceExpr;
+    use alloc::vec;
+    use alloc::vec::Vec;
+
+    /// Draws the given numbers in turn.
+    struct Script(Vec<u64>);
+
+    impl EntropySource for Script {
+        fn next_u64(&mut self) -> u64 {
+            self.0.remove(0)
+        }
+    }
+
+    #[test]
+    fn uniform_rejects_remainder() {
+        let mut source = Script(vec![u64::MAX, 7]);
+
+        assert_eq!(2, uniform(&mut source, 6));
+        assert!(source.0.is_empty())
+    }
+
+    #[test]
+    fn seeded() {
+        let expr = DiceExpr::try_from("4d6kh3+1d20").unwrap();
+
+        assert_eq!(
+            SeededRoller::new(42).roll(&expr),
+            expr.roll_with_roller(&mut EntropyRoller::seeded(42))
+        )
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn thread_os_and_secure() {
+        let expr = DiceExpr::try_from("10d6").unwrap();
+
+        for total in [
+            expr.roll_with_roller(&mut EntropyRoller::thread())
+                .unwrap()
+                .total,
+            expr.roll_with_roller(&mut EntropyRoller::os())
+                .unwrap()
+                .total,
+            SecureRoller::new().roll(&expr).unwrap().total,
+        ] {
+            assert!((10..=60).contains(&total))
+        }
+    }
+}
@@ -1,28 +1,99 @@
-use cfg_if::cfg_if;
-use lazy_static::lazy_static;
+pub use crate::ast::{Ast, Function};
+pub use crate::compare::Comparison;
+pub use crate::dialect::Dialect;
+use crate::distribution::DiceDistribution;
+pub use crate::parser::ParseOptions;
+use crate::ratio::Ratio;
+use crate::roller::Roller;
+use alloc::boxed::Box;
+use alloc::collections::BTreeMap;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::cmp::{Ordering, Reverse};
+use core::convert::TryFrom;
+use core::error::Error;
+use core::fmt::{self, Display, Formatter};
+use core::hash::{Hash, Hasher};
+use core::iter;
+use core::num::ParseIntError;
+use core::ops::{self, Range, RangeInclusive};
+#[cfg(feature = "std")]
 use rand::thread_rng;
-use regex::Regex;
-use std::convert::TryFrom;
-use std::error::Error;
-use std::fmt::{self, Display, Formatter};
-use std::num::ParseIntError;
+use rand::Rng;
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+#[cfg(feature = "std")]
+use std::time::SystemTime;
 
-cfg_if! {
-    if #[cfg(test)] {
-        use crate::die::MockDie as Die;
-    } else {
-        use crate::die::Die;
-    }
-}
+/// The values of an expression's variables, by name. Without the `std`
+/// feature they can't be set, and are only ever empty.
+#[cfg(feature = "std")]
+pub(crate) type Vars = HashMap<String, i64>;
+#[cfg(not(feature = "std"))]
+pub(crate) type Vars = BTreeMap<String, i64>;
 
-#[derive(Debug, PartialEq)]
+/// The ways parsing or rolling an expression can fail. More are added as
+/// the parser learns new constructs, so matches on it need a wildcard arm.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
 pub enum DiceExprError {
     Expr(String),
     ParseIntError(ParseIntError),
     Drop(String),
+    Keep(String),
+    Comparison(String),
+    Variable(String),
+    Limit(String),
+    Syntax(SyntaxError),
+    Multiple(Vec<DiceExprError>),
+    Unsupported(String),
+    Overflow(String),
+    /// A dice term rolling no dice, or dice with no sides, such as `0d6` or
+    /// `1d0`.
+    EmptyDice(String),
+}
+
+impl Error for DiceExprError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::ParseIntError(e) => Some(e),
+            Self::Syntax(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl DiceExprError {
+    /// The byte range of the offending text in the expression, for errors
+    /// that point at it.
+    pub fn span(&self) -> Option<Range<usize>> {
+        match self {
+            Self::Syntax(e) => Some(e.span()),
+            _ => None,
+        }
+    }
 }
 
-impl Error for DiceExprError {}
+impl From<Vec<DiceExprError>> for DiceExprError {
+    /// Combines several errors, flattening any that are themselves combined,
+    /// into one unless there's only the one.
+    fn from(errors: Vec<DiceExprError>) -> Self {
+        let mut errors: Vec<DiceExprError> = errors
+            .into_iter()
+            .flat_map(|e| match e {
+                Self::Multiple(errors) => errors,
+                e => vec![e],
+            })
+            .collect();
+
+        match errors.len() {
+            1 => errors.remove(0),
+            _ => Self::Multiple(errors),
+        }
+    }
+}
 
 impl From<ParseIntError> for DiceExprError {
     fn from(e: ParseIntError) -> Self {
@@ -42,14 +113,72 @@ impl Display for DiceExprError {
             Self::Expr(s) => write!(f, "Invalid dice expression \"{}\"", s),
             Self::ParseIntError(e) => write!(f, "Integer parsing error: {}", e),
             Self::Drop(s) => write!(f, "Invalid drop modifier \"{}\"", s),
+            Self::Keep(s) => write!(f, "Invalid keep modifier \"{}\"", s),
+            Self::Comparison(s) => write!(f, "Invalid comparison \"{}\"", s),
+            Self::Variable(s) => write!(f, "Unknown variable \"{}\"", s),
+            Self::Limit(s) => write!(f, "Dice expression \"{}\" is too large", s),
+            Self::Syntax(e) => write!(f, "{}", e),
+            Self::Multiple(errors) => {
+                let errors: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+                write!(f, "{}", errors.join("\n"))
+            }
+            Self::Unsupported(s) => write!(f, "Unsupported construct: {}", s),
+            Self::Overflow(s) => write!(f, "Dice expression \"{}\" can overflow its total", s),
+            Self::EmptyDice(s) => write!(f, "Dice term \"{}\" rolls no dice or has no sides", s),
+        }
+    }
+}
+
+/// Where in an expression parsing failed: the byte offset and length of the
+/// offending text, and what was expected there if anything in particular.
+#[derive(Debug, PartialEq, Eq, Clone)]
+#[non_exhaustive]
+pub struct SyntaxError {
+    pub expr: String,
+    pub offset: usize,
+    pub len: usize,
+    pub expected: Option<&'static str>,
+}
+
+/// Displays the expression with a caret under the offending text, as in
+///
+/// ```text
+/// Invalid dice expression "2d6 + (1d4"
+///   2d6 + (1d4
+///             ^ expected ")"
+/// ```
+impl Display for SyntaxError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let indent = self.expr[..self.offset].chars().count();
+        let width = self.expr[self.offset..self.offset + self.len]
+            .chars()
+            .count();
+
+        writeln!(f, "Invalid dice expression \"{}\"", self.expr)?;
+        writeln!(f, "  {}", self.expr)?;
+        write!(f, "  {}{}", " ".repeat(indent), "^".repeat(width.max(1)))?;
+
+        match self.expected {
+            Some(expected) => write!(f, " expected {}", expected),
+            None => Ok(()),
         }
     }
 }
 
-#[derive(Debug, PartialEq)]
-enum Drop {
-    High,
-    Low,
+impl Error for SyntaxError {}
+
+impl SyntaxError {
+    /// The byte range of the offending text in `expr`.
+    pub fn span(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+}
+
+/// Drops the highest or lowest N dice of a pool, e.g. `4d6-L` or `6d6dl2`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Drop {
+    High(u32),
+    Low(u32),
     None,
 }
 
@@ -57,9 +186,9 @@ impl TryFrom<&str> for Drop {
     type Error = DiceExprError;
 
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        match s.to_lowercase().as_str() {
-            "h" => Ok(Drop::High),
-            "l" => Ok(Drop::Low),
+        match split_selection(s)? {
+            (Some('h'), n) => Ok(Drop::High(n)),
+            (Some('l'), n) => Ok(Drop::Low(n)),
             _ => Err(Self::Error::Drop(s.to_string())),
         }
     }
@@ -67,15 +196,77 @@ impl TryFrom<&str> for Drop {
 
 impl Display for Drop {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
-        write!(
-            f,
-            "{}",
-            match self {
-                Drop::High => "-H",
-                Drop::Low => "-L",
-                Drop::None => "",
-            }
-        )
+        match self {
+            Drop::High(1) => write!(f, "-H"),
+            Drop::Low(1) => write!(f, "-L"),
+            Drop::High(n) => write!(f, "-H{}", n),
+            Drop::Low(n) => write!(f, "-L{}", n),
+            Drop::None => Ok(()),
+        }
+    }
+}
+
+/// Keeps only the highest, lowest, or middle N dice of a pool, e.g. `4d6kh3`
+/// or `3d20km1`. When the middle dice can't be centred exactly, the extra
+/// die is dropped from the top.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Keep {
+    High(u32),
+    Low(u32),
+    Middle(u32),
+    None,
+}
+
+impl TryFrom<&str> for Keep {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match split_selection(s)? {
+            (Some('h'), n) => Ok(Keep::High(n)),
+            (Some('l'), n) => Ok(Keep::Low(n)),
+            (Some('m'), n) => Ok(Keep::Middle(n)),
+            _ => Err(Self::Error::Keep(s.to_string())),
+        }
+    }
+}
+
+impl Display for Keep {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Keep::High(n) => write!(f, "kh{}", n),
+            Keep::Low(n) => write!(f, "kl{}", n),
+            Keep::Middle(n) => write!(f, "km{}", n),
+            Keep::None => Ok(()),
+        }
+    }
+}
+
+/// Splits a drop/keep selection such as `h3` or `L` into its lowercased
+/// direction and the number of dice it selects, which defaults to one.
+fn split_selection(s: &str) -> Result<(Option<char>, u32), ParseIntError> {
+    let mut chars = s.chars();
+    let direction = chars.next().map(|c| c.to_ascii_lowercase());
+    let n = match chars.as_str() {
+        "" => 1,
+        n => n.parse()?,
+    };
+
+    Ok((direction, n))
+}
+
+/// The ranks, counting up from the lowest, of the values kept from `len`
+/// sorted values by a keep or drop modifier.
+pub(crate) fn kept_ranks(keep: &Keep, drop: &Drop, len: usize) -> Range<usize> {
+    match (keep, drop) {
+        (Keep::High(n), _) => len - *n as usize..len,
+        (Keep::Low(n), _) => 0..*n as usize,
+        (Keep::Middle(n), _) => {
+            let low = (len - *n as usize) / 2;
+            low..low + *n as usize
+        }
+        (_, Drop::High(n)) => 0..len - *n as usize,
+        (_, Drop::Low(n)) => *n as usize..len,
+        (Keep::None, Drop::None) => 0..len,
     }
 }
 
@@ -85,12 +276,17 @@ mod drop {
 
     #[test]
     fn try_from_str_uppercase() {
-        assert_eq!(Ok(Drop::High), Drop::try_from("H"))
+        assert_eq!(Ok(Drop::High(1)), Drop::try_from("H"))
     }
 
     #[test]
     fn try_from_str_lowercase() {
-        assert_eq!(Ok(Drop::Low), Drop::try_from("l"))
+        assert_eq!(Ok(Drop::Low(1)), Drop::try_from("l"))
+    }
+
+    #[test]
+    fn try_from_str_count() {
+        assert_eq!(Ok(Drop::Low(2)), Drop::try_from("L2"))
     }
 
     #[test]
@@ -102,209 +298,4620 @@ mod drop {
     }
 }
 
-#[derive(Debug, PartialEq)]
-pub struct DiceExpr {
-    count: u16,
-    sides: u16,
-    modifier: i16,
-    drop: Drop,
+/// The kind of die being rolled: a numbered die such as the `6` in `3d6`, a
+/// Fate/Fudge die (`dF`) whose faces are -1, 0, and +1, a pair of percentile
+/// dice (`d%`) read as tens and ones, or a set of dice read positionally as
+/// digits, such as the two d6 of a `d66`, or a die with a custom list of
+/// faces, such as `d[0,0,1,1,2]`. A coin (`dC`) counts heads as 1 and tails
+/// as 0, so a term of coins totals its heads.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub enum Sides {
+    Number(u32),
+    Fate,
+    Coin,
+    Percentile,
+    Positional { faces: u32, digits: u8 },
+    Faces(Vec<i64>),
+}
+
+impl Sides {
+    /// The lowest and highest values the die can roll.
+    pub(crate) fn range(&self) -> RangeInclusive<i64> {
+        match self {
+            Sides::Number(n) => 1..=*n as i64,
+            Sides::Fate => -1..=1,
+            Sides::Coin => 0..=1,
+            Sides::Percentile => 1..=100,
+            Sides::Positional { faces, digits } => {
+                let repeat = |d: u32| (0..*digits).fold(0, |n, _| n * 10 + d as i64);
+                repeat(1)..=repeat(*faces)
+            }
+            Sides::Faces(faces) => *faces.iter().min().unwrap()..=*faces.iter().max().unwrap(),
+        }
+    }
+
+    /// How many faces each of the physical dice rolled for the die has.
+    pub(crate) fn count(&self) -> u32 {
+        match self {
+            Sides::Number(n) => *n,
+            Sides::Fate => 3,
+            Sides::Coin => 2,
+            Sides::Percentile => 10,
+            Sides::Positional { faces, .. } => *faces,
+            Sides::Faces(faces) => faces.len() as u32,
+        }
+    }
+
+    /// Every value the die can roll, from lowest to highest.
+    fn values(&self) -> impl Iterator<Item = i64> + '_ {
+        self.range().filter(move |&v| match self {
+            Sides::Positional { faces, .. } => v
+                .to_string()
+                .bytes()
+                .all(|b| b > b'0' && b - b'0' <= *faces as u8),
+            Sides::Faces(faces) => faces.contains(&v),
+            _ => true,
+        })
+    }
+
+    fn face(&self) -> Face {
+        match self {
+            Sides::Number(_) => Face::Number,
+            Sides::Fate => Face::Fate,
+            Sides::Coin => Face::Coin,
+            Sides::Percentile => Face::Percentile,
+            Sides::Positional { .. } | Sides::Faces(_) => Face::Number,
+        }
+    }
+
+    /// Rolls the die, using `roll_die` to roll each physical die by its
+    /// number of faces.
+    fn roll<F: FnMut(u32) -> u32>(&self, roll_die: &mut F) -> i64 {
+        match self {
+            Sides::Number(n) => roll_die(*n) as i64,
+            Sides::Fate => roll_die(3) as i64 - 2,
+            Sides::Coin => roll_die(2) as i64 - 1,
+            Sides::Percentile => match (roll_die(10) - 1) * 10 + roll_die(10) - 1 {
+                0 => 100,
+                n => n as i64,
+            },
+            Sides::Positional { faces, digits } => {
+                (0..*digits).fold(0, |n, _| n * 10 + roll_die(*faces) as i64)
+            }
+            Sides::Faces(faces) => faces[roll_die(faces.len() as u32) as usize - 1],
+        }
+    }
 }
 
-impl TryFrom<&str> for DiceExpr {
+impl TryFrom<&str> for Sides {
     type Error = DiceExprError;
 
+    /// Parses the sides of a die. Two to four repetitions of the same digit
+    /// from 2 to 9, such as `66` or `888`, are read as positional dice, and
+    /// a bracketed list such as `[1,3,5]`, or AnyDice's braced `{1,3,5}`, as
+    /// the die's faces.
     fn try_from(s: &str) -> Result<Self, Self::Error> {
-        lazy_static! {
-            static ref RE: Regex = Regex::new(r"^(\d+)?d(\d+)([+-]\d+)?(?:-([LlHh]))?$").unwrap();
+        match s {
+            "F" | "f" => Ok(Sides::Fate),
+            "C" | "c" => Ok(Sides::Coin),
+            "%" => Ok(Sides::Percentile),
+            s if (s.starts_with('[') && s.ends_with(']'))
+                || (s.starts_with('{') && s.ends_with('}')) =>
+            {
+                Ok(Sides::Faces(
+                    s[1..s.len() - 1]
+                        .split(',')
+                        .map(|n| n.parse())
+                        .collect::<Result<_, _>>()?,
+                ))
+            }
+            s if (2..=4).contains(&s.len())
+                && s.bytes().all(|b| b == s.as_bytes()[0])
+                && (b'2'..=b'9').contains(&s.as_bytes()[0]) =>
+            {
+                Ok(Sides::Positional {
+                    faces: (s.as_bytes()[0] - b'0') as u32,
+                    digits: s.len() as u8,
+                })
+            }
+            s => Ok(Sides::Number(s.parse()?)),
+        }
+    }
+}
+
+impl Display for Sides {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Sides::Number(n) => write!(f, "{}", n),
+            Sides::Fate => write!(f, "F"),
+            Sides::Coin => write!(f, "C"),
+            Sides::Percentile => write!(f, "%"),
+            Sides::Positional { faces, digits } => {
+                write!(f, "{}", faces.to_string().repeat(*digits as usize))
+            }
+            Sides::Faces(faces) => {
+                let faces: Vec<String> = faces.iter().map(|n| n.to_string()).collect();
+                write!(f, "[{}]", faces.join(","))
+            }
         }
+    }
+}
 
-        let expr = s.to_string();
+/// How the value of a rolled die is displayed: as a plain number, as a Fate
+/// die's `+`, `-`, or `0`, as a coin's `H` or `T`, or as a number followed
+/// by the tens and ones dice that make up a percentile roll.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub enum Face {
+    Number,
+    Fate,
+    Coin,
+    Percentile,
+}
 
-        if let Some(caps) = RE.captures(s) {
-            let count: u16 = match caps.get(1) {
-                Some(c) => c.as_str().parse()?,
-                None => 1,
-            };
+/// Sorts the dice of a term in its results, either ascending (`4d6s` or
+/// `4d6sa`) or descending (`4d6sd`).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum Sort {
+    Ascending,
+    Descending,
+}
 
-            let sides: u16 = match caps.get(2) {
-                Some(c) => c.as_str().parse()?,
-                None => return Err(Self::Error::from(expr)),
-            };
+impl Display for Sort {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Sort::Ascending => write!(f, "s"),
+            Sort::Descending => write!(f, "sd"),
+        }
+    }
+}
 
-            let modifier: i16 = match caps.get(3) {
-                Some(c) => match c.as_str().parse::<i16>() {
-                    Ok(n) if -n < (count * sides) as i16 => n,
-                    Ok(_) => return Err(Self::Error::from(expr)),
-                    Err(e) => return Err(Self::Error::from(e)),
-                },
-                None => 0,
-            };
+/// Rerolls dice matching a condition, either until they no longer match
+/// (`2d6r1`) or exactly once, keeping the second result (`2d6ro<=2`).
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Reroll {
+    pub(crate) condition: Comparison,
+    pub(crate) once: bool,
+}
 
-            let drop = match caps.get(4) {
-                Some(s) => match count {
-                    1 => return Err(Self::Error::from(expr)),
-                    _ => Drop::try_from(s.as_str())?,
-                },
-                None => Drop::None,
-            };
+impl Reroll {
+    pub fn condition(&self) -> &Comparison {
+        &self.condition
+    }
 
-            Ok(DiceExpr {
-                count,
-                sides,
-                modifier,
-                drop,
-            })
-        } else {
-            Err(Self::Error::from(expr))
-        }
+    /// Whether a die is only rerolled once, rather than until it no longer
+    /// matches.
+    pub fn once(&self) -> bool {
+        self.once
     }
 }
 
-impl fmt::Display for DiceExpr {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl Display for Reroll {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         write!(
             f,
-            "{}d{}{}{}",
-            match self.count {
-                1 => String::from(""),
-                n => format!("{}", n),
-            },
-            self.sides,
-            match self.modifier {
-                n if n > 0 => format!("+{}", n),
-                n if n < 0 => format!("{}", n),
-                _ => String::from(""),
-            },
-            self.drop
+            "r{}{:#}",
+            if self.once { "o" } else { "" },
+            self.condition
         )
     }
 }
 
-impl DiceExpr {
-    pub fn roll(&self) -> (u16, Vec<u16>) {
-        let mut rng = thread_rng();
-        let rolls: Vec<u16> = (0..self.count)
-            .map(|_| Die::new(self.sides).roll(&mut rng))
-            .collect();
+/// A single dice term of an expression, such as `4d6kh3` or `8d10>=8f1`,
+/// optionally labelled like `2d6[fire]`. Successes on faces matching a
+/// double modifier, such as the `t10` of `10d10>=7t10`, count twice. A
+/// modifier written as `4d6+1each` or `4d6++1` is added to every die rather
+/// than to the total.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct Dice {
+    pub(crate) count: u32,
+    pub(crate) sides: Sides,
+    pub(crate) explode: Option<Comparison>,
+    pub(crate) open_ended: bool,
+    pub(crate) reroll: Option<Reroll>,
+    pub(crate) min: Option<i64>,
+    pub(crate) max: Option<i64>,
+    pub(crate) keep: Keep,
+    pub(crate) drop: Drop,
+    pub(crate) success: Option<Comparison>,
+    pub(crate) double: Option<Comparison>,
+    pub(crate) failure: Option<Comparison>,
+    pub(crate) critical_success: Option<Comparison>,
+    pub(crate) critical_failure: Option<Comparison>,
+    pub(crate) sets: bool,
+    pub(crate) sort: Option<Sort>,
+    pub(crate) each: Option<i64>,
+    pub(crate) label: Option<String>,
+}
 
-        let sum: u16 = rolls.iter().sum::<u16>()
-            - match self.drop {
-                Drop::High => rolls.iter().max().unwrap(),
-                Drop::Low => rolls.iter().min().unwrap(),
-                Drop::None => &0,
-            };
+/// A dice expression made up of one or more dice terms and constants, such
+/// as `4d6-L` or `2d6+1d4+3`. Division is exact until the total is rounded
+/// down, unless the `floor`, `ceil` or `round` functions are used to round
+/// it sooner.
+/// Prefixing a parenthesized expression with a count, as in `3x(2d6+3)`,
+/// rolls it that many times.
+/// Totals that only subtract constants are kept from going below zero,
+/// unless the expression is made `signed`.
+/// Following an expression with a difficulty class, as in `d20+7 dc15` or
+/// `d20+7 vs 15`, checks each total against it.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct DiceExpr {
+    pub(crate) repeat: u32,
+    pub(crate) signed: bool,
+    pub(crate) ast: Ast,
+    pub(crate) dc: Option<i64>,
+}
 
-        (
-            if -self.modifier < sum as i16 {
-                (sum as i16 + self.modifier) as u16
-            } else {
-                0
-            },
-            rolls,
-        )
+/// Space for `DiceExpr::roll_into` to roll dice into, reused from one roll
+/// to the next.
+#[derive(Debug, Clone, Default)]
+pub struct RollBuffer {
+    pub(crate) rolls: Vec<DieRoll>,
+    pub(crate) order: Vec<usize>,
+    pub(crate) totals: Vec<Ratio>,
+}
+
+impl RollBuffer {
+    pub fn new() -> Self {
+        RollBuffer::default()
+    }
+
+    /// Each die of the last roll, in the order they were rolled, including
+    /// any rerolled or exploded and whatever the dice terms' sorting.
+    pub fn rolls(&self) -> &[DieRoll] {
+        &self.rolls
     }
 }
 
-#[cfg(test)]
-mod dice_expr {
-    use super::*;
+/// The outcome of rolling a `DiceExpr`: the expression rolled, its total,
+/// the outcome of each of its dice terms, the whole constants added to them
+/// as its modifier, the check against its difficulty class if it has one,
+/// and, with the `std` feature, when it was rolled.
+#[derive(Debug, Clone)]
+pub struct RollResult {
+    pub expr: String,
+    pub total: i64,
+    pub dice: Vec<DiceRoll>,
+    pub modifier: i64,
+    pub dc: Option<DcCheck>,
+    #[cfg(feature = "std")]
+    pub timestamp: SystemTime,
+}
 
-    #[test]
-    fn try_from_str() {
-        let expr = "4d4";
+/// Results are equal if they rolled the same, whenever they were rolled.
+impl PartialEq for RollResult {
+    fn eq(&self, other: &Self) -> bool {
+        self.expr == other.expr
+            && self.total == other.total
+            && self.dice == other.dice
+            && self.modifier == other.modifier
+            && self.dc == other.dc
+    }
+}
 
-        assert_eq!(
-            Ok(DiceExpr {
-                count: 4,
-                sides: 4,
-                modifier: 0,
-                drop: Drop::None,
-            }),
-            DiceExpr::try_from(expr)
-        )
+impl Eq for RollResult {}
+
+/// Hashes the same parts that are compared, so that equal results hash
+/// alike whenever they were rolled.
+impl Hash for RollResult {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.expr.hash(state);
+        self.total.hash(state);
+        self.dice.hash(state);
+        self.modifier.hash(state);
+        self.dc.hash(state);
     }
+}
 
-    #[test]
-    fn try_from_str_modifier() {
-        let expr = "4d4+1";
+/// Serializes the result as an object with the expression rolled, its
+/// total and modifier, its dice, its difficulty class check, and, with the
+/// `std` feature, its timestamp in milliseconds since the Unix epoch.
+#[cfg(feature = "serde")]
+impl serde::Serialize for RollResult {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
 
-        assert_eq!(
-            Ok(DiceExpr {
-                count: 4,
-                sides: 4,
-                modifier: 1,
-                drop: Drop::None,
-            }),
-            DiceExpr::try_from(expr)
-        )
+        let mut state = serializer.serialize_struct("RollResult", 6)?;
+        state.serialize_field("expression", &self.expr)?;
+        state.serialize_field("total", &self.total)?;
+        state.serialize_field("modifier", &self.modifier)?;
+        state.serialize_field("dice", &self.dice)?;
+        state.serialize_field("dc", &self.dc)?;
+
+        #[cfg(feature = "std")]
+        {
+            let timestamp = self
+                .timestamp
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .map_or(0, |d| d.as_millis() as u64);
+            state.serialize_field("timestamp", &timestamp)?;
+        }
+        state.end()
     }
+}
 
-    #[test]
-    fn try_from_str_modifier_lt_0() {
-        let expr = "4d4-1";
+impl RollResult {
+    pub fn is_critical_success(&self) -> bool {
+        self.dice.iter().any(|d| d.critical_success)
+    }
 
-        assert_eq!(
-            Ok(DiceExpr {
-                count: 4,
-                sides: 4,
-                modifier: -1,
-                drop: Drop::None,
-            }),
-            DiceExpr::try_from(expr)
-        )
+    pub fn is_critical_failure(&self) -> bool {
+        self.dice.iter().any(|d| d.critical_failure)
     }
 
-    #[test]
-    fn try_from_str_modifier_too_negative() {
-        let expr = "4d4-16";
+    /// Checks the total against `skill`, as a roll-under check. Only a roll
+    /// of a single plain `3d6` term, with nothing else adding to or scaling
+    /// its total, can be critical, whatever its difficulty class.
+    pub fn skill_check(&self, skill: i64) -> SkillCheck {
+        let plain = match self.dice.as_slice() {
+            [dice] => dice.expr == "3d6" && dice.total == self.total,
+            _ => false,
+        };
 
-        assert_eq!(
-            Err(DiceExprError::Expr(String::from(expr))),
-            DiceExpr::try_from(expr)
-        )
+        let margin = skill - self.total;
+        let (success, critical) = match self.total {
+            3 | 4 if plain => (true, true),
+            17 | 18 if plain => (false, true),
+            _ => (margin >= 0, false),
+        };
+
+        SkillCheck {
+            skill,
+            margin,
+            success,
+            critical,
+        }
     }
 
-    #[test]
-    fn try_from_str_drop() {
-        let expr = "4d4-H";
+    /// The combined totals of the labelled dice terms, such as the damage of
+    /// each type in `2d6[fire]+1d4[cold]`, in the order each label first
+    /// appears.
+    pub fn label_totals(&self) -> Vec<(&str, i64)> {
+        let mut totals: Vec<(&str, i64)> = Vec::new();
 
-        assert_eq!(
-            Ok(DiceExpr {
-                count: 4,
-                sides: 4,
-                modifier: 0,
-                drop: Drop::High,
-            }),
-            DiceExpr::try_from(expr)
-        )
+        for dice in &self.dice {
+            if let Some(label) = &dice.label {
+                match totals.iter_mut().find(|(l, _)| l == label) {
+                    Some((_, total)) => *total += dice.total,
+                    None => totals.push((label, dice.total)),
+                }
+            }
+        }
+
+        totals
     }
+}
 
-    #[test]
-    fn try_from_str_drop_single_die() {
-        let expr = "d4-H";
+/// Displays the expression rolled and its total, as in `3d6+2: 8`, or with
+/// the alternate flag, `{:#}`, a breakdown of the dice rolled, as in
+/// `3d6+2: [4, ~6~, 2] + 2 = 8`, with dropped dice between tildes. Each dice
+/// term's dice are bracketed separately, after the term itself if there's
+/// more than one, as in `2d6+1d4: 2d6 [3, 5], d4 [2] = 10`.
+impl Display for RollResult {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}: ", self.expr)?;
 
-        assert_eq!(
-            Err(DiceExprError::Expr(String::from(expr))),
-            DiceExpr::try_from(expr)
-        )
+        if f.alternate() {
+            let terms: Vec<String> = self
+                .dice
+                .iter()
+                .map(|dice| {
+                    let rolls: Vec<String> = dice.rolls.iter().map(|r| r.to_string()).collect();
+                    match self.dice.len() {
+                        1 => format!("[{}]", rolls.join(", ")),
+                        _ => format!("{} [{}]", dice.expr, rolls.join(", ")),
+                    }
+                })
+                .collect();
+
+            match (terms.is_empty(), self.modifier) {
+                (true, modifier) => write!(f, "{}", modifier)?,
+                (false, 0) => write!(f, "{}", terms.join(", "))?,
+                (false, modifier) => write!(
+                    f,
+                    "{} {} {}",
+                    terms.join(", "),
+                    if modifier < 0 { "-" } else { "+" },
+                    modifier.unsigned_abs()
+                )?,
+            }
+            write!(f, " = ")?;
+        }
+
+        write!(f, "{}", self.total)
     }
+}
 
-    #[test]
-    fn try_from_str_drop_invalid() {
-        let expr = "4d4-J";
+/// Merges other results into this one, as for an attack routine rolled
+/// one attack at a time: their totals, dice, and modifiers are added to
+/// this one's, and their expressions joined to its own by semicolons. A
+/// merged result has no difficulty class check, and keeps the time of the
+/// first roll.
+impl Extend<RollResult> for RollResult {
+    fn extend<I: IntoIterator<Item = RollResult>>(&mut self, results: I) {
+        for result in results {
+            self.expr = format!("{}; {}", self.expr, result.expr);
+            self.total = self.total.saturating_add(result.total);
+            self.modifier = self.modifier.saturating_add(result.modifier);
+            self.dice.extend(result.dice);
+            self.dc = None;
+        }
+    }
+}
 
-        assert_eq!(
-            Err(DiceExprError::Expr(String::from(expr))),
-            DiceExpr::try_from(expr)
-        )
+/// The combined total of any number of rolls, and how many there were, as
+/// from `results.iter().sum::<RollTotal>()`.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct RollTotal {
+    pub total: i64,
+    pub rolls: usize,
+}
+
+impl<'a> iter::Sum<&'a RollResult> for RollTotal {
+    fn sum<I: Iterator<Item = &'a RollResult>>(results: I) -> Self {
+        results
+            .map(|result| RollTotal {
+                total: result.total,
+                rolls: 1,
+            })
+            .sum()
     }
+}
 
-    #[test]
-    fn try_from_str_invalid() {
-        let expr = "asdf";
+impl iter::Sum<RollResult> for RollTotal {
+    fn sum<I: Iterator<Item = RollResult>>(results: I) -> Self {
+        results
+            .map(|result| RollTotal {
+                total: result.total,
+                rolls: 1,
+            })
+            .sum()
+    }
+}
 
-        assert_eq!(
-            Err(DiceExprError::Expr(String::from(expr))),
+impl iter::Sum for RollTotal {
+    fn sum<I: Iterator<Item = RollTotal>>(totals: I) -> Self {
+        totals.fold(RollTotal::default(), |sum, t| RollTotal {
+            total: sum.total.saturating_add(t.total),
+            rolls: sum.rolls + t.rolls,
+        })
+    }
+}
+
+/// The outcome of a GURPS-style check, rolling under a skill value as with
+/// `3d6` against 12: whether it succeeded and by how much, with a margin
+/// below zero on a failure. Rolling `3d6`, totals of 3 or 4 are always
+/// critical successes, and totals of 17 or 18 always critical failures.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub struct SkillCheck {
+    pub skill: i64,
+    pub margin: i64,
+    pub success: bool,
+    pub critical: bool,
+}
+
+/// The outcome of checking a total against a difficulty class, as with
+/// `d20+7 dc15`: whether it met or beat the DC and by how much, with a margin
+/// below zero on a failure.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DcCheck {
+    pub dc: i64,
+    pub margin: i64,
+    pub success: bool,
+}
+
+/// The outcome of rolling a single dice term of a `DiceExpr`: the term
+/// itself and its label, its total, each die that was rolled, any sets of
+/// matching dice it was asked to find, and whether any kept die landed in
+/// the term's critical success or failure range.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct DiceRoll {
+    pub expr: String,
+    pub label: Option<String>,
+    pub total: i64,
+    pub rolls: Vec<DieRoll>,
+    pub sets: Vec<Set>,
+    pub critical_success: bool,
+    pub critical_failure: bool,
+}
+
+/// Serializes the term's outcome along with the positions of its dropped
+/// and rerolled dice.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DiceRoll {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::SerializeStruct;
+
+        let mut state = serializer.serialize_struct("DiceRoll", 9)?;
+        state.serialize_field("expr", &self.expr)?;
+        state.serialize_field("label", &self.label)?;
+        state.serialize_field("total", &self.total)?;
+        state.serialize_field("rolls", &self.rolls)?;
+        state.serialize_field("dropped", &self.dropped())?;
+        state.serialize_field("rerolled", &self.rerolled())?;
+        state.serialize_field("sets", &self.sets)?;
+        state.serialize_field("critical_success", &self.critical_success)?;
+        state.serialize_field("critical_failure", &self.critical_failure)?;
+        state.end()
+    }
+}
+
+impl DiceRoll {
+    /// The positions in `rolls` of the dice dropped by a keep or drop
+    /// modifier, such as the lowest die of `4d6-L`.
+    pub fn dropped(&self) -> Vec<usize> {
+        self.positions(|r| !r.kept && !r.rerolled)
+    }
+
+    /// The positions in `rolls` of the dice discarded to be rerolled.
+    pub fn rerolled(&self) -> Vec<usize> {
+        self.positions(|r| r.rerolled)
+    }
+
+    fn positions<P: Fn(&DieRoll) -> bool>(&self, predicate: P) -> Vec<usize> {
+        self.rolls
+            .iter()
+            .enumerate()
+            .filter(|(_, r)| predicate(r))
+            .map(|(i, _)| i)
+            .collect()
+    }
+}
+
+/// A set of kept dice that rolled the same value, as found by the matching
+/// sets modifier of One Roll Engine pools like `10d6m`: how many dice
+/// matched (its width) and the value they matched on (its height).
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Set {
+    pub width: u32,
+    pub height: i64,
+}
+
+impl Display for Set {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// A single die rolled as part of a dice term, what it was rolled as if a
+/// `min` or `max` modifier changed its value, whether it counted towards
+/// the total, whether it exploded into the roll that follows it or was
+/// discarded and rerolled, and whether it counted as a success, twice over,
+/// or a failure.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct DieRoll {
+    pub value: i64,
+    pub face: Face,
+    pub clamped_from: Option<i64>,
+    pub kept: bool,
+    pub exploded: bool,
+    pub rerolled: bool,
+    pub success: bool,
+    pub double: bool,
+    pub failure: bool,
+}
+
+impl DieRoll {
+    fn new(value: i64, face: Face) -> Self {
+        DieRoll {
+            value,
+            face,
+            clamped_from: None,
+            kept: true,
+            exploded: false,
+            rerolled: false,
+            success: false,
+            double: false,
+            failure: false,
+        }
+    }
+}
+
+impl Display for DieRoll {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let face = match (self.face, self.value) {
+            (Face::Fate, 1) => String::from("+"),
+            (Face::Fate, -1) => String::from("-"),
+            (Face::Coin, 1) => String::from("H"),
+            (Face::Coin, 0) => String::from("T"),
+            (Face::Percentile, n) => format!("{} ({:02}+{})", n, n / 10 % 10 * 10, n % 10),
+            (_, n) => format!("{}", n),
+        };
+
+        let face = match self.clamped_from {
+            Some(n) => format!("{}->{}", n, face),
+            None => face,
+        };
+
+        let value = match (self.exploded, self.rerolled) {
+            (true, _) => format!("{}!", face),
+            (_, true) => format!("{}r", face),
+            _ => face,
+        };
+
+        let value = match (self.success, self.double) {
+            (true, true) => format!("{}**", value),
+            (true, false) => format!("{}*", value),
+            _ => value,
+        };
+
+        let value = match self.failure {
+            true => format!("{}f", value),
+            false => value,
+        };
+
+        match self.kept {
+            true => write!(f, "{}", value),
+            false => write!(f, "~{}~", value),
+        }
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}d{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}{}",
+            match self.count {
+                1 => String::from(""),
+                n => format!("{}", n),
+            },
+            self.sides,
+            match &self.explode {
+                Some(Comparison::Eq(v)) if v == &[*self.sides.range().end()] => String::from("!"),
+                Some(c) => format!("!{}", c),
+                None => String::from(""),
+            },
+            match self.open_ended {
+                true => "oe",
+                false => "",
+            },
+            match &self.reroll {
+                Some(r) => r.to_string(),
+                None => String::from(""),
+            },
+            match self.min {
+                Some(n) => format!("min{}", n),
+                None => String::from(""),
+            },
+            match self.max {
+                Some(n) => format!("max{}", n),
+                None => String::from(""),
+            },
+            self.keep,
+            match &self.success {
+                Some(c) => c.to_string(),
+                None => String::from(""),
+            },
+            match &self.double {
+                Some(c) => format!("t{:#}", c),
+                None => String::from(""),
+            },
+            match &self.failure {
+                Some(c) => format!("f{:#}", c),
+                None => String::from(""),
+            },
+            match &self.critical_success {
+                Some(c) => format!("cs{:#}", c),
+                None => String::from(""),
+            },
+            match &self.critical_failure {
+                Some(c) => format!("cf{:#}", c),
+                None => String::from(""),
+            },
+            self.drop,
+            match self.sets {
+                true => "m",
+                false => "",
+            },
+            match &self.sort {
+                Some(s) => s.to_string(),
+                None => String::from(""),
+            },
+            match self.each {
+                Some(n) => format!("{:+}each", n),
+                None => String::from(""),
+            },
+            match &self.label {
+                Some(l) => format!("[{}]", l),
+                None => String::from(""),
+            }
+        )
+    }
+}
+
+impl Dice {
+    /// Moves the dice one step along the dice chain, to the next larger die
+    /// if `up` or the next smaller one otherwise, stopping at either end.
+    /// Dice exploding on their highest face still do after the step. Dice
+    /// not on the chain, such as a d9 or Fate dice, are left as they are,
+    /// as are dice that would reroll or explode forever after the step,
+    /// such as a `d4r<4` stepping down to a d3.
+    pub(crate) fn step(&mut self, up: bool) {
+        let Sides::Number(sides) = self.sides else {
+            return;
+        };
+        let Some(i) = DICE_CHAIN.iter().position(|&n| n == sides) else {
+            return;
+        };
+
+        let stepped = match up {
+            true => DICE_CHAIN[(i + 1).min(DICE_CHAIN.len() - 1)],
+            false => DICE_CHAIN[i.saturating_sub(1)],
+        };
+
+        let explode = self.explode.clone();
+        if self.explode == Some(Comparison::Eq(vec![sides as i64])) {
+            self.explode = Some(Comparison::Eq(vec![stepped as i64]));
+        }
+        self.sides = Sides::Number(stepped);
+
+        if self.repeats_forever() {
+            self.explode = explode;
+            self.sides = Sides::Number(sides);
+        }
+    }
+
+    /// Creates a term of `count` dice with no modifiers.
+    pub fn new(count: u32, sides: Sides) -> Self {
+        Dice {
+            count,
+            sides,
+            explode: None,
+            open_ended: false,
+            reroll: None,
+            min: None,
+            max: None,
+            keep: Keep::None,
+            drop: Drop::None,
+            success: None,
+            double: None,
+            failure: None,
+            critical_success: None,
+            critical_failure: None,
+            sets: false,
+            sort: None,
+            each: None,
+            label: None,
+        }
+    }
+
+    pub fn count(&self) -> u32 {
+        self.count
+    }
+
+    pub fn sides(&self) -> &Sides {
+        &self.sides
+    }
+
+    /// The condition for a die to explode, rolling another.
+    pub fn explode(&self) -> Option<&Comparison> {
+        self.explode.as_ref()
+    }
+
+    pub fn open_ended(&self) -> bool {
+        self.open_ended
+    }
+
+    pub fn reroll(&self) -> Option<&Reroll> {
+        self.reroll.as_ref()
+    }
+
+    /// The lowest value each die counts as.
+    pub fn min(&self) -> Option<i64> {
+        self.min
+    }
+
+    /// The highest value each die counts as.
+    pub fn max(&self) -> Option<i64> {
+        self.max
+    }
+
+    pub fn keep(&self) -> &Keep {
+        &self.keep
+    }
+
+    pub fn drop(&self) -> &Drop {
+        &self.drop
+    }
+
+    /// The condition for a die to count as a success, which makes the term
+    /// total its successes rather than its dice.
+    pub fn success(&self) -> Option<&Comparison> {
+        self.success.as_ref()
+    }
+
+    /// The condition for a success to count twice.
+    pub fn double(&self) -> Option<&Comparison> {
+        self.double.as_ref()
+    }
+
+    /// The condition for a die to count as a failure, cancelling a success.
+    pub fn failure(&self) -> Option<&Comparison> {
+        self.failure.as_ref()
+    }
+
+    pub fn critical_success(&self) -> Option<&Comparison> {
+        self.critical_success.as_ref()
+    }
+
+    pub fn critical_failure(&self) -> Option<&Comparison> {
+        self.critical_failure.as_ref()
+    }
+
+    /// Whether the term finds sets of matching dice.
+    pub fn sets(&self) -> bool {
+        self.sets
+    }
+
+    pub fn sort(&self) -> Option<&Sort> {
+        self.sort.as_ref()
+    }
+
+    /// The modifier added to each die.
+    pub fn each(&self) -> Option<i64> {
+        self.each
+    }
+
+    pub fn label(&self) -> Option<&str> {
+        self.label.as_deref()
+    }
+
+    /// How many dice count towards the total, before any explode.
+    fn kept(&self) -> i64 {
+        let count = self.count as i64;
+        match (&self.keep, &self.drop) {
+            (Keep::High(n) | Keep::Low(n) | Keep::Middle(n), _) => *n as i64,
+            (_, Drop::High(n) | Drop::Low(n)) => count - *n as i64,
+            (Keep::None, Drop::None) => count,
+        }
+    }
+
+    /// The lowest and highest totals the term can roll.
+    pub(crate) fn range(&self) -> RangeInclusive<i64> {
+        let kept = self.kept();
+
+        let successes = match &self.double {
+            Some(_) => kept * 2,
+            None => kept,
+        };
+
+        let (start, end) = match (&self.success, &self.failure) {
+            (Some(_), Some(_)) => (-kept, successes),
+            (Some(_), None) => (0, successes),
+            (None, _) => (
+                kept.saturating_mul(*self.face_range().start()),
+                kept.saturating_mul(*self.face_range().end()),
+            ),
+        };
+
+        // Explosions add dice to the pool, so unless a keep modifier fixes
+        // how many of them count, there's no limit to the total.
+        match (&self.explode, &self.keep) {
+            _ if self.open_ended => i64::MIN..=i64::MAX,
+            (Some(_), Keep::None) if start < 0 => i64::MIN..=i64::MAX,
+            (Some(_), Keep::None) => start..=i64::MAX,
+            _ => start..=end,
+        }
+    }
+
+    /// The lowest and highest values a single die can count as, after any
+    /// `min` or `max` modifier and any modifier added to each die.
+    fn face_range(&self) -> RangeInclusive<i64> {
+        let range = self.sides.range();
+        let each = self.each.unwrap_or(0);
+        self.clamp(*range.start()).saturating_add(each)
+            ..=self.clamp(*range.end()).saturating_add(each)
+    }
+
+    /// The lowest and highest totals the term can roll without any dice
+    /// exploding, or `None` if they can't be held in a total. Dice that
+    /// explode add to the total one die at a time, so it saturates rather
+    /// than overflowing if they keep on going.
+    pub(crate) fn checked_range(&self) -> Option<RangeInclusive<i64>> {
+        let range = self.sides.range();
+        let each = self.each.unwrap_or(0);
+        let start = self.clamp(*range.start()).checked_add(each)?;
+        let end = self.clamp(*range.end()).checked_add(each)?;
+
+        let kept = self.kept();
+        match &self.success {
+            Some(_) => Some(-kept..=kept * 2),
+            None => Some(kept.checked_mul(start)?..=kept.checked_mul(end)?),
+        }
+    }
+
+    /// Whether a die could never stop being rolled, because every value it
+    /// can roll is rerolled, or every value it can count as once it's
+    /// clamped to any `min` or `max` explodes.
+    pub(crate) fn repeats_forever(&self) -> bool {
+        let reroll = self
+            .reroll
+            .as_ref()
+            .filter(|r| !r.once)
+            .map(|r| &r.condition);
+
+        // Lists of faces and positional dice have a handful of values with
+        // gaps between them, where the rest run on from lowest to highest
+        // and are decided from their ends.
+        if let Sides::Faces(_) | Sides::Positional { .. } = self.sides {
+            return reroll.is_some_and(|c| c.matches_all(self.sides.values()))
+                || self.explode.as_ref().is_some_and(|c| {
+                    c.matches_all(
+                        self.sides
+                            .values()
+                            .filter(|&v| !reroll.is_some_and(|r| r.matches(v)))
+                            .map(|v| self.clamp(v)),
+                    )
+                });
+        }
+
+        let range = self.sides.range();
+        if reroll.is_some_and(|c| c.matches_range(range.clone(), &[])) {
+            return true;
+        }
+
+        // The values left once any are rerolled run from `start` to `end`,
+        // other than any of `except` between them.
+        let (mut start, mut end) = range.into_inner();
+        let mut except: &[i64] = &[];
+        match reroll {
+            Some(Comparison::Lt(n)) => start = start.max(*n),
+            Some(Comparison::Le(n)) => start = start.max(n.saturating_add(1)),
+            Some(Comparison::Gt(n)) => end = end.min(*n),
+            Some(Comparison::Ge(n)) => end = end.min(n.saturating_sub(1)),
+            Some(Comparison::Eq(values)) => {
+                while values.contains(&start) {
+                    start += 1;
+                }
+                while values.contains(&end) {
+                    end -= 1;
+                }
+                except = values;
+            }
+            None => {}
+        }
+
+        let values = self.clamp(start)..=self.clamp(end);
+        self.explode
+            .as_ref()
+            .is_some_and(|c| c.matches_range(values, except))
+    }
+
+    fn clamp(&self, value: i64) -> i64 {
+        let value = self.min.map_or(value, |min| value.max(min));
+        self.max.map_or(value, |max| value.min(max))
+    }
+
+    /// Whether the term's total is meant to be able to go below zero, as it
+    /// can for Fate dice, dice with negative faces, open-ended rolls, and
+    /// success counting.
+    pub(crate) fn signed(&self) -> bool {
+        self.clamp(*self.sides.range().start()) < 0 || self.open_ended || self.success.is_some()
+    }
+
+    /// The chance of a single die counting as each value it can, from the
+    /// lowest up, after any reroll, `min` or `max`, and modifier added to
+    /// each die. Dice that can explode have no fixed chances.
+    fn face_probabilities(&self) -> Option<Vec<(i64, f64)>> {
+        if self.explode.is_some() || self.open_ended {
+            return None;
+        }
+
+        let faces: Vec<i64> = match &self.sides {
+            Sides::Faces(faces) => faces.clone(),
+            sides => sides.values().collect(),
+        };
+        let p = 1.0 / faces.len() as f64;
+        let rerolled = |v: i64| self.reroll.as_ref().is_some_and(|r| r.condition.matches(v));
+        let p_rerolled = faces.iter().filter(|&&v| rerolled(v)).count() as f64 * p;
+
+        let mut probabilities = BTreeMap::new();
+        for &v in &faces {
+            // A die rerolled once takes whatever comes up next, but one
+            // rerolled repeatedly only ever lands on the faces that don't
+            // match.
+            let p = match &self.reroll {
+                Some(r) if r.once && rerolled(v) => p_rerolled * p,
+                Some(r) if r.once => p + p_rerolled * p,
+                Some(_) if rerolled(v) => 0.0,
+                Some(_) => p / (1.0 - p_rerolled),
+                None => p,
+            };
+            *probabilities
+                .entry(self.clamp(v) + self.each.unwrap_or(0))
+                .or_insert(0.0) += p;
+        }
+
+        Some(probabilities.into_iter().collect())
+    }
+
+    /// What a kept die counts for towards the total: its value, or with
+    /// success counting, one for a success, two for a double success, and
+    /// minus one for a failure.
+    fn score(&self, value: i64) -> i64 {
+        match &self.success {
+            Some(success) => {
+                let successes = match success.matches(value) {
+                    true if self.double.as_ref().is_some_and(|c| c.matches(value)) => 2,
+                    true => 1,
+                    false => 0,
+                };
+                let failed = self.failure.as_ref().is_some_and(|c| c.matches(value));
+                successes - failed as i64
+            }
+            None => value,
+        }
+    }
+
+    /// The exact chance of each total the term can roll, if its dice can't
+    /// explode.
+    pub(crate) fn distribution(&self) -> Option<DiceDistribution> {
+        let faces = self.face_probabilities()?;
+        let count = self.count as usize;
+
+        if self.keep == Keep::None && self.drop == Drop::None {
+            let die = DiceDistribution::new(faces.iter().map(|&(v, p)| (self.score(v), p)));
+            return Some((0..count).fold(DiceDistribution::constant(0), |sum, _| {
+                sum.combine(&die, i64::saturating_add)
+            }));
+        }
+
+        // Going through the faces from the lowest up, each step lands some
+        // of the dice not yet placed on the face, with the dice ranked from
+        // the lowest. Those whose ranks are kept count towards the total.
+        let kept = kept_ranks(&self.keep, &self.drop, count);
+        let mut placed = BTreeMap::from([((0, 0), 1.0)]);
+
+        for &(v, p) in &faces {
+            let mut next = BTreeMap::new();
+
+            for (&(n, total), &q) in &placed {
+                let remaining = count - n;
+                let mut weight = q;
+
+                for m in 0..=remaining {
+                    if m > 0 {
+                        weight *= p * (remaining - m + 1) as f64 / m as f64;
+                    }
+
+                    let counted = (n + m).min(kept.end).saturating_sub(n.max(kept.start));
+                    let total = total + self.score(v) * counted as i64;
+                    *next.entry((n + m, total)).or_insert(0.0) += weight;
+                }
+            }
+
+            placed = next;
+        }
+
+        Some(DiceDistribution::new(
+            placed
+                .into_iter()
+                .filter(|&((n, _), _)| n == count)
+                .map(|((_, total), p)| (total, p)),
+        ))
+    }
+
+    /// The mean and variance of the term's total, if its dice can't
+    /// explode. The variance of a term keeping or dropping dice is unknown,
+    /// but its mean is found from the chance of each kept die, ranked from
+    /// the lowest, landing on each value.
+    pub(crate) fn moments(&self) -> Option<(f64, Option<f64>)> {
+        let faces = self.face_probabilities()?;
+        let count = self.count as usize;
+
+        if self.keep == Keep::None && self.drop == Drop::None {
+            let mean: f64 = faces.iter().map(|&(v, p)| self.score(v) as f64 * p).sum();
+            let variance: f64 = faces
+                .iter()
+                .map(|&(v, p)| {
+                    let deviation = self.score(v) as f64 - mean;
+                    deviation * deviation * p
+                })
+                .sum();
+
+            return Some((mean * count as f64, Some(variance * count as f64)));
+        }
+
+        // The chance of the die at each rank being no higher than each value
+        // is the chance of at least that many dice being no higher than it.
+        let mut at_most = Vec::with_capacity(faces.len());
+        let mut cumulative = 0.0;
+        for &(_, p) in &faces {
+            cumulative += p;
+
+            let mut binomial = vec![1.0];
+            for _ in 0..count {
+                let mut next = vec![0.0; binomial.len() + 1];
+                for (j, b) in binomial.iter().enumerate() {
+                    next[j] += b * (1.0 - cumulative);
+                    next[j + 1] += b * cumulative;
+                }
+                binomial = next;
+            }
+
+            let mut tail = binomial;
+            for j in (0..count).rev() {
+                tail[j] += tail[j + 1];
+            }
+            at_most.push(tail);
+        }
+
+        let mean = kept_ranks(&self.keep, &self.drop, count)
+            .map(|rank| {
+                let mut below = 0.0;
+                faces
+                    .iter()
+                    .zip(&at_most)
+                    .map(|(&(v, _), tail)| {
+                        let p = tail[rank + 1] - below;
+                        below = tail[rank + 1];
+                        self.score(v) as f64 * p
+                    })
+                    .sum::<f64>()
+            })
+            .sum();
+
+        Some((mean, None))
+    }
+
+    pub(crate) fn roll_with<F: FnMut(u32) -> u32>(&self, roll_die: &mut F) -> DiceRoll {
+        let mut rolls: Vec<DieRoll> = Vec::with_capacity(self.count as usize);
+        let total = self.roll_into(roll_die, &mut rolls, &mut Vec::new());
+
+        match self.sort {
+            Some(Sort::Ascending) => rolls.sort_by_key(|r| r.value),
+            Some(Sort::Descending) => rolls.sort_by_key(|r| Reverse(r.value)),
+            None => {}
+        }
+
+        let critical = |c: &Option<Comparison>| {
+            c.as_ref()
+                .is_some_and(|c| rolls.iter().any(|r| r.kept && c.matches(r.value)))
+        };
+
+        let mut sets = Vec::new();
+        if self.sets {
+            let mut kept: Vec<i64> = rolls.iter().filter(|r| r.kept).map(|r| r.value).collect();
+            kept.sort();
+
+            for dice in kept.chunk_by(|a, b| a == b).filter(|d| d.len() > 1) {
+                sets.push(Set {
+                    width: dice.len() as u32,
+                    height: dice[0],
+                });
+            }
+
+            sets.sort_by_key(|s| Reverse((s.width, s.height)));
+        }
+
+        DiceRoll {
+            expr: self.to_string(),
+            label: self.label.clone(),
+            total,
+            sets,
+            critical_success: critical(&self.critical_success),
+            critical_failure: critical(&self.critical_failure),
+            rolls,
+        }
+    }
+
+    /// Rolls the dice onto the end of `rolls`, in the order they're rolled,
+    /// marking which are kept and which succeed or fail, and returns their
+    /// total. `order` is space for ranking them, so that rolling into
+    /// buffers that have grown to fit allocates nothing.
+    pub(crate) fn roll_into<F: FnMut(u32) -> u32>(
+        &self,
+        roll_die: &mut F,
+        rolls: &mut Vec<DieRoll>,
+        order: &mut Vec<usize>,
+    ) -> i64 {
+        let start = rolls.len();
+
+        for _ in 0..self.count {
+            let mut roll = self.roll_one(roll_die, rolls);
+
+            while self.explode.as_ref().is_some_and(|c| c.matches(roll.value)) {
+                rolls.push(DieRoll {
+                    exploded: true,
+                    ..roll
+                });
+                roll = self.roll_one(roll_die, rolls);
+            }
+
+            // An open-ended roll of 96 or more adds another roll, and one of 5
+            // or less subtracts it, each carrying on as long as the roll
+            // that follows is 96 or more.
+            let sign = match roll.value {
+                v if self.open_ended && v >= 96 => 1,
+                v if self.open_ended && v <= 5 => -1,
+                _ => 0,
+            };
+
+            if sign != 0 {
+                loop {
+                    let next = self.roll_one(roll_die, rolls);
+                    let high = next.value >= 96;
+
+                    rolls.push(DieRoll {
+                        exploded: true,
+                        ..roll
+                    });
+                    roll = DieRoll {
+                        value: sign * next.value,
+                        face: if sign < 0 { Face::Number } else { next.face },
+                        ..next
+                    };
+
+                    if !high {
+                        break;
+                    }
+                }
+            }
+
+            rolls.push(roll);
+        }
+
+        let rolls = &mut rolls[start..];
+
+        // A modifier on each die is added once they've all been rolled, so
+        // it doesn't change which of them explode, but does count towards
+        // which are kept and whether they succeed.
+        if let Some(each) = self.each {
+            for roll in rolls.iter_mut().filter(|r| !r.rerolled) {
+                roll.value = roll.value.saturating_add(each);
+            }
+        }
+
+        order.clear();
+        order.extend((0..rolls.len()).filter(|&i| !rolls[i].rerolled));
+        order.sort_by_key(|&i| rolls[i].value);
+
+        let kept = kept_ranks(&self.keep, &self.drop, order.len());
+
+        for (rank, &i) in order.iter().enumerate() {
+            rolls[i].kept = kept.contains(&rank);
+        }
+
+        match &self.success {
+            Some(success) => {
+                for roll in rolls.iter_mut().filter(|r| r.kept) {
+                    roll.success = success.matches(roll.value);
+                    roll.double =
+                        roll.success && self.double.as_ref().is_some_and(|c| c.matches(roll.value));
+                    roll.failure = self.failure.as_ref().is_some_and(|c| c.matches(roll.value));
+                }
+
+                let successes = rolls.iter().filter(|r| r.success).count() as i64
+                    + rolls.iter().filter(|r| r.double).count() as i64;
+                let failures = rolls.iter().filter(|r| r.failure).count() as i64;
+
+                successes - failures
+            }
+            None => rolls
+                .iter()
+                .filter(|r| r.kept)
+                .fold(0, |sum: i64, r| sum.saturating_add(r.value)),
+        }
+    }
+
+    /// Rolls a single die, rerolling it while it matches the reroll
+    /// condition and recording each discarded value in `rolls`, then clamps
+    /// it to any `min` or `max`.
+    fn roll_one<F: FnMut(u32) -> u32>(
+        &self,
+        roll_die: &mut F,
+        rolls: &mut Vec<DieRoll>,
+    ) -> DieRoll {
+        let face = self.sides.face();
+        let mut value = self.sides.roll(roll_die);
+
+        if let Some(reroll) = &self.reroll {
+            while reroll.condition.matches(value) {
+                rolls.push(DieRoll {
+                    kept: false,
+                    rerolled: true,
+                    ..DieRoll::new(value, face)
+                });
+                value = self.sides.roll(roll_die);
+
+                if reroll.once {
+                    break;
+                }
+            }
+        }
+
+        match self.clamp(value) {
+            clamped if clamped != value => DieRoll {
+                clamped_from: Some(value),
+                ..DieRoll::new(clamped, face)
+            },
+            _ => DieRoll::new(value, face),
+        }
+    }
+}
+
+/// The Dungeon Crawl Classics dice chain, the die sizes that dice step up
+/// and down through, including its odd-sided d3, d5 and d7.
+const DICE_CHAIN: [u32; 13] = [3, 4, 5, 6, 7, 8, 10, 12, 14, 16, 20, 24, 30];
+
+/// An expression rolling a single dice term, such as `Dice::new(3, Sides::Number(6))`
+/// for `3d6`.
+impl From<Dice> for DiceExpr {
+    fn from(dice: Dice) -> Self {
+        DiceExpr {
+            repeat: 1,
+            signed: false,
+            ast: Ast::Dice(Box::new(dice)),
+            dc: None,
+        }
+    }
+}
+
+/// Serializes the expression as the string it displays as, such as
+/// `4d6-L`, so that it can be written in config files and payloads as it
+/// would be typed.
+#[cfg(feature = "serde")]
+impl serde::Serialize for DiceExpr {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes the expression from a string, as it's parsed by
+/// `try_from`.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DiceExpr {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DiceExpr::try_from(s.as_str()).map_err(serde::de::Error::custom)
+    }
+}
+
+/// A single d20.
+impl Default for DiceExpr {
+    fn default() -> Self {
+        DiceExpr::from(Dice::new(1, Sides::Number(20)))
+    }
+}
+
+impl DiceExpr {
+    /// Joins the expression to `rhs` with `op`. The joined expression is
+    /// repeated as many times as either side is, checked against the
+    /// left-hand side's difficulty class or else the right-hand side's, and
+    /// signed if either side is, as in `d20+2d6` and `3x(2d6) dc10` joining
+    /// into `3x(d20+2d6) dc10`.
+    ///
+    /// It isn't checked until it's rolled, which fails as parsing the joined
+    /// expression would if it can't total above zero, unless it's signed, or
+    /// if its total can overflow.
+    fn combine(self, op: fn(Box<Ast>, Box<Ast>) -> Ast, rhs: DiceExpr) -> Self {
+        DiceExpr {
+            repeat: self.repeat.max(rhs.repeat),
+            signed: self.signed || rhs.signed,
+            ast: op(Box::new(self.ast), Box::new(rhs.ast)),
+            dc: self.dc.or(rhs.dc),
+        }
+    }
+
+    /// Joins the expression to a constant with `op`, keeping its repeat
+    /// count, signedness, and difficulty class.
+    fn combine_constant(self, op: fn(Box<Ast>, Box<Ast>) -> Ast, n: i64) -> Self {
+        DiceExpr {
+            ast: op(Box::new(self.ast), Box::new(Ast::Constant(n))),
+            ..self
+        }
+    }
+}
+
+/// Adds the terms of another expression, as in `d(20)? + d(4)?`, joining
+/// their repeat counts and difficulty classes as `combine` does.
+impl ops::Add for DiceExpr {
+    type Output = DiceExpr;
+
+    fn add(self, rhs: DiceExpr) -> Self::Output {
+        self.combine(Ast::Add, rhs)
+    }
+}
+
+/// Adds a constant, as in `d(20)? + 5`, subtracting it if it's below zero.
+impl ops::Add<i64> for DiceExpr {
+    type Output = DiceExpr;
+
+    fn add(self, rhs: i64) -> Self::Output {
+        match rhs < 0 {
+            true => self.combine_constant(Ast::Sub, rhs.saturating_neg()),
+            false => self.combine_constant(Ast::Add, rhs),
+        }
+    }
+}
+
+/// Subtracts the terms of another expression, as in `d(20)? - d(4)?`.
+impl ops::Sub for DiceExpr {
+    type Output = DiceExpr;
+
+    fn sub(self, rhs: DiceExpr) -> Self::Output {
+        self.combine(Ast::Sub, rhs)
+    }
+}
+
+/// Subtracts a constant, as in `d(20)? - 1`, adding it if it's below zero.
+impl ops::Sub<i64> for DiceExpr {
+    type Output = DiceExpr;
+
+    fn sub(self, rhs: i64) -> Self::Output {
+        match rhs < 0 {
+            true => self.combine_constant(Ast::Add, rhs.saturating_neg()),
+            false => self.combine_constant(Ast::Sub, rhs),
+        }
+    }
+}
+
+/// Multiplies the total by a constant, as in `d(6)? * 2`.
+impl ops::Mul<i64> for DiceExpr {
+    type Output = DiceExpr;
+
+    fn mul(self, rhs: i64) -> Self::Output {
+        self.combine_constant(Ast::Mul, rhs)
+    }
+}
+
+/// Builds an expression of a single dice term and a modifier without
+/// writing it out to be parsed, as in
+/// `DiceExpr::builder().count(4).sides(6).drop_lowest(1).modifier(2)` for
+/// `4d6-L+2`. It starts as a single d20 with no modifier.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct DiceExprBuilder {
+    dice: Dice,
+    modifier: i64,
+}
+
+impl DiceExprBuilder {
+    pub fn count(mut self, count: u32) -> Self {
+        self.dice.count = count;
+        self
+    }
+
+    pub fn sides(mut self, sides: u32) -> Self {
+        self.dice.sides = Sides::Number(sides);
+        self
+    }
+
+    pub fn keep_highest(mut self, n: u32) -> Self {
+        self.dice.keep = Keep::High(n);
+        self
+    }
+
+    pub fn keep_lowest(mut self, n: u32) -> Self {
+        self.dice.keep = Keep::Low(n);
+        self
+    }
+
+    pub fn drop_highest(mut self, n: u32) -> Self {
+        self.dice.drop = Drop::High(n);
+        self
+    }
+
+    pub fn drop_lowest(mut self, n: u32) -> Self {
+        self.dice.drop = Drop::Low(n);
+        self
+    }
+
+    /// Sets the whole constant added to the dice, or subtracted from them
+    /// if it's below zero.
+    pub fn modifier(mut self, modifier: i64) -> Self {
+        self.modifier = modifier;
+        self
+    }
+
+    /// Builds the expression, failing as parsing it would if it rolls no
+    /// dice or dice with no sides, if more dice are kept or dropped than
+    /// there are, if dice are both kept and dropped, if it can't total above
+    /// zero, or if its total can overflow.
+    pub fn build(self) -> Result<DiceExpr, DiceExprError> {
+        let dice = self.dice;
+        let count = dice.count;
+
+        if count == 0 || dice.sides == Sides::Number(0) {
+            return Err(DiceExprError::EmptyDice(dice.to_string()));
+        }
+
+        let invalid = match dice.keep {
+            Keep::High(n) | Keep::Low(n) | Keep::Middle(n) => {
+                n == 0 || n > count || dice.drop != Drop::None
+            }
+            Keep::None => false,
+        } || match dice.drop {
+            Drop::High(n) | Drop::Low(n) => n == 0 || n >= count,
+            Drop::None => false,
+        };
+        if invalid {
+            return Err(DiceExprError::from(dice.to_string()));
+        }
+
+        let term = Box::new(Ast::Dice(Box::new(dice)));
+        let ast = match self.modifier {
+            0 => *term,
+            n if n < 0 => Ast::Sub(term, Box::new(Ast::Constant(n.saturating_neg()))),
+            n => Ast::Add(term, Box::new(Ast::Constant(n))),
+        };
+        ast.validate(&ast, false)?;
+
+        Ok(DiceExpr {
+            repeat: 1,
+            signed: false,
+            ast,
+            dc: None,
+        })
+    }
+}
+
+impl DiceExpr {
+    /// Starts building an expression, from a single d20 with no modifier.
+    pub fn builder() -> DiceExprBuilder {
+        DiceExprBuilder {
+            dice: Dice::new(1, Sides::Number(20)),
+            modifier: 0,
+        }
+    }
+}
+
+impl Display for DiceExpr {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self.repeat {
+            1 => write!(f, "{}", self.ast)?,
+            n => write!(f, "{}x({})", n, self.ast)?,
+        }
+
+        match self.dc {
+            Some(dc) => write!(f, " dc{}", dc),
+            None => Ok(()),
+        }
+    }
+}
+
+impl DiceExpr {
+    /// The parsed form of the expression, or of the expression repeated if
+    /// it has a repeat count.
+    pub fn ast(&self) -> &Ast {
+        &self.ast
+    }
+
+    /// How many times the expression is rolled by `roll_repeated`.
+    pub fn repeat(&self) -> u32 {
+        self.repeat
+    }
+
+    pub fn is_signed(&self) -> bool {
+        self.signed
+    }
+
+    /// The difficulty class each total is checked against.
+    pub fn dc(&self) -> Option<i64> {
+        self.dc
+    }
+
+    /// The dice terms of the expression in the order they're written, such
+    /// as the `4d6-L` and `1d4` of `4d6-L+1d4+2`. Terms whose count or sides
+    /// are rolled first, such as `(1d4)d6`, aren't included.
+    pub fn dice(&self) -> Vec<&Dice> {
+        self.ast
+            .terms()
+            .into_iter()
+            .filter_map(|term| match term {
+                Ast::Dice(d) => Some(d.as_ref()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The names of the expression's variables in the order they're
+    /// written, such as the `str` and `prof` of `1d20+$str+$prof`.
+    pub fn variables(&self) -> Vec<&str> {
+        self.ast.variables()
+    }
+
+    /// How many dice the expression rolls across its dice terms, not
+    /// counting any added by explosions or rerolls.
+    pub fn count(&self) -> u32 {
+        self.dice().iter().map(|d| d.count()).sum()
+    }
+
+    /// The whole constants added to or subtracted from the dice, such as the
+    /// 3 of `2d6+4-1`.
+    pub fn modifier(&self) -> i64 {
+        self.ast.modifier()
+    }
+
+    /// The lowest total the expression can roll, or `i64::MIN` if there's
+    /// no limit.
+    pub fn min_value(&self) -> i64 {
+        *self.total_range().start()
+    }
+
+    /// The highest total the expression can roll, or `i64::MAX` if there's
+    /// no limit, as when its dice can explode.
+    pub fn max_value(&self) -> i64 {
+        *self.total_range().end()
+    }
+
+    /// The average total the expression rolls, worked out exactly rather
+    /// than by rolling it, from the expression's `distribution` where it
+    /// can't be worked out term by term, as for totals kept from going
+    /// below zero. It's unknown where both are, as for expressions with
+    /// variables or exploding dice.
+    pub fn mean(&self) -> Option<f64> {
+        match self.moments() {
+            Some((mean, _)) => Some(mean),
+            None => Some(self.distribution()?.mean()),
+        }
+    }
+
+    /// The variance of the expression's total, worked out exactly like
+    /// `mean`, as it also is from the `distribution` of expressions keeping
+    /// or dropping dice.
+    pub fn variance(&self) -> Option<f64> {
+        match self.moments() {
+            Some((_, Some(variance))) => Some(variance),
+            _ => Some(self.distribution()?.variance()),
+        }
+    }
+
+    /// The exact chance of each total the expression can roll, worked out
+    /// without rolling it, as AnyDice would. It's unknown for expressions
+    /// with variables, division, or exploding dice, or groups keeping or
+    /// dropping some of their terms.
+    pub fn distribution(&self) -> Option<DiceDistribution> {
+        let distribution = self.ast.distribution()?;
+        match self.is_clamped() {
+            true => Some(distribution.map(|total| total.max(0))),
+            false => Some(distribution),
+        }
+    }
+
+    /// The exact chance of rolling a total of `n` or more, as when needing
+    /// 15 or more on `1d20+5`, if the expression has a `distribution`.
+    pub fn prob_at_least(&self, n: i64) -> Option<f64> {
+        self.distribution().map(|d| {
+            d.iter()
+                .skip_while(|&(total, _)| total < n)
+                .map(|(_, p)| p)
+                .sum()
+        })
+    }
+
+    /// The exact chance of rolling a total of `n` or less, if the expression
+    /// has a `distribution`.
+    pub fn prob_at_most(&self, n: i64) -> Option<f64> {
+        self.distribution().map(|d| d.cdf(n))
+    }
+
+    /// The exact chance of rolling a higher total than `other`, as when
+    /// asking whether `2d6+3` is better than `1d12+4`, if both expressions
+    /// have a `distribution`.
+    pub fn prob_beats(&self, other: &DiceExpr) -> Option<f64> {
+        let difference = self.difference(other)?;
+        Some(
+            difference
+                .iter()
+                .filter(|&(d, _)| d > 0)
+                .map(|(_, p)| p)
+                .sum(),
+        )
+    }
+
+    /// The exact chance of rolling the same total as `other`, if both
+    /// expressions have a `distribution`.
+    pub fn prob_ties(&self, other: &DiceExpr) -> Option<f64> {
+        Some(self.difference(other)?.pmf(0))
+    }
+
+    /// How much higher the average total is than `other`'s, or how much
+    /// lower if it's below zero.
+    pub fn mean_difference(&self, other: &DiceExpr) -> Option<f64> {
+        Some(self.mean()? - other.mean()?)
+    }
+
+    /// Orders the expression against `other` by their average totals, as for
+    /// sorting weapons by their average damage with
+    /// `weapons.sort_by(DiceExpr::cmp_by_mean)`. Expressions whose average
+    /// is unknown come after all the others, in no particular order.
+    pub fn cmp_by_mean(&self, other: &DiceExpr) -> Ordering {
+        match (self.mean(), other.mean()) {
+            (Some(a), Some(b)) => a.total_cmp(&b),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+
+    /// Sorts expressions from the lowest average total up, keeping any
+    /// with the same average in the order they were in, and putting those
+    /// whose average is unknown last.
+    pub fn sort_by_mean(exprs: &mut [DiceExpr]) {
+        exprs.sort_by(DiceExpr::cmp_by_mean)
+    }
+
+    /// The distribution of this expression's total less `other`'s.
+    fn difference(&self, other: &DiceExpr) -> Option<DiceDistribution> {
+        Some(
+            self.distribution()?
+                .combine(&other.distribution()?, i64::saturating_sub),
+        )
+    }
+
+    fn moments(&self) -> Option<(f64, Option<f64>)> {
+        match self.is_clamped() {
+            true => None,
+            false => self.ast.moments(),
+        }
+    }
+
+    /// The range of the expression's totals, as kept from going below zero.
+    fn total_range(&self) -> RangeInclusive<i64> {
+        let range = self.ast.range();
+        match self.signed || self.ast.signed() {
+            true => range,
+            false => (*range.start()).max(0)..=(*range.end()).max(0),
+        }
+    }
+
+    /// Whether some of the expression's totals are raised to zero.
+    fn is_clamped(&self) -> bool {
+        !(self.signed || self.ast.signed()) && *self.ast.range().start() < 0
+    }
+
+    /// Rewrites the expression into a canonical form, so that expressions
+    /// differing only in the order of their terms or how their constants
+    /// are split up, such as `1d6+2+3` and `5+1d6`, display the same way.
+    pub fn normalize(self) -> Self {
+        DiceExpr {
+            ast: self.ast.normalize(),
+            ..self
+        }
+    }
+
+    /// Rewrites the expression into a simpler one that rolls the same way,
+    /// replacing dice that always roll the same total, such as `1d1`, with
+    /// that total and merging its whole constants, as in `2d6+1d1+3` to
+    /// `2d6+4`, then normalizing it. An expression whose total could go
+    /// below zero still can.
+    pub fn simplify(self) -> Self {
+        let signed = self.signed || self.ast.signed();
+        let ast = self.ast.collapse().normalize();
+
+        DiceExpr {
+            signed: signed && !ast.signed(),
+            ast,
+            ..self
+        }
+    }
+
+    /// Steps each die up the Dungeon Crawl Classics dice chain, from d3 to d4
+    /// to d5 and so on up to d30, as in `2d6+1` to `2d7+1`.
+    pub fn step_up(mut self) -> Self {
+        self.ast.step(true);
+        self
+    }
+
+    /// Steps each die down the Dungeon Crawl Classics dice chain, from d30
+    /// to d24 to d20 and so on down to d3, as in `1d20` to `1d16`.
+    pub fn step_down(mut self) -> Self {
+        self.ast.step(false);
+        self
+    }
+
+    /// Sets whether every total is returned as it is, rather than only
+    /// those of expressions that can go below zero without a constant
+    /// dragging them there, such as `4dF` or `1d6-1d6`.
+    pub fn signed(self, signed: bool) -> Self {
+        DiceExpr { signed, ..self }
+    }
+
+    /// Rolls the expression once, ignoring any repeat count and taking any
+    /// variables as zero. Fails if a variable can't be zero, such as the
+    /// divisor of `1d6/$x` or the sides of `2d($size)`; roll those with
+    /// `roll_with_vars` instead, which checks their values.
+    #[cfg(feature = "std")]
+    pub fn roll(&self) -> Result<RollResult, DiceExprError> {
+        self.roll_with_roller(&mut thread_rng())
+    }
+
+    /// Rolls the expression once like `roll`, drawing from `rng` rather than
+    /// the thread's generator, so that it can be seeded.
+    pub fn roll_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> Result<RollResult, DiceExprError> {
+        self.roll_with_roller(rng)
+    }
+
+    /// Rolls the expression once like `roll`, with each die rolled by
+    /// `roller`, which can be a fake one rolling chosen values.
+    pub fn roll_with_roller<R: Roller + ?Sized>(
+        &self,
+        roller: &mut R,
+    ) -> Result<RollResult, DiceExprError> {
+        self.check_rollable()?;
+        Ok(self.roll_with(|sides| roller.roll_die(sides)))
+    }
+
+    /// Rolls the expression once like `roll_with_roller`, returning only its
+    /// total and keeping its dice in `buffer`. Once the buffer has grown to
+    /// fit the expression's dice, nothing is allocated, for rolling over and
+    /// over in a hot loop.
+    pub fn roll_into<R: Roller + ?Sized>(
+        &self,
+        roller: &mut R,
+        buffer: &mut RollBuffer,
+    ) -> Result<i64, DiceExprError> {
+        self.check_rollable()?;
+        Ok(self.total_into(roller, buffer))
+    }
+
+    /// Rolls the expression once like `roll_into`, once it's been checked
+    /// that it can be rolled.
+    fn total_into<R: Roller + ?Sized>(&self, roller: &mut R, buffer: &mut RollBuffer) -> i64 {
+        buffer.rolls.clear();
+
+        let total = self
+            .ast
+            .eval_into(&mut |sides| roller.roll_die(sides), buffer)
+            .floor();
+        match self.signed || self.ast.signed() {
+            true => total,
+            false => total.max(0),
+        }
+    }
+
+    /// Rolls the expression once like `roll`, failing if its total overflows
+    /// rather than saturating at the largest or smallest total. Expressions
+    /// are checked when they're parsed to be sure they can't overflow
+    /// without exploding dice, but enough explosions still can.
+    #[cfg(feature = "std")]
+    pub fn checked_roll(&self) -> Result<RollResult, DiceExprError> {
+        self.checked_roll_with_roller(&mut thread_rng())
+    }
+
+    /// Rolls the expression once like `checked_roll`, with each die rolled
+    /// by `roller`.
+    pub fn checked_roll_with_roller<R: Roller + ?Sized>(
+        &self,
+        roller: &mut R,
+    ) -> Result<RollResult, DiceExprError> {
+        self.check_rollable()?;
+
+        let mut dice = Vec::new();
+        let value = self
+            .ast
+            .eval(&mut |sides| roller.roll_die(sides), &Vars::new(), &mut dice);
+
+        match value.checked_floor() {
+            Some(total) => Ok(self.result(total, dice)),
+            None => Err(DiceExprError::Overflow(self.to_string())),
+        }
+    }
+
+    /// Rolls the expression over and over like `roll_with_roller`, as an
+    /// endless iterator of results to take from, filter, or stream. Fails
+    /// up front if the expression can't be rolled, as `roll` does.
+    pub fn rolls<'a, R: Roller + 'a>(
+        &'a self,
+        mut roller: R,
+    ) -> Result<impl Iterator<Item = RollResult> + 'a, DiceExprError> {
+        self.check_rollable()?;
+        Ok(iter::repeat_with(move || {
+            self.roll_with(|sides| roller.roll_die(sides))
+        }))
+    }
+
+    /// Rolls the expression `n` times like `roll`. With the `parallel`
+    /// feature, the rolls are shared out across threads, each rolling with
+    /// its own generator.
+    #[cfg(feature = "std")]
+    pub fn roll_n(&self, n: usize) -> Result<Vec<RollResult>, DiceExprError> {
+        #[cfg(feature = "parallel")]
+        {
+            use rayon::prelude::*;
+
+            self.check_rollable()?;
+            Ok((0..n)
+                .into_par_iter()
+                .map_init(thread_rng, |rng, _| {
+                    self.roll_with(|sides| rng.roll_die(sides))
+                })
+                .collect())
+        }
+
+        #[cfg(not(feature = "parallel"))]
+        {
+            Ok(self.rolls(thread_rng())?.take(n).collect())
+        }
+    }
+
+    /// Estimates the chance of rolling each total from `n` rolls, sharing
+    /// them out across threads like `roll_n`, for expressions whose exact
+    /// `distribution` is unknown. `None` if `n` is zero. Only the totals are
+    /// kept, each rolled with `roll_into` into a buffer per thread.
+    #[cfg(feature = "std")]
+    pub fn simulate(&self, n: usize) -> Result<Option<DiceDistribution>, DiceExprError> {
+        self.check_rollable()?;
+
+        let count = |mut counts: BTreeMap<i64, u64>, total| {
+            *counts.entry(total).or_insert(0) += 1;
+            counts
+        };
+
+        #[cfg(feature = "parallel")]
+        let counts = {
+            use rayon::prelude::*;
+
+            (0..n)
+                .into_par_iter()
+                .map_init(
+                    || (thread_rng(), RollBuffer::new()),
+                    |(rng, buffer), _| self.total_into(rng, buffer),
+                )
+                .fold(BTreeMap::new, count)
+                .reduce(BTreeMap::new, |a, b| {
+                    b.into_iter().fold(a, |mut a, (total, n)| {
+                        *a.entry(total).or_insert(0) += n;
+                        a
+                    })
+                })
+        };
+
+        #[cfg(not(feature = "parallel"))]
+        let counts = {
+            let (mut rng, mut buffer) = (thread_rng(), RollBuffer::new());
+            (0..n)
+                .map(|_| self.total_into(&mut rng, &mut buffer))
+                .fold(BTreeMap::new(), count)
+        };
+
+        Ok((n > 0).then(|| {
+            DiceDistribution::new(
+                counts
+                    .into_iter()
+                    .map(|(total, count)| (total, count as f64 / n as f64)),
+            )
+        }))
+    }
+
+    /// Rolls the expression once, with its variables taken from `vars`.
+    #[cfg(feature = "std")]
+    pub fn roll_with_vars(&self, vars: &Vars) -> Result<RollResult, DiceExprError> {
+        self.check_vars(vars)?;
+
+        let mut rng = thread_rng();
+        Ok(self.roll_vars_with(vars, |sides| rng.roll_die(sides)))
+    }
+
+    /// Rolls the expression as many times as its repeat count, returning
+    /// each result separately, and failing as `roll` does.
+    #[cfg(feature = "std")]
+    pub fn roll_repeated(&self) -> Result<Vec<RollResult>, DiceExprError> {
+        self.check_rollable()?;
+
+        let mut rng = thread_rng();
+        Ok(self.roll_repeated_with(&Vars::new(), |sides| rng.roll_die(sides)))
+    }
+
+    /// Rolls the expression as many times as its repeat count, with its
+    /// variables taken from `vars`.
+    #[cfg(feature = "std")]
+    pub fn roll_repeated_with_vars(&self, vars: &Vars) -> Result<Vec<RollResult>, DiceExprError> {
+        self.roll_repeated_with_roller(vars, &mut thread_rng())
+    }
+
+    /// Rolls the expression like `roll_repeated_with_vars`, with each die
+    /// rolled by `roller`.
+    #[cfg(feature = "std")]
+    pub fn roll_repeated_with_roller<R: Roller + ?Sized>(
+        &self,
+        vars: &Vars,
+        roller: &mut R,
+    ) -> Result<Vec<RollResult>, DiceExprError> {
+        self.check_vars(vars)?;
+
+        Ok(self.roll_repeated_with(vars, |sides| roller.roll_die(sides)))
+    }
+
+    /// Fails if the expression can't be rolled with its variables taken as
+    /// zero, as a divisor or the count or sides of rolled dice can't be, or
+    /// if it's been joined with operators into one that parsing would
+    /// reject. Parsing checks everything else, so each roll without
+    /// variables checks this first.
+    pub(crate) fn check_rollable(&self) -> Result<(), DiceExprError> {
+        match self.ast.rollable_with(&Vars::new()) {
+            true => self.ast.validate(self, self.signed),
+            false => Err(DiceExprError::from(self.to_string())),
+        }
+    }
+
+    /// Fails with the first variable in the expression missing from `vars`,
+    /// or if their values can't be rolled, such as a divisor of zero, or let
+    /// the total overflow.
+    #[cfg(feature = "std")]
+    pub(crate) fn check_vars(&self, vars: &Vars) -> Result<(), DiceExprError> {
+        match self
+            .ast
+            .variables()
+            .into_iter()
+            .find(|v| !vars.contains_key(*v))
+        {
+            Some(name) => Err(DiceExprError::Variable(name.to_string())),
+            None if !self.ast.rollable_with(vars) => Err(DiceExprError::from(self.to_string())),
+            None if self.ast.checked_range(vars).is_none() => {
+                Err(DiceExprError::Overflow(self.to_string()))
+            }
+            None => self.ast.validate(self, self.signed),
+        }
+    }
+
+    pub(crate) fn roll_repeated_with<F: FnMut(u32) -> u32>(
+        &self,
+        vars: &Vars,
+        mut roll_die: F,
+    ) -> Vec<RollResult> {
+        (0..self.repeat)
+            .map(|_| self.roll_vars_with(vars, &mut roll_die))
+            .collect()
+    }
+
+    pub(crate) fn roll_with<F: FnMut(u32) -> u32>(&self, roll_die: F) -> RollResult {
+        self.roll_vars_with(&Vars::new(), roll_die)
+    }
+
+    pub(crate) fn roll_vars_with<F: FnMut(u32) -> u32>(
+        &self,
+        vars: &Vars,
+        mut roll_die: F,
+    ) -> RollResult {
+        let mut dice = Vec::new();
+        let total = self.ast.eval(&mut roll_die, vars, &mut dice).floor();
+
+        self.result(total, dice)
+    }
+
+    /// The result of a roll of the dice in `dice` coming to `total`, before
+    /// any negative total is raised to zero.
+    fn result(&self, total: i64, dice: Vec<DiceRoll>) -> RollResult {
+        let total = match self.signed || self.ast.signed() {
+            true => total,
+            false => total.max(0),
+        };
+
+        RollResult {
+            expr: self.to_string(),
+            total,
+            dice,
+            modifier: self.ast.modifier(),
+            dc: self.dc.map(|dc| DcCheck {
+                dc,
+                margin: total - dc,
+                success: total >= dc,
+            }),
+            #[cfg(feature = "std")]
+            timestamp: SystemTime::now(),
+        }
+    }
+}
+
+/// What the `dice!` macro expands to, building an expression from the parts
+/// it parsed and checked when it was compiled, without checking them again.
+/// Not part of the public API: building from parts that weren't parsed can
+/// make an expression that loops forever or panics when it's rolled.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    /// The parts of a parsed `DiceExpr`.
+    pub struct ExprParts {
+        pub repeat: u32,
+        pub signed: bool,
+        pub ast: Ast,
+        pub dc: Option<i64>,
+    }
+
+    /// The parts of a parsed `Dice` term.
+    pub struct DiceParts {
+        pub count: u32,
+        pub sides: Sides,
+        pub explode: Option<Comparison>,
+        pub open_ended: bool,
+        pub reroll: Option<Reroll>,
+        pub min: Option<i64>,
+        pub max: Option<i64>,
+        pub keep: Keep,
+        pub drop: Drop,
+        pub success: Option<Comparison>,
+        pub double: Option<Comparison>,
+        pub failure: Option<Comparison>,
+        pub critical_success: Option<Comparison>,
+        pub critical_failure: Option<Comparison>,
+        pub sets: bool,
+        pub sort: Option<Sort>,
+        pub each: Option<i64>,
+        pub label: Option<String>,
+    }
+
+    pub fn unchecked_expr(parts: ExprParts) -> DiceExpr {
+        let ExprParts {
+            repeat,
+            signed,
+            ast,
+            dc,
+        } = parts;
+
+        DiceExpr {
+            repeat,
+            signed,
+            ast,
+            dc,
+        }
+    }
+
+    pub fn unchecked_dice(parts: DiceParts) -> Dice {
+        let DiceParts {
+            count,
+            sides,
+            explode,
+            open_ended,
+            reroll,
+            min,
+            max,
+            keep,
+            drop,
+            success,
+            double,
+            failure,
+            critical_success,
+            critical_failure,
+            sets,
+            sort,
+            each,
+            label,
+        } = parts;
+
+        Dice {
+            count,
+            sides,
+            explode,
+            open_ended,
+            reroll,
+            min,
+            max,
+            keep,
+            drop,
+            success,
+            double,
+            failure,
+            critical_success,
+            critical_failure,
+            sets,
+            sort,
+            each,
+            label,
+        }
+    }
+
+    pub fn unchecked_reroll(condition: Comparison, once: bool) -> Reroll {
+        Reroll { condition, once }
+    }
+}
+
+#[cfg(test)]
+mod dice {
+    use super::*;
+
+    #[test]
+    fn try_from_str() {
+        let expr = "4d4";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 4,
+                sides: Sides::Number(4),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::None,
+                drop: Drop::None,
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_drop() {
+        let expr = "4d4-H";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 4,
+                sides: Sides::Number(4),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::None,
+                drop: Drop::High(1),
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_drop_single_die() {
+        let expr = "d4-H";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_drop_invalid() {
+        let expr = "4d4-J";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_keep() {
+        let expr = "4d6kh3";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 4,
+                sides: Sides::Number(6),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::High(3),
+                drop: Drop::None,
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_keep_default_count() {
+        let expr = "2d20kl";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 2,
+                sides: Sides::Number(20),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::Low(1),
+                drop: Drop::None,
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_keep_too_many() {
+        let expr = "4d6kh5";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_keep_and_drop() {
+        let expr = "4d6kh3-L";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_keep_high() {
+        let expr = Dice::try_from("4d6kh3").unwrap();
+        let mut values = vec![3, 1, 6, 4].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(13, total);
+        assert_eq!(
+            vec![true, false, true, true],
+            rolls.iter().map(|r| r.kept).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_drop_low() {
+        let expr = Dice::try_from("4d6-L").unwrap();
+        let mut values = vec![3, 1, 6, 4].into_iter();
+        let roll = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(13, roll.total);
+        assert!(!roll.rolls[1].kept);
+        assert_eq!(vec![1], roll.dropped());
+        assert!(roll.rerolled().is_empty())
+    }
+
+    #[test]
+    fn try_from_str_drop_count() {
+        let expr = "6d6-L2";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 6,
+                sides: Sides::Number(6),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::None,
+                drop: Drop::Low(2),
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_drop_roll20() {
+        assert_eq!(Dice::try_from("6d6-L2"), Dice::try_from("6d6dl2"))
+    }
+
+    #[test]
+    fn try_from_str_drop_too_many() {
+        let expr = "4d6dh4";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_drop_count() {
+        let expr = Dice::try_from("6d6dh2").unwrap();
+        let mut values = vec![3, 1, 6, 4, 5, 2].into_iter();
+        let DiceRoll { total, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(10, total)
+    }
+
+    #[test]
+    fn try_from_str_keep_middle() {
+        let expr = "3d20km1";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 3,
+                sides: Sides::Number(20),
+                explode: None,
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::Middle(1),
+                drop: Drop::None,
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_keep_middle() {
+        let expr = Dice::try_from("3d20km1").unwrap();
+        let mut values = vec![17, 2, 9].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(9, total);
+        assert_eq!(
+            vec![false, false, true],
+            rolls.iter().map(|r| r.kept).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_keep_middle_uneven() {
+        let expr = Dice::try_from("4d6km1").unwrap();
+        let mut values = vec![5, 2, 6, 3].into_iter();
+        let DiceRoll { total, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(3, total)
+    }
+
+    #[test]
+    fn try_from_str_explode() {
+        let expr = "3d6!";
+
+        assert_eq!(
+            Ok(Dice {
+                count: 3,
+                sides: Sides::Number(6),
+                explode: Some(Comparison::Eq(vec![6])),
+                open_ended: false,
+                reroll: None,
+                min: None,
+                max: None,
+                keep: Keep::None,
+                drop: Drop::None,
+                success: None,
+                double: None,
+                failure: None,
+                critical_success: None,
+                critical_failure: None,
+                sets: false,
+                sort: None,
+                each: None,
+                label: None,
+            }),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_explode_d1() {
+        let expr = "3d1!";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_explode() {
+        let expr = Dice::try_from("2d6!").unwrap();
+        let mut values = vec![6, 6, 2, 3].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(17, total);
+        assert_eq!(
+            vec![true, true, false, false],
+            rolls.iter().map(|r| r.exploded).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_explode_threshold() {
+        let expr = Dice::try_from("d10!>8").unwrap();
+
+        assert_eq!(Some(Comparison::Gt(8)), expr.explode);
+        assert_eq!("d10!>8", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_explode_list() {
+        let expr = Dice::try_from("d6!=[5,6]").unwrap();
+
+        assert_eq!(Some(Comparison::Eq(vec![5, 6])), expr.explode)
+    }
+
+    #[test]
+    fn try_from_str_explode_every_face() {
+        let expr = "d6!>=1";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_explode_threshold() {
+        let expr = Dice::try_from("d10!>8").unwrap();
+        let mut values = vec![9, 10, 4].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(23, total);
+        assert_eq!(3, rolls.len())
+    }
+
+    #[test]
+    fn try_from_str_reroll() {
+        let expr = Dice::try_from("2d6r<3").unwrap();
+
+        assert_eq!(
+            Some(Reroll {
+                condition: Comparison::Lt(3),
+                once: false,
+            }),
+            expr.reroll
+        );
+        assert_eq!("2d6r<3", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_reroll_every_face() {
+        let expr = "2d6r<=6";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_reroll() {
+        let expr = Dice::try_from("2d6r1").unwrap();
+        let mut values = vec![1, 1, 4, 5].into_iter();
+        let roll = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(9, roll.total);
+        assert_eq!(
+            vec![true, true, false, false],
+            roll.rolls.iter().map(|r| r.rerolled).collect::<Vec<_>>()
+        );
+        assert_eq!(vec![0, 1], roll.rerolled());
+        assert!(roll.dropped().is_empty())
+    }
+
+    #[test]
+    fn try_from_str_reroll_once() {
+        let expr = Dice::try_from("2d6ro<=2").unwrap();
+
+        assert_eq!(
+            Some(Reroll {
+                condition: Comparison::Le(2),
+                once: true,
+            }),
+            expr.reroll
+        );
+        assert_eq!("2d6ro<=2", expr.to_string())
+    }
+
+    #[test]
+    fn roll_reroll_once() {
+        let expr = Dice::try_from("2d6ro<=2").unwrap();
+        let mut values = vec![1, 2, 6].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(8, total);
+        assert_eq!(
+            vec![true, false, false],
+            rolls.iter().map(|r| r.rerolled).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_fate() {
+        let expr = Dice::try_from("4dF").unwrap();
+
+        assert_eq!(Sides::Fate, expr.sides);
+        assert_eq!("4dF", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_percentile() {
+        let expr = Dice::try_from("d%").unwrap();
+
+        assert_eq!(Sides::Percentile, expr.sides);
+        assert_eq!("d%", expr.to_string())
+    }
+
+    #[test]
+    fn roll_percentile() {
+        let expr = Dice::try_from("2d%").unwrap();
+        let mut values = vec![5, 8, 1, 1].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(147, total);
+        assert_eq!("47 (40+7)", rolls[0].to_string());
+        assert_eq!("100 (00+0)", rolls[1].to_string())
+    }
+
+    #[test]
+    fn roll_coins() {
+        let expr = Dice::try_from("3dc").unwrap();
+        let mut values = vec![2, 1, 2].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(Sides::Coin, expr.sides);
+        assert_eq!("3dC", expr.to_string());
+        assert_eq!(2, total);
+        assert_eq!(
+            vec!["H", "T", "H"],
+            rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_positional() {
+        let expr = Dice::try_from("d66").unwrap();
+
+        assert_eq!(
+            Sides::Positional {
+                faces: 6,
+                digits: 2
+            },
+            expr.sides
+        );
+        assert_eq!("d66", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_positional_every_face() {
+        let expr = "d66r=[11,12,13,14,15,16,21,22,23,24,25,26,31,32,33,34,35,36,41,42,43,44,45,46,51,52,53,54,55,56,61,62,63,64,65,66]";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_positional() {
+        let expr = Dice::try_from("d66").unwrap();
+        let mut values = vec![3, 5].into_iter();
+        let DiceRoll { total, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(35, total)
+    }
+
+    #[test]
+    fn try_from_str_faces() {
+        let expr = Dice::try_from("3d[0,0,1,1,2]").unwrap();
+
+        assert_eq!(Sides::Faces(vec![0, 0, 1, 1, 2]), expr.sides);
+        assert_eq!(0..=6, expr.range());
+        assert_eq!("3d[0,0,1,1,2]", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_faces_invalid() {
+        for expr in ["d[]", "d[1,,2]", "d[1,a]"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                Dice::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_faces() {
+        let expr = Dice::try_from("2d[-1,3,5,7]!").unwrap();
+        let mut values = vec![4, 2, 1].into_iter();
+        let DiceRoll { total, .. } = expr.roll_with(&mut |sides| {
+            assert_eq!(4, sides);
+            values.next().unwrap()
+        });
+
+        assert_eq!(9, total);
+        assert!(expr.signed())
+    }
+
+    #[test]
+    fn try_from_str_open_ended() {
+        let expr = Dice::try_from("d%oe").unwrap();
+
+        assert!(expr.open_ended);
+        assert!(expr.signed());
+        assert_eq!("d%oe", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_open_ended_invalid() {
+        for expr in ["d20oe", "d100!oe", "2d100oekh1", "2d100oe-L"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                Dice::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_open_ended() {
+        let expr = Dice::try_from("2d100oe").unwrap();
+        let mut values = vec![97, 99, 40, 3, 98, 20].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(97 + 99 + 40 + 3 - 98 - 20, total);
+        assert_eq!(
+            vec!["97!", "99!", "40", "3!", "-98!", "-20"],
+            rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_min_max() {
+        let expr = Dice::try_from("8d6min2max5kh3").unwrap();
+
+        assert_eq!((Some(2), Some(5)), (expr.min, expr.max));
+        assert_eq!(6..=15, expr.range());
+        assert_eq!("8d6min2max5kh3", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_min_above_max() {
+        let expr = "8d6min5max2";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            Dice::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_clamped_explode_every_face() {
+        for expr in ["d6!min6", "d6!>=3min3", "d6!r<6"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                Dice::try_from(expr)
+            )
+        }
+
+        assert!(Dice::try_from("d6!min5").is_ok());
+        assert!(Dice::try_from("d6!r=[1,2,3,4,5]").is_err());
+        assert!(Dice::try_from("d6!=[1,6]r=[2,3,4,5]").is_err());
+        assert!(Dice::try_from("d6!=[1,6]r=[2,3,4]").is_ok());
+        assert!(Dice::try_from("d6!ro<6").is_ok())
+    }
+
+    #[test]
+    fn try_from_str_huge_sides_every_face() {
+        for (expr, valid) in [
+            ("d4000000000!<3999999999", true),
+            ("d4000000000!<4000000001", false),
+            ("d4000000000!<=3999999999min2", true),
+            ("d4000000000!<=3999999999max3999999999", false),
+            ("d4000000000!r<=3999999999", false),
+            ("d4000000000r>=1", false),
+            ("d4000000000!=[1,2]r>2", false),
+        ] {
+            assert_eq!(valid, Dice::try_from(expr).is_ok(), "{}", expr)
+        }
+    }
+
+    #[test]
+    fn roll_min() {
+        let expr = Dice::try_from("3d6min2kl2").unwrap();
+        let mut values = vec![1, 4, 3].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(5, total);
+        assert_eq!(Some(1), rolls[0].clamped_from);
+        assert_eq!(
+            vec!["1->2", "~4~", "3"],
+            rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_sets() {
+        let expr = Dice::try_from("10d6m").unwrap();
+        let mut values = vec![5, 2, 5, 6, 1, 2, 5, 3, 4, 6].into_iter();
+        let DiceRoll { sets, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!("10d6m", expr.to_string());
+        assert_eq!(
+            vec!["3x5", "2x6", "2x2"],
+            sets.iter().map(|s| s.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_sort() {
+        for (expr, sort) in [
+            ("4d6kh3s", Sort::Ascending),
+            ("4d6kh3sa", Sort::Ascending),
+            ("4d6kh3sd", Sort::Descending),
+        ] {
+            assert_eq!(Some(sort), Dice::try_from(expr).unwrap().sort)
+        }
+
+        assert_eq!("4d6kh3s", Dice::try_from("4d6kh3sa").unwrap().to_string())
+    }
+
+    #[test]
+    fn roll_sort() {
+        let expr = Dice::try_from("4d6-Lsd").unwrap();
+        let mut values = vec![2, 6, 1, 4].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(12, total);
+        assert_eq!(
+            vec![(6, true), (4, true), (2, true), (1, false)],
+            rolls.iter().map(|r| (r.value, r.kept)).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_each() {
+        for (expr, each) in [("4d6+2each", 2), ("4d6++2", 2), ("4d6-1each", -1)] {
+            assert_eq!(Some(each), Dice::try_from(expr).unwrap().each)
+        }
+
+        assert_eq!("4d6+2each", Dice::try_from("4d6++2").unwrap().to_string())
+    }
+
+    #[test]
+    fn roll_each() {
+        let expr = Dice::try_from("4d6!kh3-2each").unwrap();
+        let mut values = vec![6, 1, 3, 2, 5].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(8, total);
+        assert_eq!(
+            vec![4, -1, 1, 0, 3],
+            rolls.iter().map(|r| r.value).collect::<Vec<_>>()
+        );
+        assert_eq!(-3..=12, expr.range())
+    }
+
+    #[test]
+    fn try_from_str_success_failure() {
+        let expr = Dice::try_from("8d10>=8f1").unwrap();
+
+        assert_eq!(Some(Comparison::Ge(8)), expr.success);
+        assert_eq!(Some(Comparison::Eq(vec![1])), expr.failure);
+        assert_eq!("8d10>=8f1", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_failure_without_success() {
+        for expr in ["8d10f1", "8d10t10"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                Dice::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_success() {
+        let expr = Dice::try_from("4d10>=8").unwrap();
+        let mut values = vec![8, 3, 10, 1].into_iter();
+        let DiceRoll { total, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(2, total)
+    }
+
+    #[test]
+    fn roll_double() {
+        let expr = Dice::try_from("4d10>=7t10").unwrap();
+        let mut values = vec![10, 7, 3, 10].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!("4d10>=7t10", expr.to_string());
+        assert_eq!(0..=8, expr.range());
+        assert_eq!(5, total);
+        assert_eq!(
+            vec!["10**", "7*", "3", "10**"],
+            rolls.iter().map(|r| r.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_botch() {
+        let expr = Dice::try_from("4d10>=8f1").unwrap();
+        let mut values = vec![1, 3, 1, 1].into_iter();
+        let DiceRoll { total, rolls, .. } = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert_eq!(-3, total);
+        assert_eq!("1f", rolls[0].to_string())
+    }
+
+    #[test]
+    fn try_from_str_critical() {
+        let expr = Dice::try_from("d20cs>=19cf1").unwrap();
+
+        assert_eq!(Some(Comparison::Ge(19)), expr.critical_success);
+        assert_eq!(Some(Comparison::Eq(vec![1])), expr.critical_failure);
+        assert_eq!("d20cs>=19cf1", expr.to_string())
+    }
+
+    #[test]
+    fn roll_critical_success() {
+        let expr = Dice::try_from("d20cs>=19").unwrap();
+        let result = expr.roll_with(&mut |_| 19);
+
+        assert!(result.critical_success);
+        assert!(!result.critical_failure)
+    }
+
+    #[test]
+    fn roll_critical_dropped() {
+        let expr = Dice::try_from("2d20kh1cf1").unwrap();
+        let mut values = vec![1, 12].into_iter();
+        let result = expr.roll_with(&mut |_| values.next().unwrap());
+
+        assert!(!result.critical_failure)
+    }
+}
+
+// Rolling with variables and the thread's generator needs `std`.
+#[cfg(all(test, feature = "std"))]
+mod dice_expr {
+    use super::*;
+    use crate::seeded::SeededRoller;
+
+    /// Rolls every die as the same value.
+    struct Fixed(u32);
+
+    impl Roller for Fixed {
+        fn roll_die(&mut self, _: u32) -> u32 {
+            self.0
+        }
+    }
+
+    fn dice(count: u32, sides: u32) -> Box<Ast> {
+        Box::new(Ast::Dice(Box::new(Dice::new(count, Sides::Number(sides)))))
+    }
+
+    fn syntax_error(
+        expr: &str,
+        offset: usize,
+        len: usize,
+        expected: Option<&'static str>,
+    ) -> DiceExprError {
+        DiceExprError::Syntax(SyntaxError {
+            expr: String::from(expr),
+            offset,
+            len,
+            expected,
+        })
+    }
+
+    #[test]
+    fn try_from_str_modifier() {
+        let expr = "4d4+1";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Add(dice(4, 4), Box::new(Ast::Constant(1))),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn accessors() {
+        let expr = DiceExpr::try_from("4d6-L+1d4+2").unwrap();
+        let dice = expr.dice();
+
+        assert_eq!(2, dice.len());
+        assert_eq!(&Sides::Number(6), dice[0].sides());
+        assert_eq!(&Drop::Low(1), dice[0].drop());
+        assert_eq!(5, expr.count());
+        assert_eq!(2, expr.modifier())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn hash_key() {
+        let expr = DiceExpr::try_from("4d6-L").unwrap();
+        let mut cache = HashMap::new();
+        cache.insert(expr.clone(), 1);
+
+        assert_eq!(Some(&1), cache.get(&DiceExpr::try_from("4d6-L").unwrap()));
+        assert_eq!(None, cache.get(&DiceExpr::try_from("4d6-H").unwrap()))
+    }
+
+    #[test]
+    fn builder() {
+        let expr = DiceExpr::builder()
+            .count(4)
+            .sides(6)
+            .drop_lowest(1)
+            .modifier(2)
+            .build();
+
+        assert_eq!(DiceExpr::try_from("4d6-L+2"), expr);
+        assert_eq!(
+            DiceExpr::try_from("3d8-1"),
+            DiceExpr::builder().count(3).sides(8).modifier(-1).build()
+        );
+        assert_eq!(Ok(DiceExpr::default()), DiceExpr::builder().build())
+    }
+
+    #[test]
+    fn builder_invalid() {
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from("2d6kh3"))),
+            DiceExpr::builder()
+                .count(2)
+                .sides(6)
+                .keep_highest(3)
+                .build()
+        );
+        assert!(DiceExpr::builder().sides(0).build().is_err());
+        assert!(DiceExpr::builder()
+            .count(4)
+            .keep_highest(3)
+            .drop_lowest(1)
+            .build()
+            .is_err());
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from("d20-100"))),
+            DiceExpr::builder().modifier(-100).build()
+        );
+        assert!(matches!(
+            DiceExpr::builder().count(u32::MAX).sides(u32::MAX).build(),
+            Err(DiceExprError::Overflow(_))
+        ))
+    }
+
+    #[test]
+    fn stats() {
+        let expr = DiceExpr::try_from("2d6+3").unwrap();
+
+        assert_eq!(5, expr.min_value());
+        assert_eq!(15, expr.max_value());
+        assert_eq!(Some(10.0), expr.mean());
+        assert!((expr.variance().unwrap() - 35.0 / 6.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn stats_keep() {
+        let expr = DiceExpr::try_from("2d20kh1").unwrap();
+
+        assert!((expr.mean().unwrap() - 13.825).abs() < 1e-9);
+        assert!((expr.variance().unwrap() - 22.194375).abs() < 1e-9);
+
+        let expr = DiceExpr::try_from("4d6-L").unwrap();
+        assert!((expr.mean().unwrap() - 12.2446).abs() < 1e-4);
+        assert!((expr.variance().unwrap() - 8.1045).abs() < 1e-4)
+    }
+
+    #[test]
+    fn stats_clamped() {
+        let expr = DiceExpr::try_from("1d6-3").unwrap();
+
+        assert!((expr.mean().unwrap() - 1.0).abs() < 1e-9);
+        assert!((expr.variance().unwrap() - 4.0 / 3.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn stats_unknown() {
+        for expr in ["1d6!", "1d6/2", "$str+1d20"] {
+            assert_eq!(None, DiceExpr::try_from(expr).unwrap().mean(), "{}", expr)
+        }
+
+        let expr = DiceExpr::try_from("1d6!").unwrap();
+        assert_eq!((1, i64::MAX), (expr.min_value(), expr.max_value()));
+        assert_eq!(0, DiceExpr::try_from("1d4-3").unwrap().min_value())
+    }
+
+    #[test]
+    fn stats_modifiers() {
+        let mean = |expr| DiceExpr::try_from(expr).unwrap().mean().unwrap();
+
+        assert!((mean("1d6r1") - 4.0).abs() < 1e-9);
+        assert!((mean("1d6ro1") - 3.5 - 2.5 / 6.0).abs() < 1e-9);
+        assert!((mean("4dF") - 0.0).abs() < 1e-9);
+        assert!((mean("10d10>=8") - 3.0).abs() < 1e-9);
+        assert!((mean("1d6*2") - 7.0).abs() < 1e-9)
+    }
+
+    #[test]
+    fn distribution() {
+        let distribution = DiceExpr::try_from("2d6+3").unwrap().distribution().unwrap();
+
+        assert_eq!((5, 15), (distribution.min(), distribution.max()));
+        assert!((distribution.pmf(10) - 6.0 / 36.0).abs() < 1e-12);
+        assert!((distribution.cdf(15) - 1.0).abs() < 1e-12)
+    }
+
+    #[test]
+    fn distribution_keep() {
+        let distribution = DiceExpr::try_from("4d6-L").unwrap().distribution().unwrap();
+
+        assert_eq!((3, 18), (distribution.min(), distribution.max()));
+        assert!((distribution.pmf(18) - 21.0 / 1296.0).abs() < 1e-12);
+        assert!((distribution.mean() - 12.2446).abs() < 1e-4);
+
+        let distribution = DiceExpr::try_from("2d20kl1")
+            .unwrap()
+            .distribution()
+            .unwrap();
+        assert!((distribution.pmf(1) - 39.0 / 400.0).abs() < 1e-12)
+    }
+
+    #[test]
+    fn distribution_clamped() {
+        let distribution = DiceExpr::try_from("1d4-3").unwrap().distribution().unwrap();
+
+        assert!((distribution.pmf(0) - 0.75).abs() < 1e-12);
+        assert_eq!(0, distribution.min())
+    }
+
+    #[test]
+    fn distribution_rolled() {
+        let distribution = DiceExpr::try_from("(1d2)d6")
+            .unwrap()
+            .distribution()
+            .unwrap();
+
+        assert_eq!((1, 12), (distribution.min(), distribution.max()));
+        assert!((distribution.pmf(1) - 1.0 / 12.0).abs() < 1e-12)
+    }
+
+    #[test]
+    fn distribution_unknown() {
+        for expr in ["1d6!", "1d6/2", "$str+1d20", "{1d6,1d8}kh1"] {
+            assert_eq!(
+                None,
+                DiceExpr::try_from(expr).unwrap().distribution(),
+                "{}",
+                expr
+            )
+        }
+    }
+
+    #[test]
+    fn prob() {
+        let expr = DiceExpr::try_from("1d20+5").unwrap();
+
+        assert!((expr.prob_at_least(15).unwrap() - 0.55).abs() < 1e-12);
+        assert!((expr.prob_at_most(15).unwrap() - 0.5).abs() < 1e-12);
+        assert!((expr.prob_at_least(6).unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(0.0, expr.prob_at_least(26).unwrap());
+        assert_eq!(None, DiceExpr::try_from("1d6!").unwrap().prob_at_least(6))
+    }
+
+    #[test]
+    fn compare() {
+        let (a, b) = (
+            DiceExpr::try_from("2d6+3").unwrap(),
+            DiceExpr::try_from("1d12+4").unwrap(),
+        );
+
+        assert!((a.prob_beats(&b).unwrap() - 5.0 / 12.0).abs() < 1e-12);
+        assert!((b.prob_beats(&a).unwrap() - 6.0 / 12.0).abs() < 1e-12);
+        assert!((a.prob_ties(&b).unwrap() - 1.0 / 12.0).abs() < 1e-12);
+        assert_eq!(Some(-0.5), a.mean_difference(&b));
+        assert_eq!(None, a.prob_beats(&DiceExpr::try_from("1d6!").unwrap()))
+    }
+
+    #[test]
+    fn try_from_str_overflow() {
+        for expr in [
+            "2d[9223372036854775807]",
+            "1d[9223372036854775807]+1each",
+            "1d6+9223372036854775807",
+            "1d6*9223372036854775807*2",
+        ] {
+            assert_eq!(
+                Err(DiceExprError::Overflow(String::from(expr))),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn roll_with_vars_overflow() {
+        let expr = DiceExpr::try_from("$x*1d6").unwrap();
+        let vars = HashMap::from([(String::from("x"), i64::MAX)]);
+
+        assert_eq!(
+            Err(DiceExprError::Overflow(String::from("$x*d6"))),
+            expr.roll_with_vars(&vars)
+        )
+    }
+
+    #[test]
+    fn roll_explosion_saturates() {
+        let expr = DiceExpr::try_from("1d[9223372036854775806,9223372036854775807]!").unwrap();
+        let mut values = vec![2, 2, 1].into_iter();
+
+        assert_eq!(i64::MAX, expr.roll_with(|_| values.next().unwrap()).total)
+    }
+
+    #[test]
+    fn try_from_str_empty_dice() {
+        for (expr, term) in [
+            ("d0", "d0"),
+            ("0d6", "0d6"),
+            ("0d6+1", "0d6"),
+            ("2+0d[1,2]", "0d[1,2]"),
+        ] {
+            assert_eq!(
+                Err(DiceExprError::EmptyDice(String::from(term))),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn error_source() {
+        let e = DiceExpr::try_from("99999999999d6").unwrap_err();
+
+        assert!(matches!(e, DiceExprError::ParseIntError(_)));
+        assert!(e.source().is_some());
+        assert!(DiceExprError::Expr(String::from("d")).source().is_none());
+
+        let e = DiceExpr::try_from("2d6 + (1d4").unwrap_err();
+        assert_eq!(
+            Some(e.to_string()),
+            e.source().map(|source| source.to_string())
+        )
+    }
+
+    #[test]
+    fn error_span() {
+        let e = DiceExpr::try_from("2d6 + (1d4").unwrap_err();
+
+        assert_eq!(Some(10..10), e.span());
+        assert_eq!(None, DiceExprError::Expr(String::from("d")).span())
+    }
+
+    #[test]
+    fn from_str() {
+        assert_eq!(DiceExpr::try_from("3d6+2"), "3d6+2".parse::<DiceExpr>());
+        assert!("3d6+".parse::<DiceExpr>().is_err())
+    }
+
+    #[test]
+    fn try_from_str_modifier_lt_0() {
+        let expr = "4d4-1";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Sub(dice(4, 4), Box::new(Ast::Constant(1))),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_modifier_too_negative() {
+        let expr = "4d4-16";
+
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(expr))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_invalid() {
+        let expr = "asdf";
+
+        assert_eq!(
+            Err(syntax_error(expr, 0, 1, Some("a number, dice, or \"(\""))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_compound() {
+        let expr = "2d6+1d4+3";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Add(
+                    Box::new(Ast::Add(dice(2, 6), dice(1, 4))),
+                    Box::new(Ast::Constant(3))
+                ),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_trailing_operator() {
+        let expr = "2d6+";
+
+        assert_eq!(
+            Err(syntax_error(expr, 4, 0, Some("a number, dice, or \"(\""))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn display_keep() {
+        let expr = DiceExpr::try_from("4d6kl2+1").unwrap();
+
+        assert_eq!("4d6kl2+1", expr.to_string())
+    }
+
+    #[test]
+    fn display_compound() {
+        let expr = DiceExpr::try_from("2d6+2d4-L+3").unwrap();
+
+        assert_eq!("2d6+2d4-L+3", expr.to_string())
+    }
+
+    #[test]
+    fn roll_compound() {
+        let expr = DiceExpr::try_from("2d6+1d4+3").unwrap();
+        let mut values = vec![3, 5, 2].into_iter();
+        let result = expr.roll_with(|_| values.next().unwrap());
+
+        assert_eq!(13, result.total);
+        assert_eq!(
+            vec![8, 2],
+            result.dice.iter().map(|d| d.total).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_large() {
+        let expr = DiceExpr::try_from("1000d1000+d100000").unwrap();
+        let result = expr.roll_with(|sides| sides);
+
+        assert_eq!(1001..=1100000, expr.ast.range());
+        assert_eq!(1100000, result.total)
+    }
+
+    #[test]
+    fn roll_modifier_clamped() {
+        let expr = DiceExpr::try_from("2d6-4").unwrap();
+        let result = expr.roll_with(|_| 1);
+
+        assert_eq!(0, result.total)
+    }
+
+    #[test]
+    fn roll_modifier_signed() {
+        let expr = DiceExpr::try_from("2d6-4").unwrap().signed(true);
+        let result = expr.roll_with(|_| 1);
+
+        assert_eq!(-2, result.total)
+    }
+
+    #[test]
+    fn try_from_str_coins() {
+        for (expr, display) in [("3c", "3dC"), ("3DC", "3dC"), ("5c=1+1", "5dC=1+1")] {
+            assert_eq!(display, DiceExpr::try_from(expr).unwrap().to_string())
+        }
+
+        let expr = "0c";
+        assert_eq!(
+            Err(DiceExprError::EmptyDice(String::from(expr))),
+            DiceExpr::try_from(expr)
+        );
+
+        let expr = "3cx";
+        assert_eq!(
+            Err(syntax_error(expr, 1, 1, Some("an operator"))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_fate_negative() {
+        let expr = DiceExpr::try_from("4dF-1").unwrap();
+        let mut values = vec![1, 1, 2, 3].into_iter();
+        let result = expr.roll_with(|_| values.next().unwrap());
+
+        assert_eq!(-2, result.total);
+        assert_eq!(
+            "-, -, 0, +",
+            result.dice[0]
+                .rolls
+                .iter()
+                .map(|r| r.to_string())
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    }
+
+    #[test]
+    fn roll_critical() {
+        let expr = DiceExpr::try_from("d20cs>=19+5").unwrap();
+        let result = expr.roll_with(|_| 20);
+
+        assert_eq!(25, result.total);
+        assert!(result.is_critical_success())
+    }
+
+    #[test]
+    fn try_from_str_subtract_dice() {
+        let expr = "1d20-1d4";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Sub(dice(1, 20), dice(1, 4)),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_subtract_dice_negative() {
+        let expr = DiceExpr::try_from("1d20-1d4").unwrap();
+        let mut values = vec![1, 4].into_iter();
+        let result = expr.roll_with(|_| values.next().unwrap());
+
+        assert_eq!(-3, result.total)
+    }
+
+    #[test]
+    fn try_from_str_precedence() {
+        let expr = "2d6+3*2";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Add(
+                    dice(2, 6),
+                    Box::new(Ast::Mul(
+                        Box::new(Ast::Constant(3)),
+                        Box::new(Ast::Constant(2))
+                    ))
+                ),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_divide_by_zero() {
+        let expr = "1d6/0";
+
+        assert_eq!(
+            Err(syntax_error(expr, 4, 1, None)),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_multiple_errors() {
+        let expr = "2d6 + 3d6kh4 + 1d8/0 + (1d4";
+
+        assert_eq!(
+            Err(DiceExprError::Multiple(vec![
+                DiceExprError::Expr(String::from("3d6kh4")),
+                syntax_error(expr, 19, 1, None),
+                syntax_error(expr, 27, 0, Some("\")\"")),
+            ])),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn parse_all_multiple_errors() {
+        assert_eq!(
+            Err(DiceExprError::Multiple(vec![
+                syntax_error("4x", 1, 1, Some("an operator")),
+                DiceExprError::EmptyDice(String::from("1d0")),
+            ])),
+            DiceExpr::parse_all("2d6; 4x; 1d0", Dialect::Standard)
+        )
+    }
+
+    #[test]
+    fn syntax_error_display() {
+        assert_eq!(
+            "Invalid dice expression \"2d6 + (1d4\"\n  2d6 + (1d4\n            ^ expected \")\"",
+            DiceExpr::try_from("2d6 + (1d4").unwrap_err().to_string()
+        );
+        assert_eq!(
+            "Invalid dice expression \"1d6/(1d4-1)\"\n  1d6/(1d4-1)\n      ^^^^^^^",
+            DiceExpr::try_from("1d6/(1d4-1)").unwrap_err().to_string()
+        )
+    }
+
+    #[test]
+    fn try_from_str_unclosed_function() {
+        let expr = "ceil(1d100/10";
+
+        assert_eq!(
+            Err(syntax_error(expr, 13, 0, Some("\")\""))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn display_functions() {
+        let expr = DiceExpr::try_from("ceil(d100/10)+round(2d6*3/2)").unwrap();
+
+        assert_eq!("ceil(d100/10)+round(2d6*3/2)", expr.to_string())
+    }
+
+    #[test]
+    fn roll_divide_floor() {
+        let expr = DiceExpr::try_from("1d100/10").unwrap();
+        let result = expr.roll_with(|_| 47);
+
+        assert_eq!(4, result.total)
+    }
+
+    #[test]
+    fn roll_divide_exact_until_rounded() {
+        let expr = DiceExpr::try_from("1d6/2+1d6/2").unwrap();
+        let result = expr.roll_with(|_| 3);
+
+        assert_eq!(3, result.total)
+    }
+
+    #[test]
+    fn roll_ceil_round() {
+        let ceil = DiceExpr::try_from("ceil(1d100/10)").unwrap();
+        let round = DiceExpr::try_from("round(1d6/2)").unwrap();
+
+        assert_eq!(5, ceil.roll_with(|_| 41).total);
+        assert_eq!(3, round.roll_with(|_| 5).total)
+    }
+
+    #[test]
+    fn try_from_str_parentheses() {
+        let expr = "(1d4+1)*3";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Mul(
+                    Box::new(Ast::Add(dice(1, 4), Box::new(Ast::Constant(1)))),
+                    Box::new(Ast::Constant(3))
+                ),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_unbalanced_parentheses() {
+        for (expr, offset, len, expected) in [
+            ("(1d4+1", 6, 0, "\")\""),
+            ("1d4+1)", 5, 1, "an operator"),
+            ("()", 1, 1, "a number, dice, or \"(\""),
+            ("2*(1d8+(2d6)", 12, 0, "\")\""),
+        ] {
+            assert_eq!(
+                Err(syntax_error(expr, offset, len, Some(expected))),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_divide_by_zero_group() {
+        let expr = "1d6/(1d4-1)";
+
+        assert_eq!(
+            Err(syntax_error(expr, 4, 7, None)),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn display_parentheses() {
+        for expr in [
+            "(d4+1)*3",
+            "2*(d8+2d6)",
+            "d20-(d4-1)",
+            "(d6+1)/(d4+1)",
+            "d6+d4+1",
+        ] {
+            assert_eq!(expr, DiceExpr::try_from(expr).unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn roll_parentheses() {
+        let expr = DiceExpr::try_from("2*(1d8+2d6)").unwrap();
+        let result = expr.roll_with(|_| 3);
+
+        assert_eq!(18, result.total)
+    }
+
+    #[test]
+    fn try_from_str_nested() {
+        let expr = "(1d4)d6";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Roll(dice(1, 4), Box::new(Ast::Constant(6))),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_nested_invalid() {
+        for expr in ["(1d4-2)d6", "1d(1d6-1)", "(1d6)d(1d100000*1d100000)"] {
+            assert_eq!(
+                Err(syntax_error(expr, 0, expr.len(), None)),
+                DiceExpr::try_from(expr)
+            )
+        }
+
+        let expr = "(1d6)d";
+        assert_eq!(
+            Err(syntax_error(expr, 6, 0, Some("a number or \"(\""))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn display_nested() {
+        for expr in ["(d4)d6", "d(d20)", "2d(d4+2)", "(2d4)d(d6)"] {
+            assert_eq!(expr, DiceExpr::try_from(expr).unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn roll_nested() {
+        let expr = DiceExpr::try_from("(1d4)d(1d8)").unwrap();
+        let mut rolls = vec![3, 6, 2, 5, 4].into_iter();
+        let result = expr.roll_with(|_| rolls.next().unwrap());
+
+        assert_eq!(11, result.total);
+        assert_eq!(
+            vec!["d4", "d8", "3d6"],
+            result
+                .dice
+                .iter()
+                .map(|d| d.expr.as_str())
+                .collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn try_from_str_repeat() {
+        let expr = "3x(2d6+3)";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 3,
+                signed: false,
+                ast: Ast::Add(dice(2, 6), Box::new(Ast::Constant(3))),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_repeat_invalid() {
+        for (expr, offset, len, expected) in [
+            ("0x(2d6)", 0, 2, None),
+            ("3x2d6", 1, 1, Some("an operator")),
+            ("3x(2d6)+1", 7, 1, Some("an operator")),
+            ("2d6+3x(1d4)", 5, 1, Some("an operator")),
+        ] {
+            assert_eq!(
+                Err(syntax_error(expr, offset, len, expected)),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn display_repeat() {
+        let expr = DiceExpr::try_from("3x(2d6+3)").unwrap();
+
+        assert_eq!("3x(2d6+3)", expr.to_string())
+    }
+
+    #[test]
+    fn roll_repeated() {
+        let expr = DiceExpr::try_from("3x(1d20+5)").unwrap();
+        let mut rolls = vec![12, 3, 20].into_iter();
+        let results = expr.roll_repeated_with(&Vars::new(), |_| rolls.next().unwrap());
+
+        assert_eq!(
+            vec![17, 8, 25],
+            results.iter().map(|r| r.total).collect::<Vec<_>>()
+        )
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde() {
+        let expr = DiceExpr::try_from("4D6-L + 3").unwrap();
+        let json = serde_json::to_string(&expr).unwrap();
+
+        assert_eq!("\"4d6-L+3\"", json);
+        assert_eq!(expr, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<DiceExpr>("\"2d6+\"").is_err())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serde_roll_result() {
+        let expr = DiceExpr::try_from("4d6-L+1").unwrap();
+        let mut values = vec![3, 1, 6, 4].into_iter();
+        let result = expr.roll_with(|_| values.next().unwrap());
+        let json = serde_json::to_value(&result).unwrap();
+
+        assert_eq!("4d6-L+1", json["expression"]);
+        assert_eq!(14, json["total"]);
+        assert_eq!(1, json["modifier"]);
+        assert_eq!(serde_json::json!([1]), json["dice"][0]["dropped"]);
+        assert_eq!(6, json["dice"][0]["rolls"][2]["value"]);
+        assert!(json["timestamp"].as_u64().unwrap() > 0)
+    }
+
+    #[test]
+    fn roll_result_modifier() {
+        let expr = DiceExpr::try_from("2d6+4-1d4-1+2*3").unwrap();
+        let result = expr.roll_with(|_| 2);
+
+        assert_eq!("2d6+4-d4-1+2*3", result.expr);
+        assert_eq!(9, result.modifier);
+        assert_eq!(11, result.total)
+    }
+
+    #[test]
+    fn roll_with_rng() {
+        use rand::rngs::mock::StepRng;
+
+        let expr = DiceExpr::try_from("2d6+1").unwrap();
+        assert_eq!(
+            3,
+            expr.roll_with_rng(&mut StepRng::new(0, 0)).unwrap().total
+        )
+    }
+
+    #[test]
+    fn roll_with_roller() {
+        let expr = DiceExpr::try_from("2d6+1").unwrap();
+        assert_eq!(7, expr.roll_with_roller(&mut Fixed(3)).unwrap().total)
+    }
+
+    #[test]
+    fn rolls() {
+        let expr = DiceExpr::try_from("2d6+1").unwrap();
+        assert_eq!(
+            vec![7, 7, 7],
+            expr.rolls(Fixed(3))
+                .unwrap()
+                .take(3)
+                .map(|r| r.total)
+                .collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_into() {
+        let mut buffer = RollBuffer::new();
+
+        for expr in [
+            "4d6kh3+2",
+            "3d6!>5-1d4",
+            "2d20r1kl1",
+            "{1d8, 2d4, {3d6, 1d20}kl1}kh2",
+            "(1d4)d6/2",
+            "8d10>7f1",
+            "1d6-1d8",
+        ] {
+            let expr = DiceExpr::try_from(expr).unwrap();
+            let result = expr.roll_with_roller(&mut SeededRoller::new(8)).unwrap();
+
+            assert_eq!(
+                Ok(result.total),
+                expr.roll_into(&mut SeededRoller::new(8), &mut buffer)
+            );
+            assert!(result.dice.iter().flat_map(|d| &d.rolls).eq(buffer.rolls()))
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn checked_roll() {
+        let expr = DiceExpr::try_from("500d1000+5000").unwrap();
+        assert!((5500..=505_000).contains(&expr.checked_roll().unwrap().total));
+
+        /// Rolls the given values in turn.
+        struct Script(Vec<u32>);
+
+        impl Roller for Script {
+            fn roll_die(&mut self, _: u32) -> u32 {
+                self.0.remove(0)
+            }
+        }
+
+        let exploding = DiceExpr::try_from("1d6!*1000000000000000000").unwrap();
+        assert_eq!(
+            Err(DiceExprError::Overflow(exploding.to_string())),
+            exploding
+                .checked_roll_with_roller(&mut Script(vec![6, 6, 1]))
+                .map(|r| r.total)
+        );
+        assert_eq!(
+            1_000_000_000_000_000_000,
+            exploding
+                .checked_roll_with_roller(&mut Fixed(1))
+                .unwrap()
+                .total
+        )
+    }
+
+    #[test]
+    fn display_result() {
+        let result = DiceExpr::try_from("3d6kl2+2")
+            .unwrap()
+            .roll_with_roller(&mut Fixed(4))
+            .unwrap();
+
+        assert_eq!("3d6kl2+2: 10", result.to_string());
+        assert_eq!("3d6kl2+2: [4, 4, ~4~] + 2 = 10", format!("{:#}", result));
+
+        for (expr, display) in [
+            ("2d6+1d4-1", "2d6+d4-1: 2d6 [4, 4], d4 [4] - 1 = 11"),
+            ("1d8", "d8: [4] = 4"),
+            ("5", "5: 5 = 5"),
+        ] {
+            let result = DiceExpr::try_from(expr)
+                .unwrap()
+                .roll_with_roller(&mut Fixed(4))
+                .unwrap();
+            assert_eq!(display, format!("{:#}", result))
+        }
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn sum_totals() {
+        let results = DiceExpr::try_from("3x(1d8+2)")
+            .unwrap()
+            .roll_repeated()
+            .unwrap();
+
+        assert_eq!(
+            RollTotal {
+                total: results.iter().map(|r| r.total).sum(),
+                rolls: 3,
+            },
+            results.iter().sum()
+        );
+        assert_eq!(
+            results.iter().sum::<RollTotal>(),
+            results.into_iter().sum::<RollTotal>()
+        );
+        assert_eq!(RollTotal::default(), Vec::<RollResult>::new().iter().sum())
+    }
+
+    #[test]
+    fn extend() {
+        let attack = DiceExpr::try_from("1d20+5 dc12").unwrap();
+        let damage = DiceExpr::try_from("2d6+3").unwrap();
+        let mut result = attack.roll_with_roller(&mut Fixed(4)).unwrap();
+        result.extend([
+            damage.roll_with_roller(&mut Fixed(4)).unwrap(),
+            damage.roll_with_roller(&mut Fixed(1)).unwrap(),
+        ]);
+
+        assert_eq!(format!("{}; {}; {}", attack, damage, damage), result.expr);
+        assert_eq!(9 + 11 + 5, result.total);
+        assert_eq!(5 + 3 + 3, result.modifier);
+        assert_eq!(3, result.dice.len());
+        assert_eq!(None, result.dc)
+    }
+
+    #[test]
+    fn result_hash_key() {
+        use std::collections::HashSet;
+        use std::time::Duration;
+
+        let expr = DiceExpr::try_from("2d6+3").unwrap();
+        let first = expr.roll_with_roller(&mut Fixed(4)).unwrap();
+        let mut later = expr.roll_with_roller(&mut Fixed(4)).unwrap();
+        later.timestamp += Duration::from_secs(60);
+
+        let seen = HashSet::from([first]);
+        assert!(seen.contains(&later));
+        assert!(!seen.contains(&expr.roll_with_roller(&mut Fixed(3)).unwrap()))
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn roll_n() {
+        let results = DiceExpr::try_from("2d6").unwrap().roll_n(1000).unwrap();
+
+        assert_eq!(1000, results.len());
+        assert!(results.iter().all(|r| (2..=12).contains(&r.total)))
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn simulate() {
+        let expr = DiceExpr::try_from("2d6").unwrap();
+        let simulated = expr.simulate(100_000).unwrap().unwrap();
+
+        assert!((simulated.iter().map(|(_, p)| p).sum::<f64>() - 1.0).abs() < 1e-9);
+        assert!((simulated.pmf(7) - 6.0 / 36.0).abs() < 0.01);
+        assert!((2..=12).contains(&simulated.min()));
+        assert_eq!(Ok(None), expr.simulate(0))
+    }
+
+    #[test]
+    fn try_from_str_stats() {
+        for expr in ["stats", "abilities", " stats "] {
+            assert_eq!(DiceExpr::try_from("6x(4d6-L)"), DiceExpr::try_from(expr))
+        }
+
+        let expr = "stats+1";
+        assert_eq!(
+            Err(syntax_error(expr, 0, 1, Some("a number, dice, or \"(\""))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn ast() {
+        let expr = DiceExpr::try_from("3x(4d6kh3[str]+2)").unwrap();
+
+        assert_eq!(3, expr.repeat());
+        match expr.ast() {
+            Ast::Add(a, b) => match (a.as_ref(), b.as_ref()) {
+                (Ast::Dice(d), Ast::Constant(2)) => {
+                    assert_eq!(
+                        (4, &Sides::Number(6), &Keep::High(3), Some("str")),
+                        (d.count(), d.sides(), d.keep(), d.label())
+                    )
+                }
+                _ => panic!("unexpected operands"),
+            },
+            ast => panic!("unexpected node {}", ast),
+        }
+    }
+
+    #[test]
+    fn normalize() {
+        for (expr, canonical) in [
+            ("1d6+2+3", "d6+5"),
+            ("2+1d4-1d6+1d8+1", "d8+d4-d6+3"),
+            ("3-1d4", "3-d4"),
+            ("0-1d4-3", "0-d4-3"),
+            ("2d6-4+4", "2d6"),
+            ("(1+2)*1d6+$str", "$str+3*d6"),
+            ("1d6+1/2", "d6+1/2"),
+            ("(1+1d4)d(2*3)", "(d4+1)d6"),
+            ("{1+1d6,1d8}kh1", "{d6+1,d8}kh1"),
+            ("2x(1+1d6) vs 10", "2x(d6+1) dc10"),
+        ] {
+            for expr in [expr, canonical] {
+                assert_eq!(
+                    canonical,
+                    DiceExpr::try_from(expr).unwrap().normalize().to_string()
+                )
+            }
+        }
+    }
+
+    #[test]
+    fn cmp_by_mean() {
+        let mut weapons: Vec<DiceExpr> = ["2d6", "1d12+1", "1d4+$str", "1d8", "1d4-2", "2d4+1"]
+            .iter()
+            .map(|s| DiceExpr::try_from(*s).unwrap())
+            .collect();
+        DiceExpr::sort_by_mean(&mut weapons);
+
+        assert_eq!(
+            vec!["d4-2", "d8", "2d4+1", "2d6", "d12+1", "d4+$str"],
+            weapons.iter().map(|w| w.to_string()).collect::<Vec<_>>()
+        );
+        assert_eq!(Ordering::Less, weapons[1].cmp_by_mean(&weapons[2]))
+    }
+
+    #[test]
+    fn simplify() {
+        for (expr, simplified) in [
+            ("2d6+1d1+3", "2d6+4"),
+            ("1d1+1d1", "2"),
+            ("3d1kh2*1d6", "2*d6"),
+            ("(1d1)d6", "d6"),
+            ("(1+1)d(3*1d1)", "2d3"),
+            ("{1d1,1d4}kh1", "{1,d4}kh1"),
+            ("1d1[fire]+1", "d1[fire]+1"),
+            ("1d20+5", "d20+5"),
+        ] {
+            assert_eq!(
+                simplified,
+                DiceExpr::try_from(expr).unwrap().simplify().to_string()
+            )
+        }
+
+        let expr = DiceExpr::try_from("1d4-2d1").unwrap().simplify();
+        assert_eq!("d4-2", expr.to_string());
+        assert_eq!(-1, expr.roll_with_roller(&mut Fixed(1)).unwrap().total)
+    }
+
+    #[test]
+    fn try_from_str_pool() {
+        let expr = "{2d6, 1d8,1d10}kh1";
+
+        assert_eq!(
+            Ok(DiceExpr {
+                repeat: 1,
+                signed: false,
+                ast: Ast::Group(
+                    vec![*dice(2, 6), *dice(1, 8), *dice(1, 10)],
+                    Keep::High(1),
+                    Drop::None
+                ),
+                dc: None,
+            }),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_pool_invalid() {
+        for (expr, offset, len, expected) in [
+            ("{2d6,1d8}kh3", 0, 12, None),
+            ("{2d6,1d8}dl2", 0, 12, None),
+            ("{2d6,1d8", 8, 0, Some("\",\" or \"}\"")),
+            ("{2d6 1d8}", 5, 1, Some("\",\" or \"}\"")),
+            ("{}", 1, 1, Some("a number, dice, or \"(\"")),
+        ] {
+            assert_eq!(
+                Err(syntax_error(expr, offset, len, expected)),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn display_pool() {
+        for expr in ["{2d6,d8,d10}kh1", "{d20+5,d20+3}dl1", "{3d6,3d6}*2"] {
+            assert_eq!(expr, DiceExpr::try_from(expr).unwrap().to_string())
+        }
+    }
+
+    #[test]
+    fn roll_pool() {
+        let expr = DiceExpr::try_from("{2d6,1d8,1d10}kh1").unwrap();
+        let mut rolls = vec![3, 4, 6, 9].into_iter();
+        let result = expr.roll_with(|_| rolls.next().unwrap());
+
+        assert_eq!(9, result.total);
+        assert_eq!(3, result.dice.len())
+    }
+
+    #[test]
+    fn roll_pool_drop() {
+        let expr = DiceExpr::try_from("{1d20+5,1d20+3}dl1").unwrap();
+        let mut rolls = vec![14, 17].into_iter();
+        let result = expr.roll_with(|_| rolls.next().unwrap());
+
+        assert_eq!(20, result.total)
+    }
+
+    #[test]
+    fn try_from_str_advantage() {
+        for (expr, canonical) in [
+            ("adv", "2d20kh1"),
+            ("d20a+5", "2d20kh1+5"),
+            ("dis", "2d20kl1"),
+            ("d20d-1", "2d20kl1-1"),
+        ] {
+            assert_eq!(
+                DiceExpr::try_from(canonical),
+                DiceExpr::try_from(expr),
+                "{}",
+                expr
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_advantage_invalid() {
+        for (expr, offset, expected) in [
+            ("adv2", 0, "a number, dice, or \"(\""),
+            ("d20ad", 3, "an operator"),
+            ("disx", 1, "a number or \"(\""),
+        ] {
+            assert_eq!(
+                Err(syntax_error(expr, offset, 1, Some(expected))),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_labels() {
+        let expr = DiceExpr::try_from(" 2d6[fire] + 1d4[cold]  ").unwrap();
+
+        assert_eq!("2d6[fire]+d4[cold]", expr.to_string())
+    }
+
+    #[test]
+    fn roll_labels() {
+        let expr = DiceExpr::try_from("2d6[fire]+1d4[cold]+1d6[fire]+2").unwrap();
+        let result = expr.roll_with(|_| 3);
+
+        assert_eq!(14, result.total);
+        assert_eq!(vec![("fire", 9), ("cold", 3)], result.label_totals())
+    }
+
+    #[test]
+    fn try_from_str_roll_and_keep() {
+        let expr = DiceExpr::try_from("5k3+2").unwrap();
+
+        assert_eq!(DiceExpr::try_from("5d10!kh3+2").unwrap(), expr);
+        assert_eq!("5d10!kh3+2", expr.to_string())
+    }
+
+    #[test]
+    fn try_from_str_roll_and_keep_too_many() {
+        let expr = "3k5";
+
+        assert_eq!(
+            Err(syntax_error(expr, 0, 3, None)),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_roll_and_keep_errors() {
+        assert_eq!(
+            Err(DiceExprError::EmptyDice(String::from("0k3"))),
+            DiceExpr::try_from("0k3")
+        );
+        assert!(matches!(
+            DiceExpr::try_from("99999999999k3"),
+            Err(DiceExprError::ParseIntError(_))
+        ))
+    }
+
+    #[test]
+    fn parse_roll20_invalid() {
+        let expr = "[[1d20+5";
+        assert_eq!(
+            Err(syntax_error(expr, 8, 0, Some("\"]]\""))),
+            DiceExpr::parse(expr, Dialect::Roll20)
+        );
+
+        for expr in ["/roll 1d20", "1d20+@{prof}"] {
+            assert!(DiceExpr::parse(expr, Dialect::Standard).is_err())
+        }
+    }
+
+    #[test]
+    fn parse_anydice_unsupported() {
+        for (expr, construct) in [
+            ("function: test X:n { result: X }", "function"),
+            ("X: 3d6", "variable assignment"),
+            ("output [highest 1 of 2d20]", "function call"),
+            ("output {1,2,3}", "sequence"),
+            ("output 3d6 + X", "variable"),
+            ("output 3d6 >= 10", "comparison"),
+            ("output 3d6>=10", "comparison"),
+        ] {
+            assert_eq!(
+                Err(DiceExprError::Unsupported(String::from(construct))),
+                DiceExpr::parse(expr, Dialect::AnyDice)
+            )
+        }
+
+        let expr = "3d6";
+        assert_eq!(
+            Err(syntax_error(expr, 0, 1, Some("\"output\""))),
+            DiceExpr::parse(expr, Dialect::AnyDice)
+        )
+    }
+
+    #[test]
+    fn step_up_and_down() {
+        for (expr, up, down) in [
+            ("2d6+1", "2d7+1", "2d5+1"),
+            ("d20+d3", "d24+d4", "d16+d3"),
+            ("d30!", "d30!", "d24!"),
+            ("d9+dF", "d9+dF", "d9+dF"),
+            ("d4r<4", "d5r<4", "d4r<4"),
+            ("d4!<4", "d5!<4", "d4!<4"),
+        ] {
+            let dice = DiceExpr::try_from(expr).unwrap();
+            assert_eq!(up, dice.step_up().to_string());
+
+            let dice = DiceExpr::try_from(expr).unwrap();
+            assert_eq!(down, dice.step_down().to_string());
+        }
+    }
+
+    #[test]
+    fn parse_all() {
+        let exprs = DiceExpr::parse_all("2d6+3; d20, {1d6,1d8}kh1;d[1,2];", Dialect::Standard);
+
+        assert_eq!(
+            vec!["2d6+3", "d20", "{d6,d8}kh1", "d[1,2]"],
+            exprs
+                .unwrap()
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn parse_all_invalid() {
+        assert_eq!(
+            Err(syntax_error("4x", 1, 1, Some("an operator"))),
+            DiceExpr::parse_all("2d6; 4x", Dialect::Standard)
+        );
+        assert_eq!(
+            Err(DiceExprError::Expr(String::from(" ; "))),
+            DiceExpr::parse_all(" ; ", Dialect::Standard)
+        )
+    }
+
+    #[test]
+    fn parse_with_options() {
+        let options = ParseOptions {
+            max_dice: Some(100),
+            max_sides: Some(1000),
+            max_terms: Some(4),
+        };
+
+        for expr in ["100d1000", "2x(50d6)", "(1d10)d(1d100)+1", "{d6,d8}kh1"] {
+            assert!(DiceExpr::parse_with_options(expr, Dialect::Standard, options).is_ok())
+        }
+
+        for expr in [
+            "99999d99999",
+            "101d6",
+            "3x(50d6)",
+            "d1001",
+            "(1d10)d(1d1001)",
+            "1d6+1d6+1d6+1d6+1",
+            "d4000000000!<3999999999",
+            "101c",
+            "101k3",
+            "step 1000",
+            "51x(adv)",
+        ] {
+            assert_eq!(
+                Err(DiceExprError::Limit(String::from(expr))),
+                DiceExpr::parse_with_options(expr, Dialect::Standard, options)
+            )
+        }
+    }
+
+    #[test]
+    fn parse_all_with_options() {
+        let options = ParseOptions {
+            max_dice: Some(10),
+            ..ParseOptions::default()
+        };
+
+        assert_eq!(
+            2,
+            DiceExpr::parse_all_with_options("2d6; 10d4", Dialect::Standard, options)
+                .unwrap()
+                .len()
+        );
+        assert_eq!(
+            Err(DiceExprError::Limit(String::from("11d4"))),
+            DiceExpr::parse_all_with_options("2d6; 11d4", Dialect::Standard, options)
+        )
+    }
+
+    #[test]
+    fn roll_world_of_darkness() {
+        let expr = DiceExpr::parse("5d10", Dialect::WorldOfDarkness { difficulty: 6 }).unwrap();
+        let mut rolls = vec![10, 7, 1, 6, 3, 1].into_iter();
+        let result = expr.roll_with(|_| rolls.next().unwrap());
+
+        assert_eq!(1, result.total)
+    }
+
+    #[test]
+    fn try_from_str_dc() {
+        for expr in ["d20+7 dc15", "d20+7 DC 15", "d20+7vs15", "d20+7 VS 15"] {
+            assert_eq!("d20+7 dc15", DiceExpr::try_from(expr).unwrap().to_string())
+        }
+
+        let expr = "d20+7 dc";
+        assert_eq!(
+            Err(syntax_error(expr, 6, 1, Some("an operator"))),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn roll_dc() {
+        let expr = DiceExpr::try_from("d20+7 vs 15").unwrap();
+        let check = |value| expr.roll_with(|_| value).dc.unwrap();
+
+        assert_eq!(
+            DcCheck {
+                dc: 15,
+                margin: 3,
+                success: true
+            },
+            check(11)
+        );
+        assert_eq!((false, -1), (check(7).success, check(7).margin))
+    }
+
+    #[test]
+    fn skill_check() {
+        let expr = DiceExpr::try_from("3d6").unwrap();
+        let check = |values: [u32; 3], skill| {
+            let mut values = values.into_iter();
+            let check = expr
+                .roll_with(|_| values.next().unwrap())
+                .skill_check(skill);
+            (check.success, check.critical, check.margin)
+        };
+
+        assert_eq!((true, false, 3), check([3, 4, 2], 12));
+        assert_eq!((false, false, -2), check([6, 4, 4], 12));
+        assert_eq!((true, true, 0), check([1, 1, 2], 4));
+        assert_eq!((false, true, 1), check([6, 6, 5], 18))
+    }
+
+    #[test]
+    fn skill_check_not_3d6() {
+        let check = |expr, value| {
+            let expr = DiceExpr::try_from(expr).unwrap();
+            let check = expr.roll_with(|_| value).skill_check(18);
+            (check.success, check.critical)
+        };
+
+        assert_eq!((true, false), check("d20", 17));
+        assert_eq!((true, false), check("3d6+1", 5));
+        assert_eq!((true, false), check("3d6*2", 1));
+        assert_eq!((true, false), check("3d6!", 1));
+        assert_eq!((true, false), check("d20", 3))
+    }
+
+    #[test]
+    fn skill_check_suffix() {
+        for expr in ["3d6 dc12", "3d6 vs 10", "3x(3d6)"] {
+            let expr = DiceExpr::try_from(expr).unwrap();
+            let check = expr.roll_with(|_| 1).skill_check(12);
+
+            assert_eq!((true, true), (check.success, check.critical))
+        }
+    }
+
+    #[test]
+    fn try_from_str_step() {
+        for (expr, canonical) in [
+            ("step 1", "d4!-2"),
+            ("step7", "d12!"),
+            ("step 9", "d8!+d6!"),
+            ("step 12+2", "2d10!+2"),
+            ("step 15", "d12!+2d6!"),
+            ("step 22*2", "(2d12!+2d6!)*2"),
+            ("step 100", "13d12!+d8!+d6!"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::try_from(expr).unwrap().to_string(),
+                "{}",
+                expr
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_step_zero() {
+        let expr = "step 0";
+
+        assert_eq!(
+            Err(syntax_error(expr, 0, 6, None)),
+            DiceExpr::try_from(expr)
+        )
+    }
+
+    #[test]
+    fn try_from_str_step_too_high() {
+        for (expr, len) in [("step 1001", 9), ("step 4000000000", 15)] {
+            assert_eq!(
+                Err(syntax_error(expr, 0, len, None)),
+                DiceExpr::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_floor() {
+        let expr = DiceExpr::try_from("floor(3d6/2)*2").unwrap();
+
+        assert_eq!(8, expr.roll_with(|_| 3).total)
+    }
+
+    #[test]
+    fn roll_abs() {
+        let expr = DiceExpr::try_from("abs(1d6-1d6)").unwrap();
+        let mut rolls = vec![2, 5].into_iter();
+
+        assert_eq!(0..=5, expr.ast.range());
+        assert_eq!(3, expr.roll_with(|_| rolls.next().unwrap()).total)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn try_from_str_variables() {
+        let expr = DiceExpr::try_from("1d20+$str+$prof").unwrap();
+
+        assert_eq!(vec!["str", "prof"], expr.ast.variables());
+        assert_eq!("d20+$str+$prof", expr.to_string())
+    }
+
+    #[test]
+    fn roll_variables() {
+        let expr = DiceExpr::try_from("1d20+$str*2").unwrap();
+        let vars = Vars::from([(String::from("str"), 4)]);
+
+        assert_eq!(18, expr.roll_vars_with(&vars, |_| 10).total)
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn roll_variables_missing() {
+        let expr = DiceExpr::try_from("1d20+$str+$dex").unwrap();
+        let vars = HashMap::from([(String::from("str"), 4)]);
+
+        assert_eq!(
+            Err(DiceExprError::Variable(String::from("dex"))),
+            expr.roll_with_vars(&vars)
+        )
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn roll_variable_divisor_and_count() {
+        let expr = DiceExpr::try_from("1d6/$x+($prof)d4").unwrap();
+        let vars = |x, prof| HashMap::from([(String::from("x"), x), (String::from("prof"), prof)]);
+
+        assert_eq!(vec!["x", "prof"], expr.variables());
+        assert!(expr.check_vars(&vars(2, 1)).is_ok());
+        assert_eq!(6, expr.roll_vars_with(&vars(2, 1), |_| 4).total);
+
+        for (x, prof) in [(0, 1), (2, -1)] {
+            assert_eq!(
+                Err(DiceExprError::Expr(expr.to_string())),
+                expr.roll_with_vars(&vars(x, prof))
+            )
+        }
+    }
+
+    #[test]
+    fn roll_variables_as_zero() {
+        for expr in ["1d6/$x", "2d($x)"] {
+            let expr = DiceExpr::try_from(expr).unwrap();
+            let err = Some(DiceExprError::Expr(expr.to_string()));
+
+            assert_eq!(err, expr.roll().err());
+            assert_eq!(err, expr.checked_roll().err());
+            assert_eq!(err, expr.roll_repeated().err());
+            assert_eq!(err, expr.roll_n(3).err());
+            assert_eq!(err, expr.simulate(3).err());
+            assert_eq!(
+                err,
+                expr.roll_into(&mut Fixed(1), &mut RollBuffer::new()).err()
+            );
+            assert_eq!(err, expr.rolls(Fixed(1)).err());
+            assert_eq!(err, SeededRoller::new(1).roll(&expr).err());
+        }
+
+        let expr = DiceExpr::try_from("1d6+$x").unwrap();
+        assert_eq!(4, expr.roll_with_roller(&mut Fixed(4)).unwrap().total)
+    }
+
+    #[test]
+    fn try_from_str_whitespace() {
+        for (expr, canonical) in [
+            ("2d6 + 3", "2d6+3"),
+            ("\t1d20 +\n$str ", "d20+$str"),
+            ("( 1d4 + 1 ) * 3", "(d4+1)*3"),
+            ("ceil ( 1d100 / 10 )", "ceil(d100/10)"),
+            ("3 x ( 2d6 + 3 )", "3x(2d6+3)"),
+            ("{ 2d6 , 1d8 } kh1", "{2d6,d8}kh1"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::try_from(expr).unwrap().to_string(),
+                "{:?}",
+                expr
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_lenient() {
+        for (expr, canonical) in [
+            ("4D6", "4d6"),
+            ("2D20kh1+D4", "2d20kh1+d4"),
+            ("(1D4)D6", "(d4)d6"),
+            ("4dF+0", "4dF"),
+            ("1d20+-2", "d20-2"),
+            ("1d20 - +2", "d20-2"),
+            ("1d6[Damage]", "d6[Damage]"),
+        ] {
+            assert_eq!(
+                canonical,
+                DiceExpr::try_from(expr).unwrap().to_string(),
+                "{}",
+                expr
+            )
+        }
+    }
+
+    #[test]
+    fn try_from_str_lenient_error() {
+        let expr = "4D6+";
+
+        assert_eq!(
+            Err(syntax_error(expr, 4, 0, Some("a number, dice, or \"(\""))),
             DiceExpr::try_from(expr)
         )
     }
@@ -0,0 +1,224 @@
+#[cfg(feature = "std")]
+use crate::audit::RollSource;
+use crate::entropy::{self, EntropySource};
+use crate::expr::{DiceExpr, DiceExprError, RollResult};
+use crate::roller::Roller;
+use alloc::format;
+use alloc::string::{String, ToString};
+use core::fmt::{self, Debug, Formatter};
+#[cfg(feature = "std")]
+use rand::rngs::OsRng;
+#[cfg(feature = "std")]
+use rand::TryRngCore;
+use sha2::{Digest, Sha256};
+
+/// Rolls dice that neither the roller nor the player can rig, by the
+/// commit–reveal scheme of provably fair games.
+///
+/// The roller picks a secret server seed and publishes its commitment, the
+/// SHA-256 hash of the seed. The player then picks a client seed, and each
+/// die is rolled from the SHA-256 hashes of both seeds and a counter. Once
+/// the rolls are done the roller reveals the server seed, and anyone can
+/// check it against the commitment and roll the same dice from the same
+/// seeds with `with_server_seed`.
+///
+/// The roller can't be cloned, so that no copy of it can roll ahead with the
+/// server seed before it's revealed.
+#[derive(PartialEq, Eq)]
+pub struct FairRoller {
+    server_seed: [u8; 32],
+    client_seed: String,
+    counter: u64,
+}
+
+impl FairRoller {
+    /// A roller with a server seed from the operating system's generator.
+    /// Panics if the operating system can't provide one.
+    #[cfg(feature = "std")]
+    pub fn new() -> Self {
+        let mut server_seed = [0; 32];
+        OsRng
+            .try_fill_bytes(&mut server_seed)
+            .expect("the operating system's generator failed");
+
+        FairRoller::with_server_seed(server_seed)
+    }
+
+    /// A roller with a known server seed, such as one revealed after
+    /// rolling, for replaying its rolls.
+    pub fn with_server_seed(server_seed: [u8; 32]) -> Self {
+        FairRoller {
+            server_seed,
+            client_seed: String::new(),
+            counter: 0,
+        }
+    }
+
+    /// The hash of the server seed, in hex, to publish before the player
+    /// picks a client seed.
+    pub fn commitment(&self) -> String {
+        hex(&Sha256::digest(self.server_seed))
+    }
+
+    /// Sets the player's seed, starting the rolls from it over.
+    pub fn set_client_seed(&mut self, client_seed: &str) {
+        self.client_seed = client_seed.to_string();
+        self.counter = 0;
+    }
+
+    /// Rolls the expression once, ignoring any repeat count and taking any
+    /// variables as zero.
+    pub fn roll(&mut self, expr: &DiceExpr) -> Result<RollResult, DiceExprError> {
+        expr.roll_with_roller(self)
+    }
+
+    /// Ends the rolls, giving up the server seed so that they can be
+    /// checked.
+    pub fn reveal(self) -> [u8; 32] {
+        self.server_seed
+    }
+
+    /// Whether `server_seed` is the one that `commitment` was published
+    /// for.
+    pub fn verify(commitment: &str, server_seed: &[u8; 32]) -> bool {
+        commitment.eq_ignore_ascii_case(&hex(&Sha256::digest(server_seed)))
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn source(&self) -> RollSource {
+        RollSource::Fair {
+            commitment: self.commitment(),
+            client_seed: self.client_seed.clone(),
+            counter: self.counter,
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for FairRoller {
+    fn default() -> Self {
+        FairRoller::new()
+    }
+}
+
+/// Shows the commitment in place of the server seed, which stays secret
+/// until it's revealed.
+impl Debug for FairRoller {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("FairRoller")
+            .field("commitment", &self.commitment())
+            .field("client_seed", &self.client_seed)
+            .field("counter", &self.counter)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Draws the first eight bytes of the hash of the server seed, the counter
+/// and the client seed, in that order, then counts up.
+impl EntropySource for FairRoller {
+    fn next_u64(&mut self) -> u64 {
+        let hash = Sha256::new()
+            .chain_update(self.server_seed)
+            .chain_update(self.counter.to_le_bytes())
+            .chain_update(self.client_seed.as_bytes())
+            .finalize();
+        self.counter += 1;
+
+        u64::from_le_bytes(hash[..8].try_into().unwrap())
+    }
+}
+
+impl Roller for FairRoller {
+    fn roll_die(&mut self, sides: u32) -> u32 {
+        entropy::uniform(self, sides)
+    }
+}
+
+/// Writes bytes as lowercase hex, as seeds and commitments are shown.
+pub fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Reads 32 bytes, such as a server seed, from the 64 hex digits they're
+/// shown as.
+pub fn parse_seed(s: &str) -> Option<[u8; 32]> {
+    let mut seed = [0; 32];
+    if s.len() != 64 || !s.is_ascii() {
+        return None;
+    }
+    for (i, byte) in seed.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+
+    Some(seed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    #[test]
+    fn commitment() {
+        let roller = FairRoller::with_server_seed([0; 32]);
+
+        assert_eq!(
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+            roller.commitment()
+        );
+        assert!(FairRoller::verify(&roller.commitment(), &roller.reveal()));
+        assert!(!FairRoller::verify(
+            "66687aadf862bd776c8fc18b8e9f8e20089714856ee233b3902a591d0d5f2925",
+            &[1; 32]
+        ))
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replay() {
+        let expr = DiceExpr::try_from("4d6kh3+1d20").unwrap();
+        let mut roller = FairRoller::new();
+        let commitment = roller.commitment();
+        roller.set_client_seed("player");
+        let rolled = roller.roll(&expr);
+
+        let server_seed = roller.reveal();
+        assert!(FairRoller::verify(&commitment, &server_seed));
+
+        let mut replay = FairRoller::with_server_seed(server_seed);
+        replay.set_client_seed("player");
+        assert_eq!(rolled, replay.roll(&expr))
+    }
+
+    #[test]
+    fn client_seed() {
+        let mut a = FairRoller::with_server_seed([7; 32]);
+        let mut b = FairRoller::with_server_seed([7; 32]);
+        a.set_client_seed("a");
+        b.set_client_seed("b");
+
+        assert_ne!(
+            (0..4).map(|_| a.next_u64()).collect::<Vec<_>>(),
+            (0..4).map(|_| b.next_u64()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn debug_redacts_server_seed() {
+        let roller = FairRoller::with_server_seed([0xab; 32]);
+        let debug = format!("{:?}", roller);
+
+        assert!(!debug.contains("171"));
+        assert!(!debug.contains(&hex(&[0xab; 32])));
+        assert!(debug.contains(&roller.commitment()))
+    }
+
+    #[test]
+    fn parse_seed_hex() {
+        let seed = [0xab; 32];
+
+        assert_eq!(Some(seed), parse_seed(&hex(&seed)));
+        assert_eq!(None, parse_seed("ab"));
+        assert_eq!(None, parse_seed(&"zz".repeat(32)))
+    }
+}
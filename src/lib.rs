@@ -1,2 +1,82 @@
-mod die;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+// For the `dice!` macro's expansions in tests, which name the crate.
+#[cfg(all(test, feature = "macros"))]
+extern crate self as diceroll;
+
+#[cfg(feature = "proptest")]
+pub mod arbitrary;
+mod ast;
+#[cfg(feature = "std")]
+pub mod audit;
+#[cfg(feature = "std")]
+pub mod cache;
+mod compare;
+#[cfg(feature = "std")]
+pub mod cortex;
+mod dialect;
+pub mod die;
+pub mod distribution;
+#[cfg(feature = "drand")]
+pub mod drand;
+pub mod entropy;
 pub mod expr;
+pub mod fair;
+#[cfg(feature = "std")]
+pub mod narrative;
+mod parser;
+pub mod presets;
+mod ratio;
+pub mod roller;
+pub mod seeded;
+pub mod session;
+mod token;
+pub mod weighted;
+
+#[cfg(feature = "macros")]
+pub use diceroll_macros::dice;
+pub use die::Die;
+#[doc(hidden)]
+pub use expr::__private;
+pub use expr::{DiceExpr, DiceExprError, RollResult};
+
+#[cfg(all(test, feature = "macros"))]
+mod tests {
+    use super::*;
+
+    macro_rules! assert_expands {
+        ($($expr:literal),*) => {
+            $(assert_eq!(DiceExpr::try_from($expr), Ok(dice!($expr)));)*
+        };
+    }
+
+    #[test]
+    fn dice_macro() {
+        assert_expands!(
+            "4d6kh3",
+            "4d6-L",
+            "2d20km1",
+            "2d6[fire]+1d4[cold]",
+            "{2d6,1d8,1d10}kh1",
+            "ceil(1d100/10)*2",
+            "(1d4)d6-1",
+            "3x(2d6+3)",
+            "d20+7 dc15",
+            "d20+$str",
+            "d[0,0,1,1,2]",
+            "d66",
+            "4dF",
+            "2d%",
+            "dC",
+            "d10!>8",
+            "d%oe",
+            "2d6ro<=2",
+            "8d6min2max5kh3",
+            "10d10>=7t10f1",
+            "d20cs>19cf<2",
+            "4d6msd",
+            "4d6++1"
+        );
+    }
+}
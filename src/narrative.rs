@@ -0,0 +1,407 @@
+use crate::die::Die;
+use crate::expr::DiceExprError;
+use crate::roller::Roller;
+use lazy_static::lazy_static;
+use rand::thread_rng;
+use regex::Regex;
+use std::convert::TryFrom;
+use std::fmt::{self, Display, Formatter};
+use std::ops::Add;
+
+/// The symbols shown on one or more faces of narrative dice, counted up to
+/// `u32::MAX` of each.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct Symbols {
+    pub success: u32,
+    pub failure: u32,
+    pub advantage: u32,
+    pub threat: u32,
+    pub triumph: u32,
+    pub despair: u32,
+}
+
+impl Symbols {
+    /// Cancels successes against failures and advantages against threats.
+    /// A triumph also counts as a success and a despair as a failure, but
+    /// both are kept whatever they cancel.
+    pub fn net(&self) -> Self {
+        let success = self.success.saturating_add(self.triumph);
+        let failure = self.failure.saturating_add(self.despair);
+
+        Symbols {
+            success: success.saturating_sub(failure),
+            failure: failure.saturating_sub(success),
+            advantage: self.advantage.saturating_sub(self.threat),
+            threat: self.threat.saturating_sub(self.advantage),
+            ..*self
+        }
+    }
+}
+
+impl Add for Symbols {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Symbols {
+            success: self.success.saturating_add(other.success),
+            failure: self.failure.saturating_add(other.failure),
+            advantage: self.advantage.saturating_add(other.advantage),
+            threat: self.threat.saturating_add(other.threat),
+            triumph: self.triumph.saturating_add(other.triumph),
+            despair: self.despair.saturating_add(other.despair),
+        }
+    }
+}
+
+impl Display for Symbols {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let symbols: Vec<String> = [
+            (self.success, "success"),
+            (self.failure, "failure"),
+            (self.advantage, "advantage"),
+            (self.threat, "threat"),
+            (self.triumph, "triumph"),
+            (self.despair, "despair"),
+        ]
+        .iter()
+        .filter(|(n, _)| *n > 0)
+        .map(|(n, name)| format!("{} {}", n, name))
+        .collect();
+
+        match symbols.is_empty() {
+            true => write!(f, "blank"),
+            false => write!(f, "{}", symbols.join(", ")),
+        }
+    }
+}
+
+/// Builds a face from its symbols, written as they are on the dice: `S`
+/// success, `F` failure, `A` advantage, `T` threat, `R` triumph, and `D`
+/// despair.
+const fn face(symbols: &str) -> Symbols {
+    let symbols = symbols.as_bytes();
+    let mut face = Symbols {
+        success: 0,
+        failure: 0,
+        advantage: 0,
+        threat: 0,
+        triumph: 0,
+        despair: 0,
+    };
+
+    let mut i = 0;
+    while i < symbols.len() {
+        match symbols[i] {
+            b'S' => face.success += 1,
+            b'F' => face.failure += 1,
+            b'A' => face.advantage += 1,
+            b'T' => face.threat += 1,
+            b'R' => face.triumph += 1,
+            b'D' => face.despair += 1,
+            _ => panic!("unknown symbol"),
+        }
+        i += 1;
+    }
+
+    face
+}
+
+const BOOST: [Symbols; 6] = [
+    face(""),
+    face(""),
+    face("S"),
+    face("SA"),
+    face("AA"),
+    face("A"),
+];
+
+const SETBACK: [Symbols; 6] = [
+    face(""),
+    face(""),
+    face("F"),
+    face("F"),
+    face("T"),
+    face("T"),
+];
+
+const ABILITY: [Symbols; 8] = [
+    face(""),
+    face("S"),
+    face("S"),
+    face("SS"),
+    face("A"),
+    face("A"),
+    face("SA"),
+    face("AA"),
+];
+
+const DIFFICULTY: [Symbols; 8] = [
+    face(""),
+    face("F"),
+    face("FF"),
+    face("T"),
+    face("T"),
+    face("T"),
+    face("TT"),
+    face("FT"),
+];
+
+const PROFICIENCY: [Symbols; 12] = [
+    face(""),
+    face("S"),
+    face("S"),
+    face("SS"),
+    face("SS"),
+    face("A"),
+    face("SA"),
+    face("SA"),
+    face("SA"),
+    face("AA"),
+    face("AA"),
+    face("R"),
+];
+
+const CHALLENGE: [Symbols; 12] = [
+    face(""),
+    face("F"),
+    face("F"),
+    face("FF"),
+    face("FF"),
+    face("T"),
+    face("T"),
+    face("FT"),
+    face("FT"),
+    face("TT"),
+    face("TT"),
+    face("D"),
+];
+
+/// One of the Genesys narrative dice, whose faces show symbols rather than
+/// numbers. Each is rolled as a numeric die of the same size, with the
+/// number picking the face.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum NarrativeDie {
+    Boost,
+    Setback,
+    Ability,
+    Difficulty,
+    Proficiency,
+    Challenge,
+}
+
+impl NarrativeDie {
+    fn faces(&self) -> &'static [Symbols] {
+        match self {
+            NarrativeDie::Boost => &BOOST,
+            NarrativeDie::Setback => &SETBACK,
+            NarrativeDie::Ability => &ABILITY,
+            NarrativeDie::Difficulty => &DIFFICULTY,
+            NarrativeDie::Proficiency => &PROFICIENCY,
+            NarrativeDie::Challenge => &CHALLENGE,
+        }
+    }
+
+    /// The die as a `Die` of the symbols on its faces.
+    pub fn die(&self) -> Die<Symbols> {
+        Die::new(self.faces().to_vec()).unwrap()
+    }
+
+    fn roll<F: FnMut(u32) -> u32>(&self, roll_die: &mut F) -> Symbols {
+        let faces = self.faces();
+        faces[roll_die(faces.len() as u32) as usize - 1]
+    }
+}
+
+impl TryFrom<char> for NarrativeDie {
+    type Error = DiceExprError;
+
+    fn try_from(c: char) -> Result<Self, Self::Error> {
+        match c {
+            'B' => Ok(NarrativeDie::Boost),
+            'S' => Ok(NarrativeDie::Setback),
+            'A' => Ok(NarrativeDie::Ability),
+            'D' => Ok(NarrativeDie::Difficulty),
+            'P' => Ok(NarrativeDie::Proficiency),
+            'C' => Ok(NarrativeDie::Challenge),
+            c => Err(Self::Error::Expr(c.to_string())),
+        }
+    }
+}
+
+impl Display for NarrativeDie {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            NarrativeDie::Boost => write!(f, "B"),
+            NarrativeDie::Setback => write!(f, "S"),
+            NarrativeDie::Ability => write!(f, "A"),
+            NarrativeDie::Difficulty => write!(f, "D"),
+            NarrativeDie::Proficiency => write!(f, "P"),
+            NarrativeDie::Challenge => write!(f, "C"),
+        }
+    }
+}
+
+/// A pool of narrative dice, written as counts and letters such as
+/// `2A1P2D1S` for two ability, one proficiency, two difficulty, and one
+/// setback die.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct NarrativePool {
+    dice: Vec<(u32, NarrativeDie)>,
+}
+
+/// The outcome of rolling a `NarrativePool`: the symbols on each die rolled
+/// and their net result once they've cancelled each other out.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct NarrativeRoll {
+    pub faces: Vec<(NarrativeDie, Symbols)>,
+    pub net: Symbols,
+}
+
+impl TryFrom<&str> for NarrativePool {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        lazy_static! {
+            static ref RE: Regex = Regex::new(r"^(?:\d*[BSADPC])+$").unwrap();
+            static ref DIE: Regex = Regex::new(r"(\d*)([BSADPC])").unwrap();
+        }
+
+        if !RE.is_match(s) {
+            return Err(Self::Error::Expr(s.to_string()));
+        }
+
+        let mut dice = Vec::new();
+        for caps in DIE.captures_iter(s) {
+            let count = match &caps[1] {
+                "" => 1,
+                n => n.parse()?,
+            };
+
+            if count == 0 {
+                return Err(Self::Error::Expr(s.to_string()));
+            }
+
+            let die = NarrativeDie::try_from(caps[2].chars().next().unwrap())?;
+            dice.push((count, die));
+        }
+
+        Ok(NarrativePool { dice })
+    }
+}
+
+impl Display for NarrativePool {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (count, die) in &self.dice {
+            write!(f, "{}{}", count, die)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl NarrativePool {
+    pub fn roll(&self) -> NarrativeRoll {
+        self.roll_with_roller(&mut thread_rng())
+    }
+
+    /// Rolls the pool like `roll`, with each die rolled by `roller`.
+    pub fn roll_with_roller<R: Roller + ?Sized>(&self, roller: &mut R) -> NarrativeRoll {
+        self.roll_with(|sides| roller.roll_die(sides))
+    }
+
+    fn roll_with<F: FnMut(u32) -> u32>(&self, mut roll_die: F) -> NarrativeRoll {
+        let faces: Vec<(NarrativeDie, Symbols)> = self
+            .dice
+            .iter()
+            .flat_map(|&(count, die)| (0..count).map(move |_| die))
+            .map(|die| (die, die.roll(&mut roll_die)))
+            .collect();
+
+        let net = faces
+            .iter()
+            .fold(Symbols::default(), |sum, (_, symbols)| sum + *symbols)
+            .net();
+
+        NarrativeRoll { faces, net }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn try_from_str() {
+        let pool = NarrativePool::try_from("2AP2D").unwrap();
+
+        assert_eq!(
+            vec![
+                (2, NarrativeDie::Ability),
+                (1, NarrativeDie::Proficiency),
+                (2, NarrativeDie::Difficulty)
+            ],
+            pool.dice
+        );
+        assert_eq!("2A1P2D", pool.to_string())
+    }
+
+    #[test]
+    fn try_from_str_invalid() {
+        for expr in ["", "2X", "0A", "2d6"] {
+            assert_eq!(
+                Err(DiceExprError::Expr(String::from(expr))),
+                NarrativePool::try_from(expr)
+            )
+        }
+    }
+
+    #[test]
+    fn roll_cancels() {
+        let pool = NarrativePool::try_from("2A1P1C").unwrap();
+        let mut values = vec![4, 7, 12, 12].into_iter();
+        let roll = pool.roll_with(|_| values.next().unwrap());
+
+        assert_eq!(
+            Symbols {
+                success: 3,
+                advantage: 1,
+                triumph: 1,
+                despair: 1,
+                ..Symbols::default()
+            },
+            roll.net
+        );
+        assert_eq!(
+            "3 success, 1 advantage, 1 triumph, 1 despair",
+            roll.net.to_string()
+        )
+    }
+
+    #[test]
+    fn roll_large_pool() {
+        let pool = NarrativePool::try_from("70000P").unwrap();
+        let roll = pool.roll_with(|_| 12);
+
+        assert_eq!((70000, 70000), (roll.net.success, roll.net.triumph))
+    }
+
+    #[test]
+    fn add_saturates() {
+        let max = Symbols {
+            success: u32::MAX,
+            triumph: u32::MAX,
+            ..Symbols::default()
+        };
+
+        assert_eq!(u32::MAX, (max + max).success);
+        assert_eq!(u32::MAX, max.net().success)
+    }
+
+    #[test]
+    fn die() {
+        let die = NarrativeDie::Proficiency.die();
+
+        assert_eq!(12, die.sides());
+        assert_eq!(face("R"), die.faces()[11])
+    }
+}
@@ -0,0 +1,1479 @@
+use crate::ast::{rollable, Ast, Function};
+use crate::compare::Comparison;
+use crate::dialect::Dialect;
+use crate::expr::{Dice, DiceExpr, DiceExprError, Drop, Keep, Reroll, Sides, Sort, SyntaxError};
+use crate::token::{self, Kind, Token};
+use alloc::boxed::Box;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec;
+use alloc::vec::Vec;
+use core::convert::TryFrom;
+use core::str::FromStr;
+
+/// The parts of a dice term as they're written, read by `Parser::dice`
+/// before they're checked and turned into a `Dice`.
+#[derive(Default)]
+struct DiceTokens<'a> {
+    text: &'a str,
+    count: Option<&'a str>,
+    sides: &'a str,
+    explode: Option<Option<&'a str>>,
+    open_ended: bool,
+    reroll: Option<(bool, &'a str)>,
+    min: Option<&'a str>,
+    max: Option<&'a str>,
+    keep: Option<&'a str>,
+    drop: Option<&'a str>,
+    success: Option<&'a str>,
+    double: Option<&'a str>,
+    failure: Option<&'a str>,
+    critical_success: Option<&'a str>,
+    critical_failure: Option<&'a str>,
+    drop_short: Option<&'a str>,
+    sets: bool,
+    sort: Option<&'a str>,
+    each: Option<&'a str>,
+    label: Option<&'a str>,
+}
+
+impl TryFrom<DiceTokens<'_>> for Dice {
+    type Error = DiceExprError;
+
+    fn try_from(dice: DiceTokens) -> Result<Self, Self::Error> {
+        let expr = dice.text.to_string();
+
+        let count: u32 = match dice.count {
+            Some(c) => c.parse()?,
+            None => 1,
+        };
+
+        let sides = Sides::try_from(dice.sides)?;
+        if count == 0 || sides.count() == 0 {
+            return Err(Self::Error::EmptyDice(expr));
+        }
+
+        let explode = match dice.explode {
+            Some(c) => {
+                let c = match c {
+                    Some(c) => Comparison::try_from(c)?,
+                    None => Comparison::Eq(vec![*sides.range().end()]),
+                };
+
+                Some(c)
+            }
+            None => None,
+        };
+
+        // Open-ended rolls are Rolemaster's, and only make sense for its
+        // percentile dice without any other way of adding or removing dice.
+        let open_ended = dice.open_ended;
+        if open_ended
+            && (sides.range() != (1..=100)
+                || explode.is_some()
+                || dice.keep.is_some()
+                || dice.drop.is_some()
+                || dice.drop_short.is_some())
+        {
+            return Err(Self::Error::from(expr));
+        }
+
+        let reroll = match dice.reroll {
+            Some((once, c)) => Some(Reroll {
+                condition: Comparison::try_from(c)?,
+                once,
+            }),
+            None => None,
+        };
+
+        let min = match dice.min {
+            Some(n) => Some(n.parse()?),
+            None => None,
+        };
+
+        let max = match dice.max {
+            Some(n) => Some(n.parse()?),
+            None => None,
+        };
+
+        if let (Some(min), Some(max)) = (min, max) {
+            if min > max {
+                return Err(Self::Error::from(expr));
+            }
+        }
+
+        let keep = match dice.keep {
+            Some(k) => match Keep::try_from(k)? {
+                Keep::High(n) | Keep::Low(n) | Keep::Middle(n) if n == 0 || n > count => {
+                    return Err(Self::Error::from(expr))
+                }
+                keep => keep,
+            },
+            None => Keep::None,
+        };
+
+        let drop = match (dice.drop, dice.drop_short) {
+            (Some(_), Some(_)) => return Err(Self::Error::from(expr)),
+            (Some(s), None) | (None, Some(s)) => match Drop::try_from(s)? {
+                Drop::High(n) | Drop::Low(n) if n == 0 || n >= count => {
+                    return Err(Self::Error::from(expr))
+                }
+                _ if keep != Keep::None => return Err(Self::Error::from(expr)),
+                drop => drop,
+            },
+            (None, None) => Drop::None,
+        };
+
+        let success = match dice.success {
+            Some(c) => Some(Comparison::try_from(c)?),
+            None => None,
+        };
+
+        let double = match (dice.double, &success) {
+            (Some(c), Some(_)) => Some(Comparison::try_from(c)?),
+            (Some(_), None) => return Err(Self::Error::from(expr)),
+            (None, _) => None,
+        };
+
+        let failure = match (dice.failure, &success) {
+            (Some(c), Some(_)) => Some(Comparison::try_from(c)?),
+            (Some(_), None) => return Err(Self::Error::from(expr)),
+            (None, _) => None,
+        };
+
+        let critical_success = match dice.critical_success {
+            Some(c) => Some(Comparison::try_from(c)?),
+            None => None,
+        };
+
+        let critical_failure = match dice.critical_failure {
+            Some(c) => Some(Comparison::try_from(c)?),
+            None => None,
+        };
+
+        let each = match dice.each {
+            Some(n) => Some(n.parse()?),
+            None => None,
+        };
+
+        let dice = Dice {
+            count,
+            sides,
+            explode,
+            open_ended,
+            reroll,
+            min,
+            max,
+            keep,
+            drop,
+            success,
+            double,
+            failure,
+            critical_success,
+            critical_failure,
+            sets: dice.sets,
+            sort: dice.sort.map(|s| match s {
+                "sd" => Sort::Descending,
+                _ => Sort::Ascending,
+            }),
+            each,
+            label: dice.label.map(|l| l.to_string()),
+        };
+
+        match dice.repeats_forever() {
+            true => Err(Self::Error::from(expr)),
+            false => Ok(dice),
+        }
+    }
+}
+
+impl TryFrom<&str> for Dice {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        let mut parser = Parser::new(s, s, Dialect::Standard, ParseOptions::default());
+
+        match parser.dice() {
+            Some(dice) if parser.is_done() => Dice::try_from(dice),
+            _ => Err(Self::Error::from(s.to_string())),
+        }
+    }
+}
+
+/// The highest Earthdawn step number, far beyond any step table.
+const MAX_STEP: u32 = 1000;
+
+/// How many dice of each size are rolled for an Earthdawn step number, from
+/// largest to smallest, and the modifier added to them. From step 13 on,
+/// each step is the step seven below it plus a d12.
+fn step_dice(step: u32) -> (Vec<(u32, u32)>, i64) {
+    let twelves = step.saturating_sub(6) / 7;
+    let (sides, modifier): (&[u32], i64) = match step - twelves * 7 {
+        1 => (&[4], -2),
+        2 => (&[4], -1),
+        3 => (&[4], 0),
+        4 => (&[6], 0),
+        5 => (&[8], 0),
+        6 => (&[10], 0),
+        7 => (&[12], 0),
+        8 => (&[6, 6], 0),
+        9 => (&[8, 6], 0),
+        10 => (&[8, 8], 0),
+        11 => (&[10, 8], 0),
+        _ => (&[10, 10], 0),
+    };
+
+    let mut dice = vec![(twelves, 12)];
+    for &sides in sides {
+        match dice.last_mut() {
+            Some((count, last)) if *last == sides => *count += 1,
+            _ => dice.push((1, sides)),
+        }
+    }
+    dice.retain(|&(count, _)| count > 0);
+
+    (dice, modifier)
+}
+
+/// Limits on the size of the expressions to parse, so that one like
+/// `99999d99999` can be rejected before it's rolled: on how many dice it
+/// rolls in all, before any explode, how many sides any of them has, and
+/// how many dice terms, constants, and variables it has. Each is unlimited
+/// by default.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash, Default)]
+pub struct ParseOptions {
+    pub max_dice: Option<u32>,
+    pub max_sides: Option<u32>,
+    pub max_terms: Option<usize>,
+}
+
+impl ParseOptions {
+    /// Whether the expression has few enough terms. Its dice and their
+    /// sides are counted as they're read, by `Parser::limit`.
+    fn allows(&self, ast: &Ast) -> bool {
+        self.max_terms.is_none_or(|max| ast.terms().len() <= max)
+    }
+}
+
+/// Rewrites an uppercase `D` for dice, as in `4D6`, into the lowercase form
+/// the parser expects: one that doesn't follow a letter, `_`, `$` or `[`,
+/// and comes before a number, `%`, Fate or Cortex dice, a list of faces, or
+/// a parenthesized size. The result is the same length as `s`, so that
+/// offsets into it are offsets into `s` as well.
+fn normalize(s: &str) -> String {
+    let mut normalized = String::with_capacity(s.len());
+    let mut chars = s.chars().peekable();
+    let mut previous = None;
+
+    while let Some(c) = chars.next() {
+        let dice = c == 'D'
+            && !previous.is_some_and(|p: char| p.is_ascii_alphabetic() || "_$[".contains(p))
+            && chars
+                .peek()
+                .is_some_and(|&n| n.is_ascii_digit() || "%FfCc[(".contains(n));
+        normalized.push(if dice { 'd' } else { c });
+        previous = Some(c);
+    }
+
+    normalized
+}
+
+/// Adds or subtracts `term`, dropping it if it's zero, as in `1d20+0`.
+fn add(ast: Ast, subtract: bool, term: Ast) -> Ast {
+    match (subtract, term) {
+        (_, Ast::Constant(0)) => ast,
+        (false, term) => Ast::Add(Box::new(ast), Box::new(term)),
+        (true, term) => Ast::Sub(Box::new(ast), Box::new(term)),
+    }
+}
+
+/// How deeply nested in brackets the token following `t` is, given how
+/// deeply `t` is.
+fn nesting(depth: usize, t: &Token) -> usize {
+    match t.text {
+        "(" | "[" | "{" => depth + 1,
+        ")" | "]" | "}" => depth.saturating_sub(1),
+        _ => depth,
+    }
+}
+
+/// A recursive descent parser for dice expressions, reading the tokens of
+/// an expression by the grammar:
+///
+/// ```text
+/// expr   = term (("+" | "-") term)*
+/// term   = factor (("*" | "/") factor)*
+/// factor = dice | advantage | coins | keep | step | var | function group | roll | pool
+/// dice   = number? "d" sides modifier*
+/// roll   = (constant | group)? ("d" (constant | group))?
+/// group  = "(" expr ")"
+/// pool   = "{" expr ("," expr)* "}" (("k" [hlm] | "d" [hl]) constant?)?
+/// ```
+struct Parser<'a> {
+    expr: &'a str,
+    src: &'a str,
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+    dialect: Dialect,
+    options: ParseOptions,
+    /// How many times the expression is repeated, and how many dice its
+    /// terms read so far roll, counted against `options`.
+    repeats: u32,
+    dice: u64,
+}
+
+impl<'a> Parser<'a> {
+    /// Creates a parser reading `src`, the normalized form of `expr`.
+    fn new(expr: &'a str, src: &'a str, dialect: Dialect, options: ParseOptions) -> Self {
+        Parser {
+            expr,
+            src,
+            tokens: token::tokenize(src),
+            pos: 0,
+            dialect,
+            options,
+            repeats: 1,
+            dice: 0,
+        }
+    }
+
+    /// Counts a term of `count` dice of `sides` sides against the limits on
+    /// the expression as soon as it's read, before it's checked any further,
+    /// so that one far larger than they allow fails at once.
+    fn limit(&mut self, count: u64, sides: u64) -> Result<(), DiceExprError> {
+        self.dice = self.dice.saturating_add(count);
+        let ParseOptions {
+            max_dice,
+            max_sides,
+            ..
+        } = self.options;
+
+        match max_dice.is_none_or(|max| self.dice.saturating_mul(self.repeats.into()) <= max.into())
+            && max_sides.is_none_or(|max| sides <= max.into())
+        {
+            true => Ok(()),
+            false => Err(DiceExprError::Limit(self.expr.to_string())),
+        }
+    }
+
+    /// An error at the next token, or at the end of the expression if
+    /// every token has been read.
+    fn err(&self, expected: &'static str) -> DiceExprError {
+        let (offset, len) = match self.peek() {
+            Some(t) => (t.offset, t.text.len()),
+            None => (self.src.len(), 0),
+        };
+
+        self.err_at(offset, len, Some(expected))
+    }
+
+    /// An error spanning the tokens read since the token at `start`, for
+    /// terms that are well formed but can't be rolled.
+    fn err_since(&self, start: usize) -> DiceExprError {
+        let text = self.text(start);
+        let offset = self.tokens.get(start).map_or(self.src.len(), |t| t.offset);
+        let trimmed = text.trim_start();
+
+        self.err_at(
+            offset + text.len() - trimmed.len(),
+            trimmed.trim_end().len(),
+            None,
+        )
+    }
+
+    fn err_at(&self, offset: usize, len: usize, expected: Option<&'static str>) -> DiceExprError {
+        DiceExprError::Syntax(SyntaxError {
+            expr: self.expr.to_string(),
+            offset,
+            len,
+            expected,
+        })
+    }
+
+    fn peek(&self) -> Option<Token<'a>> {
+        self.tokens.get(self.pos).copied()
+    }
+
+    /// Whether every token has been read.
+    fn is_done(&self) -> bool {
+        self.pos == self.tokens.len()
+    }
+
+    /// Whether the next token doesn't carry on a word, as a letter or
+    /// number would.
+    fn at_word_end(&self) -> bool {
+        !self
+            .peek()
+            .is_some_and(|t| matches!(t.kind, Kind::Letter | Kind::Number))
+    }
+
+    /// The text of the tokens read since the token at `start`.
+    fn text(&self, start: usize) -> &'a str {
+        let offset = |i: usize| self.tokens.get(i).map_or(self.src.len(), |t| t.offset);
+        &self.src[offset(start)..offset(self.pos)]
+    }
+
+    /// Runs `f`, rewinding to where it started if it finds no match.
+    fn attempt<T>(&mut self, f: impl FnOnce(&mut Self) -> Option<T>) -> Option<T> {
+        let start = self.pos;
+        let result = f(self);
+
+        if result.is_none() {
+            self.pos = start;
+        }
+
+        result
+    }
+
+    /// Consumes `c` if it's the next token.
+    fn eat(&mut self, c: char) -> bool {
+        match self.peek() {
+            Some(t) if t.is(c) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consumes the letters of `word` if they're next.
+    fn eat_word(&mut self, word: &str) -> bool {
+        self.attempt(|p| word.chars().all(|c| p.eat(c)).then_some(()))
+            .is_some()
+    }
+
+    /// Consumes the letters of `word` if they're next, in either case.
+    fn eat_word_ignoring_case(&mut self, word: &str) -> bool {
+        self.attempt(|p| {
+            word.chars()
+                .all(|c| p.eat(c.to_ascii_lowercase()) || p.eat(c.to_ascii_uppercase()))
+                .then_some(())
+        })
+        .is_some()
+    }
+
+    /// Skips any whitespace between tokens.
+    fn skip_whitespace(&mut self) {
+        while let Some(t) = self.peek() {
+            match t.kind {
+                Kind::Space => self.pos += 1,
+                // AnyDice comments are written between backslashes.
+                _ if self.dialect == Dialect::AnyDice && t.is('\\') => {
+                    self.pos += 1;
+                    while self.peek().is_some_and(|t| !t.is('\\')) {
+                        self.pos += 1;
+                    }
+                    self.eat('\\');
+                }
+                _ => break,
+            }
+        }
+    }
+
+    /// Consumes a number if it's next, returning it as written.
+    fn number(&mut self) -> Option<&'a str> {
+        match self.peek() {
+            Some(t) if t.kind == Kind::Number => {
+                self.pos += 1;
+                Some(t.text)
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes a number that may have a minus sign.
+    fn signed_number(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        self.attempt(|p| {
+            p.eat('-');
+            p.number()?;
+            Some(p.text(start))
+        })
+    }
+
+    /// Consumes a bracketed list of numbers such as `[1,3,5]`, which may
+    /// be negative if `signed`.
+    fn list(&mut self, signed: bool) -> Option<&'a str> {
+        self.delimited_list('[', ']', signed)
+    }
+
+    /// Consumes a list of numbers between `open` and `close`.
+    fn delimited_list(&mut self, open: char, close: char, signed: bool) -> Option<&'a str> {
+        let start = self.pos;
+        self.attempt(|p| {
+            p.eat(open).then_some(())?;
+
+            loop {
+                match signed {
+                    true => p.signed_number()?,
+                    false => p.number()?,
+                };
+
+                if p.eat(close) {
+                    return Some(p.text(start));
+                }
+                p.eat(',').then_some(())?;
+            }
+        })
+    }
+
+    /// Consumes a comparison such as `>=8` or `=[5,6]`, or a bare number or
+    /// list such as `5` if `bare`, returning it as written.
+    fn comparison(&mut self, bare: bool) -> Option<&'a str> {
+        let start = self.pos;
+        self.attempt(|p| {
+            let operator = match p.eat('<') || p.eat('>') {
+                true => {
+                    p.eat('=');
+                    true
+                }
+                false => p.eat('='),
+            };
+
+            if !operator && !bare {
+                return None;
+            }
+
+            p.number().or_else(|| p.list(false))?;
+            Some(p.text(start))
+        })
+    }
+
+    /// Consumes a keep or drop selection such as `h3`, going in one of
+    /// `directions`.
+    fn selection(&mut self, directions: &str) -> Option<&'a str> {
+        let start = self.pos;
+        self.attempt(|p| {
+            directions.chars().any(|c| p.eat(c)).then_some(())?;
+            p.number();
+            Some(p.text(start))
+        })
+    }
+
+    /// Consumes a dice term such as `4d6kh3`.
+    fn dice(&mut self) -> Option<DiceTokens<'a>> {
+        let start = self.pos;
+        self.attempt(|p| {
+            let count = p.number();
+            p.eat('d').then_some(())?;
+            let sides = p.sides()?;
+            Some(match p.dialect {
+                // AnyDice dice don't take modifiers, leaving comparisons such
+                // as `3d6>=10` to be reported as unsupported.
+                Dialect::AnyDice => DiceTokens {
+                    text: p.text(start),
+                    count,
+                    sides,
+                    ..DiceTokens::default()
+                },
+                Dialect::Foundry => p.foundry_modifiers(start, count, sides),
+                _ => p.modifiers(start, count, sides),
+            })
+        })
+    }
+
+    fn sides(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        match self.number().is_some()
+            || "FfCc%".chars().any(|c| self.eat(c))
+            || self.list(true).is_some()
+            || (self.dialect == Dialect::AnyDice && self.delimited_list('{', '}', true).is_some())
+        {
+            true => Some(self.text(start)),
+            false => None,
+        }
+    }
+
+    /// Consumes the modifiers following the count and sides of a dice term
+    /// starting at the token `start`. Each is optional, but they have to
+    /// come in this order.
+    fn modifiers(
+        &mut self,
+        start: usize,
+        count: Option<&'a str>,
+        sides: &'a str,
+    ) -> DiceTokens<'a> {
+        let explode = match self.eat('!') {
+            true => Some(self.comparison(true)),
+            false => None,
+        };
+        let open_ended = self.eat_word("oe");
+        let reroll = self.attempt(|p| {
+            p.eat('r').then_some(())?;
+            let once = p.eat('o');
+            Some((once, p.comparison(true)?))
+        });
+        let min = self.attempt(|p| {
+            p.eat_word("min").then_some(())?;
+            p.signed_number()
+        });
+        let max = self.attempt(|p| {
+            p.eat_word("max").then_some(())?;
+            p.signed_number()
+        });
+        let keep = self.attempt(|p| {
+            p.eat('k').then_some(())?;
+            p.selection("hlm")
+        });
+        let drop = match keep {
+            Some(_) => None,
+            None => self.attempt(|p| {
+                p.eat('d').then_some(())?;
+                p.selection("hl")
+            }),
+        };
+        let success = self.comparison(false);
+        let double = self.attempt(|p| {
+            p.eat('t').then_some(())?;
+            p.comparison(true)
+        });
+        let failure = self.attempt(|p| {
+            p.eat('f').then_some(())?;
+            p.comparison(true)
+        });
+        let critical_success = self.attempt(|p| {
+            p.eat_word("cs").then_some(())?;
+            p.comparison(true)
+        });
+        let critical_failure = self.attempt(|p| {
+            p.eat_word("cf").then_some(())?;
+            p.comparison(true)
+        });
+        let drop_short = self.attempt(|p| {
+            p.eat('-').then_some(())?;
+            p.selection("LlHh")
+        });
+        let sets = self.eat('m');
+        let sort_start = self.pos;
+        let sort = match self.eat('s') {
+            true => {
+                let _ = self.eat('a') || self.eat('d');
+                Some(self.text(sort_start))
+            }
+            false => None,
+        };
+        let each = match self.attempt(|p| {
+            p.eat('+').then_some(())?;
+            let start = p.pos;
+            p.eat('+').then_some(())?;
+            p.number()?;
+            Some(p.text(start))
+        }) {
+            Some(each) => Some(each),
+            None => self.attempt(|p| {
+                let start = p.pos;
+                (p.eat('+') || p.eat('-')).then_some(())?;
+                p.number()?;
+                let each = p.text(start);
+                p.eat_word("each").then_some(each)
+            }),
+        };
+        let label = self.label();
+
+        DiceTokens {
+            text: self.text(start),
+            count,
+            sides,
+            explode,
+            open_ended,
+            reroll,
+            min,
+            max,
+            keep,
+            drop,
+            success,
+            double,
+            failure,
+            critical_success,
+            critical_failure,
+            drop_short,
+            sets,
+            sort,
+            each,
+            label,
+        }
+    }
+
+    /// Consumes the modifiers following the count and sides of a Foundry
+    /// dice term starting at the token `start`, which can come in any order.
+    fn foundry_modifiers(
+        &mut self,
+        start: usize,
+        count: Option<&'a str>,
+        sides: &'a str,
+    ) -> DiceTokens<'a> {
+        let mut dice = DiceTokens {
+            count,
+            sides,
+            ..DiceTokens::default()
+        };
+
+        loop {
+            if self.eat('x') {
+                dice.explode = Some(self.comparison(true));
+            } else if let Some(reroll) = self.attempt(|p| {
+                p.eat('r').then_some(())?;
+                let once = !p.eat('r');
+                Some((once, p.comparison(true)?))
+            }) {
+                dice.reroll = Some(reroll);
+            } else if let Some(keep) = self.attempt(|p| {
+                p.eat('k').then_some(())?;
+                p.selection("hl").or(Some("h"))
+            }) {
+                dice.keep = Some(keep);
+            } else if let Some(drop) = self.attempt(|p| {
+                p.eat('d').then_some(())?;
+                p.selection("hl")
+            }) {
+                dice.drop = Some(drop);
+            } else if let Some(success) = self.attempt(|p| {
+                p.eat_word("cs").then_some(())?;
+                p.comparison(true)
+            }) {
+                dice.success = Some(success);
+            } else if let Some(failure) = self.attempt(|p| {
+                p.eat_word("cf").then_some(())?;
+                p.comparison(true)
+            }) {
+                dice.failure = Some(failure);
+            } else if let Some(min) = self.attempt(|p| {
+                p.eat_word("min").then_some(())?;
+                p.signed_number()
+            }) {
+                dice.min = Some(min);
+            } else if let Some(max) = self.attempt(|p| {
+                p.eat_word("max").then_some(())?;
+                p.signed_number()
+            }) {
+                dice.max = Some(max);
+            } else {
+                break;
+            }
+        }
+
+        dice.label = self.label();
+        dice.text = self.text(start);
+        dice
+    }
+
+    /// Consumes a label such as `[fire]` following a dice term.
+    fn label(&mut self) -> Option<&'a str> {
+        self.attempt(|p| {
+            p.eat('[').then_some(())?;
+            let start = p.pos;
+            p.peek()?
+                .text
+                .starts_with(|c: char| c.is_ascii_alphabetic())
+                .then_some(())?;
+
+            while !p.peek()?.is(']') {
+                p.pos += 1;
+            }
+
+            let label = p.text(start);
+            p.eat(']');
+            Some(label)
+        })
+    }
+
+    fn expr(&mut self) -> Result<Ast, DiceExprError> {
+        let mut ast = self.term()?;
+
+        while let Some(subtract) = self.additive() {
+            ast = add(ast, subtract, self.term()?);
+        }
+
+        Ok(ast)
+    }
+
+    /// Parses an expression like `expr`, but carries on past a term that
+    /// fails to parse from the next `+` or `-` outside of it, so that every
+    /// term's errors are reported together.
+    fn sum(&mut self) -> Result<Ast, DiceExprError> {
+        let mut errors = Vec::new();
+        let mut ast = self.recovering_term(&mut errors);
+
+        while let Some(subtract) = self.additive() {
+            let term = self.recovering_term(&mut errors);
+            ast = match (ast, term) {
+                (Some(ast), Some(term)) => Some(add(ast, subtract, term)),
+                _ => None,
+            };
+        }
+
+        match ast {
+            Some(ast) if errors.is_empty() => Ok(ast),
+            _ => Err(DiceExprError::from(errors)),
+        }
+    }
+
+    /// Parses a term, or on failure records the error and skips ahead to
+    /// the next `+` or `-` that isn't nested inside the term.
+    fn recovering_term(&mut self, errors: &mut Vec<DiceExprError>) -> Option<Ast> {
+        let start = self.pos;
+
+        match self.term() {
+            Ok(term) => Some(term),
+            Err(e) => {
+                errors.push(e);
+
+                let mut depth = self.tokens[start..self.pos]
+                    .iter()
+                    .fold(0, |depth, t| nesting(depth, t));
+                while let Some(t) = self.peek() {
+                    if depth == 0 && (t.is('+') || t.is('-')) {
+                        break;
+                    }
+                    depth = nesting(depth, &t);
+                    self.pos += 1;
+                }
+
+                None
+            }
+        }
+    }
+
+    /// Consumes an operator between terms, returning whether it subtracts.
+    /// A plus and minus together, as in `1d20+-2`, subtract.
+    fn additive(&mut self) -> Option<bool> {
+        self.skip_whitespace();
+
+        if self.eat('+') {
+            self.skip_whitespace();
+            Some(self.eat('-'))
+        } else if self.eat('-') {
+            self.skip_whitespace();
+            self.eat('+');
+            Some(true)
+        } else {
+            None
+        }
+    }
+
+    fn term(&mut self) -> Result<Ast, DiceExprError> {
+        let mut ast = self.labelled_factor()?;
+
+        loop {
+            self.skip_whitespace();
+            ast = if self.eat('*') {
+                Ast::Mul(Box::new(ast), Box::new(self.labelled_factor()?))
+            } else if self.eat('/') {
+                // A divisor with variables is checked once they're known,
+                // when it's rolled with them.
+                let start = self.pos;
+                match self.labelled_factor()? {
+                    divisor if divisor.variables().is_empty() && divisor.range().contains(&0) => {
+                        return Err(self.err_since(start))
+                    }
+                    divisor => Ast::Div(Box::new(ast), Box::new(divisor)),
+                }
+            } else {
+                return Ok(ast);
+            };
+        }
+    }
+
+    /// Consumes a factor, followed in the Roll20 and Foundry dialects by an
+    /// optional label. Labels on dice are kept, but any other term's label
+    /// only annotates the roll and is skipped.
+    fn labelled_factor(&mut self) -> Result<Ast, DiceExprError> {
+        let ast = self.factor()?;
+
+        if let Dialect::Roll20 | Dialect::Foundry = self.dialect {
+            self.attempt(|p| {
+                p.skip_whitespace();
+                p.eat('[').then_some(())?;
+
+                while !p.peek()?.is(']') {
+                    p.pos += 1;
+                }
+
+                Some(p.eat(']'))
+            });
+        }
+
+        Ok(ast)
+    }
+
+    fn factor(&mut self) -> Result<Ast, DiceExprError> {
+        self.skip_whitespace();
+
+        if self.dialect == Dialect::AnyDice {
+            if let Some(construct) = self.unsupported() {
+                return Err(DiceExprError::Unsupported(construct.to_string()));
+            }
+        }
+
+        if let Some(name) = self.var().or_else(|| self.attribute()) {
+            return Ok(Ast::Var(name));
+        }
+
+        // Advantage and disadvantage are shorthand for the equivalent dice,
+        // so they display and roll exactly as if those had been written.
+        if let Some(keep) = self.advantage() {
+            self.limit(2, 20)?;
+            return Ok(Ast::Dice(Box::new(Dice {
+                keep,
+                ..Dice::new(2, Sides::Number(20))
+            })));
+        }
+
+        if let Some(dice) = self.dice() {
+            // Terms whose count or sides don't parse fail below instead.
+            let count = dice.count.map_or(Ok(1), str::parse::<u32>);
+            if let (Ok(count), Ok(sides)) = (count, Sides::try_from(dice.sides)) {
+                self.limit(count.into(), sides.count().into())?;
+            }
+
+            // The dialect's conventions, such as World of Darkness's
+            // 10-again, can make a die that rerolls or clamps its tens
+            // explode forever, which the term alone wouldn't.
+            let text = dice.text;
+            let dice = self.dialect.apply(Dice::try_from(dice)?);
+            if dice.repeats_forever() {
+                return Err(DiceExprError::from(text.to_string()));
+            }
+            return Ok(Ast::Dice(Box::new(dice)));
+        }
+
+        // `3c` flips three coins, and takes the same modifiers as `3dC`.
+        let start = self.pos;
+        let coins = self.attempt(|p| {
+            let count = p.number()?;
+            p.eat('c').then_some(())?;
+            match p.peek() {
+                Some(t) if t.text.starts_with(|c: char| c.is_ascii_lowercase()) => None,
+                _ => Some(count),
+            }
+        });
+        if let Some(count) = coins {
+            self.limit(count.parse().unwrap_or(u64::MAX), 2)?;
+            let dice = self.modifiers(start, Some(count), "C");
+            return match Dice::try_from(dice) {
+                Ok(dice) => Ok(Ast::Dice(Box::new(dice))),
+                Err(e @ DiceExprError::EmptyDice(_)) => Err(e),
+                Err(_) => Err(self.err_since(start)),
+            };
+        }
+
+        // Legend of the Five Rings' `5k3` rolls five exploding d10s and keeps
+        // the highest three.
+        let roll_and_keep = self.attempt(|p| {
+            let count = p.number()?;
+            p.eat('k').then_some(())?;
+            Some((count, p.number()?))
+        });
+        if let Some((count, keep)) = roll_and_keep {
+            self.limit(count.parse().unwrap_or(u64::MAX), 10)?;
+            let dice = format!("{}d10!kh{}", count, keep);
+            // Errors naming the term name it as it was written, rather than
+            // as the dice it's rewritten to.
+            return match Dice::try_from(dice.as_str()) {
+                Ok(dice) => Ok(Ast::Dice(Box::new(dice))),
+                Err(DiceExprError::EmptyDice(_)) => Err(DiceExprError::EmptyDice(
+                    self.text(start).trim().to_string(),
+                )),
+                Err(DiceExprError::Expr(_)) => Err(self.err_since(start)),
+                Err(e) => Err(e),
+            };
+        }
+
+        // Earthdawn's `step 9` rolls the exploding dice for that step, in
+        // this case `d8!+d6!`.
+        let step = self.attempt(|p| {
+            p.eat_word("step").then_some(())?;
+            p.skip_whitespace();
+            p.number()
+        });
+        if let Some(step) = step {
+            let step = match step.parse()? {
+                n if n == 0 || n > MAX_STEP => return Err(self.err_since(start)),
+                n => n,
+            };
+
+            let (dice, modifier) = step_dice(step);
+            for &(count, sides) in &dice {
+                self.limit(count.into(), sides.into())?;
+            }
+            let mut terms = dice.into_iter().map(|(count, sides)| {
+                let sides = Sides::Number(sides);
+                Ast::Dice(Box::new(Dice {
+                    explode: Some(Comparison::Eq(vec![*sides.range().end()])),
+                    ..Dice::new(count, sides)
+                }))
+            });
+
+            let first = terms.next().unwrap();
+            let ast = terms.fold(first, |ast, dice| Ast::Add(Box::new(ast), Box::new(dice)));
+
+            return Ok(match modifier {
+                0 => ast,
+                n => Ast::Sub(Box::new(ast), Box::new(Ast::Constant(-n))),
+            });
+        }
+
+        for (name, function) in [
+            ("floor", Function::Floor),
+            ("ceil", Function::Ceil),
+            ("round", Function::Round),
+            ("abs", Function::Abs),
+        ] {
+            if self.eat_word(name) {
+                return Ok(Ast::Call(function, Box::new(self.group()?)));
+            }
+        }
+
+        if self.peek().is_some_and(|t| t.is('{')) {
+            return self.pool();
+        }
+
+        let count = match self.constant()? {
+            Some(n) => n,
+            None if self.peek().is_some_and(|t| t.is('d')) => Ast::Constant(1),
+            None if self.peek().is_some_and(|t| t.is('(')) => self.group()?,
+            None => return Err(self.err("a number, dice, or \"(\"")),
+        };
+
+        if !self.eat('d') {
+            return Ok(count);
+        }
+
+        let sides = match self.constant()? {
+            Some(n) => n,
+            None if self.peek().is_some_and(|t| t.is('(')) => self.group()?,
+            None => return Err(self.err("a number or \"(\"")),
+        };
+
+        let most = |ast: &Ast| {
+            ast.range()
+                .end()
+                .clamp(&0, &(u32::MAX as i64))
+                .unsigned_abs()
+        };
+        self.limit(most(&count), most(&sides))?;
+
+        // Like a divisor, a count or sides with variables is checked once
+        // they're known.
+        let variable = !(count.variables().is_empty() && sides.variables().is_empty());
+        match variable || rollable(&count.range(), &sides.range()) {
+            true => Ok(Ast::Roll(Box::new(count), Box::new(sides))),
+            false => Err(self.err_since(start)),
+        }
+    }
+
+    /// Consumes a variable such as `$str`, returning its name.
+    fn var(&mut self) -> Option<String> {
+        self.attempt(|p| {
+            p.eat('$').then_some(())?;
+            Some(p.word()?.to_string())
+        })
+    }
+
+    /// Consumes a Roll20 attribute such as `@{strength_mod}`, or a Foundry
+    /// data path such as `@abilities.str.mod`, returning its name.
+    fn attribute(&mut self) -> Option<String> {
+        self.attempt(|p| {
+            p.eat('@').then_some(())?;
+
+            match p.dialect {
+                Dialect::Roll20 => {
+                    p.eat('{').then_some(())?;
+                    let name = p.word()?;
+                    p.eat('}').then(|| name.to_string())
+                }
+                Dialect::Foundry => {
+                    let start = p.pos;
+                    p.word()?;
+                    while p
+                        .attempt(|p| p.eat('.').then(|| p.word()).flatten())
+                        .is_some()
+                    {}
+                    Some(p.text(start).to_string())
+                }
+                _ => None,
+            }
+        })
+    }
+
+    /// Consumes a name starting with a letter, such as `str` or `dex_mod`.
+    fn word(&mut self) -> Option<&'a str> {
+        let start = self.pos;
+        (self.peek()?.kind == Kind::Letter).then_some(())?;
+
+        while !self.at_word_end() {
+            self.pos += 1;
+        }
+
+        Some(self.text(start))
+    }
+
+    /// Consumes what comes before the expression in some dialects: the
+    /// `/roll` or `/r` command or the `[[` of an inline roll starting a
+    /// Roll20 macro, or the `output` starting an AnyDice statement. Returns
+    /// whether it was an inline roll that has to be closed with `]]`.
+    fn prefix(&mut self) -> Result<bool, DiceExprError> {
+        self.skip_whitespace();
+
+        match self.dialect {
+            Dialect::Roll20 => {
+                self.attempt(|p| {
+                    (p.eat('/') && (p.eat_word("roll") || p.eat('r'))).then_some(())?;
+                    (p.peek()?.kind == Kind::Space).then_some(())
+                });
+                self.skip_whitespace();
+                Ok(self.eat_word("[["))
+            }
+            Dialect::AnyDice => {
+                for keyword in ["function", "loop", "if", "else", "set", "result"] {
+                    if self.keyword(keyword) {
+                        return Err(DiceExprError::Unsupported(keyword.to_string()));
+                    }
+                }
+
+                if self
+                    .attempt(|p| p.word().and_then(|_| p.eat(':').then_some(())))
+                    .is_some()
+                {
+                    return Err(DiceExprError::Unsupported(String::from(
+                        "variable assignment",
+                    )));
+                }
+
+                match self.keyword("output") {
+                    true => Ok(false),
+                    false => Err(self.err("\"output\"")),
+                }
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Consumes what comes after the expression in some dialects: the `]]`
+    /// closing a Roll20 inline roll, or the `named "..."` ending an AnyDice
+    /// statement.
+    fn suffix(&mut self, inline: bool) -> Result<(), DiceExprError> {
+        self.skip_whitespace();
+
+        if inline && !self.eat_word("]]") {
+            return Err(self.err("\"]]\""));
+        }
+
+        if self.dialect == Dialect::AnyDice {
+            if let Some(construct) = self.unsupported() {
+                return Err(DiceExprError::Unsupported(construct.to_string()));
+            }
+
+            if self.keyword("named") {
+                self.skip_whitespace();
+                if !self.eat('"') {
+                    return Err(self.err("a quoted name"));
+                }
+                while self.peek().is_some_and(|t| !t.is('"')) {
+                    self.pos += 1;
+                }
+                if !self.eat('"') {
+                    return Err(self.err("a closing quote"));
+                }
+            }
+        }
+
+        self.skip_whitespace();
+        Ok(())
+    }
+
+    /// Consumes `word` if it's next and not the start of a longer word.
+    fn keyword(&mut self, word: &str) -> bool {
+        self.attempt(|p| (p.eat_word(word) && p.at_word_end()).then_some(()))
+            .is_some()
+    }
+
+    /// Names the AnyDice construct the next token starts, if it's one that
+    /// isn't supported.
+    fn unsupported(&self) -> Option<&'static str> {
+        let t = self.peek()?;
+
+        match t.text {
+            "[" => Some("function call"),
+            "{" => Some("sequence"),
+            "#" => Some("length"),
+            "@" => Some("sequence access"),
+            "<" | ">" | "=" | "!" => Some("comparison"),
+            "&" | "|" => Some("boolean operator"),
+            "^" => Some("exponent"),
+            _ if t.kind == Kind::Letter && t.text.starts_with(|c: char| c.is_ascii_uppercase()) => {
+                Some("variable")
+            }
+            _ => None,
+        }
+    }
+
+    /// Consumes `adv` or `dis`, or their long forms `d20a` and `d20d`,
+    /// returning which of the two d20 they keep.
+    fn advantage(&mut self) -> Option<Keep> {
+        self.attempt(|p| {
+            let keep = if p.eat_word("adv") {
+                Keep::High(1)
+            } else if p.eat_word("dis") {
+                Keep::Low(1)
+            } else {
+                p.eat('d').then_some(())?;
+                (p.number()? == "20").then_some(())?;
+                if p.eat('a') {
+                    Keep::High(1)
+                } else if p.eat('d') {
+                    Keep::Low(1)
+                } else {
+                    return None;
+                }
+            };
+
+            p.at_word_end().then_some(keep)
+        })
+    }
+
+    fn constant(&mut self) -> Result<Option<Ast>, DiceExprError> {
+        match self.number() {
+            Some(n) => Ok(Some(Ast::Constant(n.parse()?))),
+            None => Ok(None),
+        }
+    }
+
+    fn pool(&mut self) -> Result<Ast, DiceExprError> {
+        let start = self.pos;
+        self.eat('{');
+        let mut terms = Vec::new();
+
+        loop {
+            terms.push(self.expr()?);
+
+            if self.eat('}') {
+                break;
+            } else if !self.eat(',') {
+                return Err(self.err("\",\" or \"}\""));
+            }
+        }
+
+        let len = terms.len() as u32;
+        self.skip_whitespace();
+        let keep = self.attempt(|p| {
+            p.eat('k').then_some(())?;
+            p.selection("hlm")
+        });
+        let drop = self.attempt(|p| {
+            p.eat('d').then_some(())?;
+            p.selection("hl")
+        });
+        let (keep, drop) = match (keep, drop) {
+            (Some(k), _) => (Keep::try_from(k)?, Drop::None),
+            (None, Some(d)) => (Keep::None, Drop::try_from(d)?),
+            (None, None) => (Keep::None, Drop::None),
+        };
+
+        match (&keep, &drop) {
+            (Keep::High(n) | Keep::Low(n) | Keep::Middle(n), _) if *n == 0 || *n > len => {
+                Err(self.err_since(start))
+            }
+            (_, Drop::High(n) | Drop::Low(n)) if *n == 0 || *n >= len => Err(self.err_since(start)),
+            _ => Ok(Ast::Group(terms, keep, drop)),
+        }
+    }
+
+    /// Consumes the `3x` of a repeated expression, returning how many times
+    /// to roll it.
+    fn repeat(&mut self) -> Result<u32, DiceExprError> {
+        // Rolling a set of ability scores, with `stats` or `abilities`, is
+        // shorthand for `6x(4d6-L)`.
+        self.skip_whitespace();
+        let stats = self.attempt(|p| {
+            (p.eat_word("stats") || p.eat_word("abilities")).then_some(())?;
+            p.skip_whitespace();
+            p.is_done().then_some(())
+        });
+        if stats.is_some() {
+            self.src = "(4d6-L)";
+            self.tokens = token::tokenize(self.src);
+            self.pos = 0;
+            return Ok(6);
+        }
+
+        // Leave the opening parenthesis for the group that follows.
+        let start = self.pos;
+        let count = self.attempt(|p| {
+            let count = p.number()?;
+            p.skip_whitespace();
+            p.eat('x').then_some(())?;
+            p.skip_whitespace();
+            p.peek()?.is('(').then_some(count)
+        });
+
+        match count {
+            Some(count) => match count.parse()? {
+                0 => Err(self.err_since(start)),
+                n => Ok(n),
+            },
+            None => Ok(1),
+        }
+    }
+
+    /// Consumes a trailing difficulty class, `dc15` or `vs 15`, if there is
+    /// one.
+    fn dc(&mut self) -> Result<Option<i64>, DiceExprError> {
+        self.skip_whitespace();
+        let dc = self.attempt(|p| {
+            (p.eat_word_ignoring_case("dc") || p.eat_word_ignoring_case("vs")).then_some(())?;
+            p.skip_whitespace();
+            p.number()
+        });
+
+        match dc {
+            Some(dc) => Ok(Some(dc.parse()?)),
+            None => Ok(None),
+        }
+    }
+
+    fn group(&mut self) -> Result<Ast, DiceExprError> {
+        self.skip_whitespace();
+        if !self.eat('(') {
+            return Err(self.err("\"(\""));
+        }
+
+        let ast = self.expr()?;
+
+        match self.eat(')') {
+            true => Ok(ast),
+            false => Err(self.err("\")\"")),
+        }
+    }
+}
+
+impl TryFrom<&str> for DiceExpr {
+    type Error = DiceExprError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        DiceExpr::parse(s, Dialect::Standard)
+    }
+}
+
+impl FromStr for DiceExpr {
+    type Err = DiceExprError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DiceExpr::try_from(s)
+    }
+}
+
+impl DiceExpr {
+    /// Parses an expression following the conventions of `dialect`.
+    pub fn parse(s: &str, dialect: Dialect) -> Result<Self, DiceExprError> {
+        DiceExpr::parse_with_options(s, dialect, ParseOptions::default())
+    }
+
+    /// Parses an expression following the conventions of `dialect`, failing
+    /// if it's larger than `options` allow.
+    pub fn parse_with_options(
+        s: &str,
+        dialect: Dialect,
+        options: ParseOptions,
+    ) -> Result<Self, DiceExprError> {
+        if let Dialect::WorldOfDarkness { difficulty } = dialect {
+            if !(2..=10).contains(&difficulty) {
+                return Err(DiceExprError::from(s.to_string()));
+            }
+        }
+
+        let normalized = normalize(s);
+        let mut parser = Parser::new(s, &normalized, dialect, options);
+        let inline = parser.prefix()?;
+        let repeat = parser.repeat()?;
+        parser.repeats = repeat;
+        let ast = match repeat {
+            1 => parser.sum()?,
+            _ => parser.group()?,
+        };
+
+        let dc = parser.dc()?;
+        parser.suffix(inline)?;
+
+        if !parser.is_done() {
+            return Err(parser.err("an operator"));
+        } else if !options.allows(&ast) {
+            return Err(DiceExprError::Limit(s.to_string()));
+        }
+        ast.validate(&s, false)?;
+
+        Ok(DiceExpr {
+            repeat,
+            signed: false,
+            ast,
+            dc,
+        })
+    }
+
+    /// Parses several expressions separated by semicolons or commas, such as
+    /// `2d6+3; d20; 4d4-L`. Commas inside a pool, a list of faces, or a
+    /// parenthesized expression don't separate expressions, and in AnyDice
+    /// each line is its own statement. If more than one
+    /// fails to parse, the errors from all of them are returned together.
+    pub fn parse_all(s: &str, dialect: Dialect) -> Result<Vec<Self>, DiceExprError> {
+        DiceExpr::parse_all_with_options(s, dialect, ParseOptions::default())
+    }
+
+    /// Parses several expressions like `parse_all`, failing if any is larger
+    /// than `options` allow.
+    pub fn parse_all_with_options(
+        s: &str,
+        dialect: Dialect,
+        options: ParseOptions,
+    ) -> Result<Vec<Self>, DiceExprError> {
+        let mut exprs = Vec::new();
+        let mut depth = 0;
+        let mut start = 0;
+
+        for (i, c) in s.char_indices() {
+            match c {
+                '(' | '[' | '{' => depth += 1,
+                ')' | ']' | '}' => depth -= 1,
+                ';' | ',' if depth == 0 => {
+                    exprs.push(&s[start..i]);
+                    start = i + 1;
+                }
+                '\n' if depth == 0 && dialect == Dialect::AnyDice => {
+                    exprs.push(&s[start..i]);
+                    start = i + 1;
+                }
+                _ => {}
+            }
+        }
+        exprs.push(&s[start..]);
+
+        let (exprs, errors): (Vec<_>, Vec<_>) = exprs
+            .into_iter()
+            .map(str::trim)
+            .filter(|expr| !expr.is_empty())
+            .map(|expr| DiceExpr::parse_with_options(expr, dialect, options))
+            .partition(Result::is_ok);
+
+        match (exprs.is_empty(), errors.is_empty()) {
+            (_, false) => Err(DiceExprError::from(
+                errors
+                    .into_iter()
+                    .filter_map(Result::err)
+                    .collect::<Vec<_>>(),
+            )),
+            (true, true) => Err(DiceExprError::from(s.to_string())),
+            (false, true) => Ok(exprs.into_iter().filter_map(Result::ok).collect()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_uppercase() {
+        assert_eq!("4d6+d%+dF+d[1,2]", normalize("4D6+D%+DF+D[1,2]"));
+        assert_eq!("$D6 [D6] XD6 D DD6", normalize("$D6 [D6] XD6 D DD6"))
+    }
+}
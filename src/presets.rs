@@ -0,0 +1,99 @@
+use crate::expr::{Dice, DiceExpr, DiceExprError, Sides};
+use alloc::string::ToString;
+#[cfg(feature = "std")]
+use lazy_static::lazy_static;
+
+#[cfg(feature = "std")]
+lazy_static! {
+    pub static ref D4: DiceExpr = d(4).unwrap();
+    pub static ref D6: DiceExpr = d(6).unwrap();
+    pub static ref D8: DiceExpr = d(8).unwrap();
+    pub static ref D10: DiceExpr = d(10).unwrap();
+    pub static ref D12: DiceExpr = d(12).unwrap();
+    pub static ref D20: DiceExpr = d(20).unwrap();
+    pub static ref D100: DiceExpr = d(100).unwrap();
+}
+
+/// A single die of `sides` sides, such as `d(6)?` for `d6`, for composing
+/// expressions in code as in `d(20)? + 5` or `d(6)? * 2 + d(4)?`. Fails as
+/// parsing `d0` would if `sides` is zero. Composed expressions are checked
+/// as parsing would check them when they're rolled.
+pub fn d(sides: u32) -> Result<DiceExpr, DiceExprError> {
+    let dice = Dice::new(1, Sides::Number(sides));
+    match sides {
+        0 => Err(DiceExprError::EmptyDice(dice.to_string())),
+        _ => Ok(DiceExpr::from(dice)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeded::SeededRoller;
+    use alloc::string::String;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn display() {
+        let presets: [&DiceExpr; 7] = [&D4, &D6, &D8, &D10, &D12, &D20, &D100];
+
+        assert_eq!(
+            vec!["d4", "d6", "d8", "d10", "d12", "d20", "d100"],
+            presets.iter().map(|d| d.to_string()).collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn compose() -> Result<(), DiceExprError> {
+        assert_eq!(DiceExpr::try_from("d20+5"), Ok(d(20)? + 5));
+        assert_eq!(DiceExpr::try_from("d6*2+d4"), Ok(d(6)? * 2 + d(4)?));
+        assert_eq!(DiceExpr::try_from("d8-1-d4"), Ok(d(8)? + -1 - d(4)?));
+        assert_eq!(DiceExpr::try_from("(d6+1)*2"), Ok((d(6)? + 1) * 2));
+        Ok(())
+    }
+
+    #[test]
+    fn compose_repeat_and_dc() -> Result<(), DiceExprError> {
+        let damage = DiceExpr::try_from("3x(2d6) dc10")?;
+
+        assert_eq!(
+            DiceExpr::try_from("3x(d20+2d6) dc10"),
+            Ok(d(20)? + damage.clone())
+        );
+        assert_eq!(
+            DiceExpr::try_from("3x(d20-2d6) dc15"),
+            Ok(DiceExpr::try_from("d20 dc15")? - damage)
+        );
+        Ok(())
+    }
+
+    #[test]
+    #[allow(clippy::erasing_op)]
+    fn compose_invalid() -> Result<(), DiceExprError> {
+        let roll = |expr: DiceExpr| expr.roll_with_roller(&mut SeededRoller::new(1));
+
+        assert_eq!(Err(DiceExprError::EmptyDice(String::from("d0"))), d(0));
+        assert_eq!(
+            Some(DiceExprError::Expr(String::from("d6-100"))),
+            roll(d(6)? - 100).err()
+        );
+        assert_eq!(
+            Some(DiceExprError::Expr(String::from("d6*0"))),
+            roll(d(6)? * 0).err()
+        );
+        assert!(matches!(
+            roll(d(u32::MAX)? * i64::MAX),
+            Err(DiceExprError::Overflow(_))
+        ));
+        assert!(roll(d(6)?.signed(true) - 100).is_ok());
+        assert!(roll(d(6)? - 100 + d(4)?.signed(true)).is_ok());
+        Ok(())
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn default() {
+        assert_eq!(*D20, DiceExpr::default());
+        assert_eq!(DiceExpr::try_from("d20"), Ok(DiceExpr::default()))
+    }
+}
@@ -0,0 +1,205 @@
+use core::cmp::Ordering;
+use core::ops::{Add, Mul, Neg, Sub};
+
+/// An exact fraction, used to evaluate expressions containing division
+/// without rounding until a whole number is needed. It's held in wider
+/// integers than the totals it produces so that arithmetic on them can't
+/// overflow before it's rounded, and saturates at their bounds rather than
+/// wrapping if it somehow does.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Ratio {
+    num: i128,
+    den: i128,
+}
+
+fn gcd(a: i128, b: i128) -> i128 {
+    match b {
+        0 => a.saturating_abs(),
+        b => gcd(b, a.checked_rem(b).unwrap_or(0)),
+    }
+}
+
+/// Narrows a whole number back to a total, saturating at its bounds.
+fn saturate(n: i128) -> i64 {
+    n.clamp(i64::MIN.into(), i64::MAX.into()) as i64
+}
+
+impl Ratio {
+    /// Creates the fraction `num / den`, or `None` if `den` is zero.
+    pub fn new(num: i64, den: i64) -> Option<Self> {
+        Ratio::reduce(num.into(), den.into())
+    }
+
+    fn reduce(num: i128, den: i128) -> Option<Self> {
+        match den {
+            0 => None,
+            den => {
+                let g = gcd(num, den) * den.signum();
+                Some(Ratio {
+                    num: num.saturating_div(g),
+                    den: den.saturating_div(g),
+                })
+            }
+        }
+    }
+
+    pub fn floor(self) -> i64 {
+        saturate(self.num.div_euclid(self.den))
+    }
+
+    /// Rounds down like `floor`, or `None` if that's too large to be held
+    /// in a total.
+    pub fn checked_floor(self) -> Option<i64> {
+        i64::try_from(self.num.div_euclid(self.den)).ok()
+    }
+
+    pub fn ceil(self) -> i64 {
+        saturate(
+            self.num
+                .saturating_neg()
+                .div_euclid(self.den)
+                .saturating_neg(),
+        )
+    }
+
+    /// Rounds to the nearest whole number, with halves rounded away from
+    /// zero.
+    pub fn round(self) -> i64 {
+        let half = (self.num.saturating_abs().saturating_mul(2))
+            .saturating_add(self.den)
+            .div_euclid(self.den.saturating_mul(2));
+        saturate(half * self.num.signum())
+    }
+
+    pub fn abs(self) -> Self {
+        Ratio {
+            num: self.num.saturating_abs(),
+            den: self.den,
+        }
+    }
+
+    pub fn checked_div(self, other: Self) -> Option<Self> {
+        Ratio::reduce(
+            self.num.saturating_mul(other.den),
+            self.den.saturating_mul(other.num),
+        )
+    }
+}
+
+impl From<i64> for Ratio {
+    fn from(n: i64) -> Self {
+        Ratio {
+            num: n.into(),
+            den: 1,
+        }
+    }
+}
+
+impl Ord for Ratio {
+    fn cmp(&self, other: &Self) -> Ordering {
+        (self.num.saturating_mul(other.den)).cmp(&other.num.saturating_mul(self.den))
+    }
+}
+
+impl PartialOrd for Ratio {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Add for Ratio {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        Ratio::reduce(
+            self.num
+                .saturating_mul(other.den)
+                .saturating_add(other.num.saturating_mul(self.den)),
+            self.den.saturating_mul(other.den),
+        )
+        .unwrap()
+    }
+}
+
+impl Sub for Ratio {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        self + -other
+    }
+}
+
+impl Mul for Ratio {
+    type Output = Self;
+
+    fn mul(self, other: Self) -> Self {
+        Ratio::reduce(
+            self.num.saturating_mul(other.num),
+            self.den.saturating_mul(other.den),
+        )
+        .unwrap()
+    }
+}
+
+impl Neg for Ratio {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Ratio {
+            num: self.num.saturating_neg(),
+            den: self.den,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_reduces() {
+        assert_eq!(Ratio::new(2, 4), Ratio::new(-1, -2))
+    }
+
+    #[test]
+    fn new_zero_denominator() {
+        assert_eq!(None, Ratio::new(1, 0))
+    }
+
+    #[test]
+    fn rounding() {
+        let r = Ratio::new(7, 2).unwrap();
+
+        assert_eq!((3, 4, 4), (r.floor(), r.ceil(), r.round()));
+        assert_eq!((-4, -3, -4), ((-r).floor(), (-r).ceil(), (-r).round()))
+    }
+
+    #[test]
+    fn exact_arithmetic() {
+        let third = Ratio::new(1, 3).unwrap();
+
+        assert_eq!(Ratio::from(1), third * Ratio::from(3));
+        assert_eq!(Ratio::from(1), third + third + third)
+    }
+
+    #[test]
+    fn ordering() {
+        assert!(Ratio::new(1, 3) < Ratio::new(1, 2));
+        assert!(Ratio::new(-1, 2) < Ratio::new(-1, 3))
+    }
+
+    #[test]
+    fn saturates() {
+        let min = Ratio::new(i64::MIN, -1).unwrap();
+
+        assert_eq!(i64::MAX, min.floor());
+        assert_eq!(None, min.checked_floor());
+        assert_eq!(Some(i64::MIN), (-min).checked_floor());
+        assert_eq!(i64::MIN, (-min).ceil());
+        assert_eq!(Ratio::new(1, 1), Ratio::new(i64::MIN, i64::MIN));
+
+        let max = Ratio::from(i64::MAX);
+        assert_eq!(i64::MAX, (max * max * max).floor());
+        assert_eq!(i64::MIN, (-(max * max * max) - max).ceil())
+    }
+}
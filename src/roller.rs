@@ -0,0 +1,27 @@
+use rand::Rng;
+
+/// A source of die rolls. Every random number generator is one, rolling
+/// each die uniformly, and a fixed or scripted roller can stand in for one
+/// to roll expressions predictably.
+pub trait Roller {
+    /// Rolls a single die of `sides` sides, from 1 up to `sides`.
+    fn roll_die(&mut self, sides: u32) -> u32;
+}
+
+impl<R: Rng + ?Sized> Roller for R {
+    fn roll_die(&mut self, sides: u32) -> u32 {
+        self.gen_range(1..=sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn test_roll() {
+        let mut rng = StepRng::new(1, 0);
+        assert_eq!(rng.roll_die(4), 1);
+    }
+}
@@ -0,0 +1,97 @@
+#[cfg(feature = "std")]
+use crate::audit::RollSource;
+use crate::entropy::{self, EntropySource};
+use crate::expr::{DiceExpr, DiceExprError, RollResult, Vars};
+use crate::roller::Roller;
+use alloc::vec::Vec;
+
+/// Rolls expressions reproducibly from a seed, so that the same seed and
+/// expressions always give the same results, across runs and releases.
+///
+/// Numbers are drawn with SplitMix64 (Steele, Lea and Flood, 2014), whose
+/// state starts as the seed, and each die is rolled from them as by an
+/// `EntropyRoller`. Neither depends on `rand`, whose algorithms can change
+/// between its releases.
+#[derive(Debug, PartialEq, Eq, Clone, Hash)]
+pub struct SeededRoller {
+    state: u64,
+}
+
+impl SeededRoller {
+    pub fn new(seed: u64) -> Self {
+        SeededRoller { state: seed }
+    }
+
+    /// Rolls the expression once, ignoring any repeat count and taking any
+    /// variables as zero.
+    pub fn roll(&mut self, expr: &DiceExpr) -> Result<RollResult, DiceExprError> {
+        expr.roll_with_roller(self)
+    }
+
+    /// Rolls the expression as many times as its repeat count, returning
+    /// each result separately.
+    pub fn roll_repeated(&mut self, expr: &DiceExpr) -> Result<Vec<RollResult>, DiceExprError> {
+        expr.check_rollable()?;
+        Ok(expr.roll_repeated_with(&Vars::new(), |sides| self.roll_die(sides)))
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn source(&self) -> RollSource {
+        RollSource::Seeded(self.state)
+    }
+}
+
+impl EntropySource for SeededRoller {
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+}
+
+impl Roller for SeededRoller {
+    fn roll_die(&mut self, sides: u32) -> u32 {
+        entropy::uniform(self, sides)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn next_u64() {
+        let mut roller = SeededRoller::new(0);
+
+        assert_eq!(0xe220a8397b1dcdaf, roller.next_u64());
+        assert_eq!(0x6e789e6aa1b965f4, roller.next_u64())
+    }
+
+    #[test]
+    fn roll_reproducible() {
+        let expr = DiceExpr::try_from("4d6kh3+1d20").unwrap();
+        let first = SeededRoller::new(42).roll(&expr).unwrap();
+
+        assert_eq!(Ok(first.clone()), SeededRoller::new(42).roll(&expr));
+        assert_eq!(
+            vec![2, 2, 1, 1, 11],
+            first
+                .dice
+                .iter()
+                .flat_map(|d| d.rolls.iter().map(|r| r.value))
+                .collect::<Vec<_>>()
+        )
+    }
+
+    #[test]
+    fn roll_repeated() {
+        let expr = DiceExpr::try_from("3x(1d6)").unwrap();
+        let mut roller = SeededRoller::new(7);
+
+        assert_eq!(3, roller.roll_repeated(&expr).unwrap().len());
+        assert_ne!(roller, SeededRoller::new(7))
+    }
+}
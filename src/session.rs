@@ -0,0 +1,86 @@
+use crate::expr::{DiceExpr, DiceExprError, RollResult};
+use crate::seeded::SeededRoller;
+use alloc::vec::Vec;
+
+/// A sequence of rolls from a single seed, keeping each expression rolled
+/// as its script, so that `Session::replay` can roll the whole sequence
+/// again exactly, as for a bug report or checking play by post.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Session {
+    seed: u64,
+    roller: SeededRoller,
+    script: Vec<DiceExpr>,
+}
+
+impl Session {
+    /// Starts recording a session with a random seed.
+    #[cfg(feature = "std")]
+    pub fn record() -> Self {
+        Session::with_seed(rand::random())
+    }
+
+    /// Starts recording a session with the given seed.
+    pub fn with_seed(seed: u64) -> Self {
+        Session {
+            seed,
+            roller: SeededRoller::new(seed),
+            script: Vec::new(),
+        }
+    }
+
+    /// Rolls the expression once, ignoring any repeat count and taking any
+    /// variables as zero, and adds it to the script if it could be rolled.
+    pub fn roll(&mut self, expr: &DiceExpr) -> Result<RollResult, DiceExprError> {
+        let result = self.roller.roll(expr)?;
+        self.script.push(expr.clone());
+        Ok(result)
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Each expression rolled so far, in the order they were rolled.
+    pub fn script(&self) -> &[DiceExpr] {
+        &self.script
+    }
+
+    /// Rolls each expression of a recorded session's script in turn from
+    /// its seed, giving the same results it did.
+    pub fn replay<'a, I: IntoIterator<Item = &'a DiceExpr>>(
+        seed: u64,
+        script: I,
+    ) -> Result<Vec<RollResult>, DiceExprError> {
+        let mut session = Session::with_seed(seed);
+        script.into_iter().map(|expr| session.roll(expr)).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn replay() {
+        let mut session = Session::record();
+        let rolls: Vec<RollResult> = ["1d20+5", "2d6", "4d6kh3", "1d20+5"]
+            .iter()
+            .map(|s| session.roll(&DiceExpr::try_from(*s).unwrap()).unwrap())
+            .collect();
+
+        assert_eq!(4, session.script().len());
+        assert_eq!(Ok(rolls), Session::replay(session.seed(), session.script()))
+    }
+
+    #[test]
+    fn replay_partial() {
+        let expr = DiceExpr::try_from("3d6").unwrap();
+        let mut session = Session::with_seed(9);
+        let first = session.roll(&expr).unwrap();
+        session.roll(&expr).unwrap();
+
+        assert_eq!(Ok(vec![first]), Session::replay(9, &session.script()[..1]))
+    }
+}
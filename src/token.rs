@@ -0,0 +1,108 @@
+use alloc::vec::Vec;
+
+/// What a token of a dice expression is made of.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Kind {
+    /// A run of digits.
+    Number,
+    /// A single letter or underscore. Letters aren't grouped into words,
+    /// since the modifiers of a dice term run together, as in `4dFkh3`.
+    Letter,
+    /// A run of whitespace.
+    Space,
+    /// Any other single character.
+    Symbol,
+}
+
+/// A token of a dice expression, and where it starts in the expression.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token<'a> {
+    pub kind: Kind,
+    pub text: &'a str,
+    pub offset: usize,
+}
+
+impl Token<'_> {
+    /// Whether the token is the single letter or symbol `c`.
+    pub fn is(&self, c: char) -> bool {
+        matches!(self.kind, Kind::Letter | Kind::Symbol) && self.text.starts_with(c)
+    }
+}
+
+/// Splits a dice expression into tokens.
+pub fn tokenize(s: &str) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = s.char_indices().peekable();
+
+    while let Some((offset, c)) = chars.next() {
+        let kind = kind(c);
+        let mut end = offset + c.len_utf8();
+
+        if let Kind::Number | Kind::Space = kind {
+            while let Some(&(i, c)) = chars.peek() {
+                if self::kind(c) != kind {
+                    break;
+                }
+                end = i + c.len_utf8();
+                chars.next();
+            }
+        }
+
+        tokens.push(Token {
+            kind,
+            text: &s[offset..end],
+            offset,
+        });
+    }
+
+    tokens
+}
+
+fn kind(c: char) -> Kind {
+    match c {
+        c if c.is_ascii_digit() => Kind::Number,
+        c if c.is_ascii_alphabetic() || c == '_' => Kind::Letter,
+        c if c.is_whitespace() => Kind::Space,
+        _ => Kind::Symbol,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    #[test]
+    fn tokenize_dice() {
+        let tokens: Vec<(Kind, &str)> = tokenize("10d6kh3 + $str")
+            .iter()
+            .map(|t| (t.kind, t.text))
+            .collect();
+
+        assert_eq!(
+            vec![
+                (Kind::Number, "10"),
+                (Kind::Letter, "d"),
+                (Kind::Number, "6"),
+                (Kind::Letter, "k"),
+                (Kind::Letter, "h"),
+                (Kind::Number, "3"),
+                (Kind::Space, " "),
+                (Kind::Symbol, "+"),
+                (Kind::Space, " "),
+                (Kind::Symbol, "$"),
+                (Kind::Letter, "s"),
+                (Kind::Letter, "t"),
+                (Kind::Letter, "r"),
+            ],
+            tokens
+        )
+    }
+
+    #[test]
+    fn tokenize_offsets() {
+        let offsets: Vec<usize> = tokenize("d20 ≥ 15").iter().map(|t| t.offset).collect();
+
+        assert_eq!(vec![0, 1, 3, 4, 7, 8], offsets)
+    }
+}
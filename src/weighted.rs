@@ -0,0 +1,133 @@
+use crate::distribution::DiceDistribution;
+use crate::roller::Roller;
+use alloc::vec::Vec;
+use rand::distr::{Distribution, WeightError, WeightedIndex};
+#[cfg(feature = "std")]
+use rand::thread_rng;
+use rand::Rng;
+
+/// A die whose faces aren't all equally likely, such as a loaded die or a
+/// board game's die with repeated faces, each face rolled in proportion to
+/// its weight.
+#[derive(Debug, PartialEq, Clone)]
+pub struct WeightedDie {
+    faces: Vec<i64>,
+    index: WeightedIndex<u32>,
+}
+
+impl WeightedDie {
+    /// A die with the given faces and their weights. Fails if there are no
+    /// faces, if every weight is zero, or if the weights add up to more
+    /// than `u32::MAX`.
+    pub fn new<I: IntoIterator<Item = (i64, u32)>>(faces: I) -> Result<Self, WeightError> {
+        let (faces, weights): (Vec<i64>, Vec<u32>) = faces.into_iter().unzip();
+
+        Ok(WeightedDie {
+            faces,
+            index: WeightedIndex::new(weights)?,
+        })
+    }
+
+    /// A die numbered from 1 up, with the given weights in order, such as
+    /// `[1, 1, 1, 1, 1, 3]` for a d6 loaded to roll 6 as often as 1 to 3
+    /// together.
+    pub fn with_weights(weights: &[u32]) -> Result<Self, WeightError> {
+        WeightedDie::new((1..).zip(weights.iter().copied()))
+    }
+
+    /// Each face with its weight, in the order they were given.
+    pub fn faces(&self) -> impl Iterator<Item = (i64, u32)> + '_ {
+        self.faces.iter().copied().zip(self.index.weights())
+    }
+
+    /// The exact chance of rolling each face.
+    pub fn distribution(&self) -> DiceDistribution {
+        let total = self.index.total_weight() as f64;
+        DiceDistribution::new(self.faces().map(|(face, w)| (face, w as f64 / total)))
+    }
+
+    #[cfg(feature = "std")]
+    pub fn roll(&self) -> i64 {
+        self.roll_with_rng(&mut thread_rng())
+    }
+
+    pub fn roll_with_rng<R: Rng + ?Sized>(&self, rng: &mut R) -> i64 {
+        self.faces[self.index.sample(rng)]
+    }
+
+    /// Rolls the die with `roller`, rolling a die with as many sides as the
+    /// weights add up to and giving each face as many of its sides as its
+    /// weight.
+    pub fn roll_with_roller<R: Roller + ?Sized>(&self, roller: &mut R) -> i64 {
+        let mut side = roller.roll_die(self.index.total_weight());
+        for (face, weight) in self.faces() {
+            if side <= weight {
+                return face;
+            }
+            side -= weight;
+        }
+        unreachable!("rolled past the last face")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::seeded::SeededRoller;
+
+    /// Rolls each die as its given side.
+    struct Fixed(u32);
+
+    impl Roller for Fixed {
+        fn roll_die(&mut self, _: u32) -> u32 {
+            self.0
+        }
+    }
+
+    #[test]
+    fn new() {
+        assert_eq!(
+            Err(WeightError::InvalidInput),
+            WeightedDie::with_weights(&[])
+        );
+        assert_eq!(
+            Err(WeightError::InsufficientNonZero),
+            WeightedDie::with_weights(&[0, 0])
+        );
+        assert_eq!(
+            Err(WeightError::Overflow),
+            WeightedDie::with_weights(&[u32::MAX, 1])
+        )
+    }
+
+    #[test]
+    fn roll_with_roller() {
+        let die = WeightedDie::new([(0, 2), (5, 0), (10, 1)]).unwrap();
+
+        assert_eq!(0, die.roll_with_roller(&mut Fixed(1)));
+        assert_eq!(0, die.roll_with_roller(&mut Fixed(2)));
+        assert_eq!(10, die.roll_with_roller(&mut Fixed(3)))
+    }
+
+    #[test]
+    fn roll_never_zero_weight() {
+        let die = WeightedDie::with_weights(&[0, 1, 0, 3]).unwrap();
+        let mut roller = SeededRoller::new(5);
+
+        for _ in 0..100 {
+            #[cfg(feature = "std")]
+            assert!([2, 4].contains(&die.roll()));
+            assert!([2, 4].contains(&die.roll_with_roller(&mut roller)))
+        }
+    }
+
+    #[test]
+    fn distribution() {
+        let die = WeightedDie::with_weights(&[1, 1, 1, 1, 1, 3]).unwrap();
+        let distribution = die.distribution();
+
+        assert_eq!(0.375, distribution.pmf(6));
+        assert_eq!(0.125, distribution.pmf(1));
+        assert_eq!(4.125, distribution.mean())
+    }
+}